@@ -1,13 +1,33 @@
 use std::fs;
-use std::path::PathBuf;
+use std::io::{
+    Read,
+    Write,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
 
 use clap::{
     Parser,
     Subcommand,
+    ValueEnum,
+};
+use esp_nvs::encryption::NVS_KEY_SIZE;
+use esp_nvs_partition_tool::encryption::{
+    generate_keys_partition,
+    load_keys,
 };
 use esp_nvs_partition_tool::{
+    DuplicatePolicy,
     EntryContent,
+    GenerateOptions,
+    MergePolicy,
+    NvsEncryptionKeys,
     NvsPartition,
+    TrailingPage,
+    emit_c_header,
+    emit_rust_static,
 };
 
 #[derive(Parser)]
@@ -16,83 +36,639 @@ use esp_nvs_partition_tool::{
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Don't print status messages
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print additional details, e.g. the number of entries per namespace
+    #[arg(short, long, global = true)]
+    verbose: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Generate NVS partition binary from CSV file
     Generate {
-        /// Input CSV file path
+        /// Input CSV file path, `-` for stdin
         input: PathBuf,
 
-        /// Output binary file path
+        /// Output binary file path, `-` for stdout
         output: PathBuf,
 
-        /// Partition size in bytes (must be multiple of 4096)
+        /// Partition size in bytes (must be multiple of 4096), or `auto` for the smallest size
+        /// that fits all entries
         #[arg(short, long, value_parser = parse_size)]
-        size: usize,
+        size: PartitionSize,
+
+        /// How to handle entries defined multiple times
+        #[arg(long, value_enum, default_value_t = OnDuplicate::LastWins)]
+        on_duplicate: OnDuplicate,
+
+        /// State of the last page the entries were written to
+        #[arg(long, value_enum, default_value_t = Trailing::Auto)]
+        trailing_page: Trailing,
+
+        /// Fixed index of a namespace as `NAME=INDEX`, may be repeated. Other namespaces get the
+        /// lowest unused index
+        #[arg(long, value_parser = parse_namespace_index)]
+        namespace_index: Vec<(String, u8)>,
+
+        /// Encrypt the entries like ESP-IDF's encrypted NVS, requires `--keys`
+        #[arg(long, requires = "keys")]
+        encrypt: bool,
+
+        /// File with the encryption keys, either an `nvs_keys` partition or the 64 bytes of both
+        /// keys
+        #[arg(long, requires = "encrypt")]
+        keys: Option<PathBuf>,
+
+        /// Also write a JSON summary of the generated partition, e.g. the namespace indices, to
+        /// this file
+        #[arg(long)]
+        manifest: Option<PathBuf>,
     },
     /// Parse NVS partition binary to CSV file
     Parse {
+        /// Input binary file path, `-` for stdin
+        input: PathBuf,
+
+        /// Output file path, `-` for stdout
+        output: PathBuf,
+
+        /// Only keep the entries of this namespace
+        #[arg(long)]
+        namespace: Option<String>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = ParseFormat::Csv)]
+        format: ParseFormat,
+
+        /// Decrypt the entries of an encrypted partition, requires `--keys`
+        #[arg(long, requires = "keys")]
+        decrypt: bool,
+
+        /// File with the encryption keys, either an `nvs_keys` partition or the 64 bytes of both
+        /// keys
+        #[arg(long, requires = "decrypt")]
+        keys: Option<PathBuf>,
+    },
+    /// Compare two partitions, given as binary or CSV files, and list the changed entries
+    ///
+    /// Exits with status 1 if the partitions differ.
+    Diff {
+        /// Old partition file path
+        old: PathBuf,
+
+        /// New partition file path
+        new: PathBuf,
+    },
+    /// Print the value of a single entry of a partition, given as binary or CSV file
+    ///
+    /// Exits with status 1 if the entry doesn't exist.
+    Lookup {
+        /// Input file path
+        input: PathBuf,
+
+        /// Namespace of the entry
+        namespace: String,
+
+        /// Key of the entry
+        key: String,
+    },
+    /// Print a hash of the values of a partition, given as binary or CSV file
+    ///
+    /// The hash only changes with the values by namespace and key, not with the order of the
+    /// entries or the layout of the binary.
+    Fingerprint {
+        /// Input file path
+        input: PathBuf,
+    },
+    /// Check the structure of a partition binary and list all problems
+    ///
+    /// Exits with status 1 if problems were found.
+    Validate {
         /// Input binary file path
         input: PathBuf,
+    },
+    /// Print the page headers and entries of a partition binary for debugging
+    Dump {
+        /// Input binary file path, `-` for stdin
+        input: PathBuf,
+    },
+    /// Write a partition, given as binary or CSV file, as C header or Rust source to embed it in
+    /// firmware
+    EmitHeader {
+        /// Input file path, `-` for stdin
+        input: PathBuf,
+
+        /// Output file path, `-` for stdout
+        output: PathBuf,
+
+        /// Partition size for CSV input, defaults to the smallest size that fits
+        #[arg(short, long, value_parser = parse_size)]
+        size: Option<PartitionSize>,
+
+        /// Name of the array, upper case for Rust
+        #[arg(long, default_value = "nvs_image")]
+        symbol: String,
+
+        /// Output language
+        #[arg(short, long, value_enum, default_value_t = HeaderFormat::C)]
+        format: HeaderFormat,
+    },
+    /// Merge multiple partitions, given as binary or CSV files, into one
+    Merge {
+        /// Input file paths, later inputs are merged into earlier ones
+        #[arg(required = true, num_args = 2..)]
+        inputs: Vec<PathBuf>,
+
+        /// Output file path
+        #[arg(short, long)]
+        output: PathBuf,
 
-        /// Output CSV file path
+        /// Generate a partition binary of the given size, or `auto` for the smallest size that
+        /// fits, instead of a CSV file
+        #[arg(short, long, value_parser = parse_size)]
+        size: Option<PartitionSize>,
+
+        /// How to handle entries present in multiple inputs
+        #[arg(short, long, value_enum, default_value_t = Policy::Overwrite)]
+        policy: Policy,
+    },
+    /// Generate or read the `nvs_keys` partition holding the keys of an encrypted partition
+    Keys {
+        #[command(subcommand)]
+        command: KeysCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeysCommand {
+    /// Write an `nvs_keys` partition binary like `nvs_partition_gen.py generate-key`, with random
+    /// keys unless both keys are given
+    Generate {
+        /// Output binary file path, `-` for stdout
         output: PathBuf,
+
+        /// Key to encrypt the data (eky), as 64 hex digits
+        #[arg(long, value_parser = parse_key, requires = "tweak_key")]
+        data_key: Option<[u8; NVS_KEY_SIZE]>,
+
+        /// Key to encrypt the tweak (tky), as 64 hex digits
+        #[arg(long, value_parser = parse_key, requires = "data_key")]
+        tweak_key: Option<[u8; NVS_KEY_SIZE]>,
     },
+    /// Print both keys of an `nvs_keys` partition binary, or a file containing just the 64 bytes
+    /// of both keys, as hex
+    Parse {
+        /// Input binary file path, `-` for stdin
+        input: PathBuf,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Policy {
+    /// Keep the entry of the earlier input
+    KeepExisting,
+    /// Use the entry of the later input
+    Overwrite,
+    /// Abort on the first conflicting entry
+    Error,
+}
+
+impl From<Policy> for MergePolicy {
+    fn from(policy: Policy) -> Self {
+        match policy {
+            Policy::KeepExisting => MergePolicy::KeepExisting,
+            Policy::Overwrite => MergePolicy::Overwrite,
+            Policy::Error => MergePolicy::Error,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OnDuplicate {
+    /// Abort and list all duplicates
+    Error,
+    /// Use the last definition
+    LastWins,
+    /// Use the first definition
+    FirstWins,
+}
+
+impl From<OnDuplicate> for DuplicatePolicy {
+    fn from(policy: OnDuplicate) -> Self {
+        match policy {
+            OnDuplicate::Error => DuplicatePolicy::Error,
+            OnDuplicate::LastWins => DuplicatePolicy::LastWins,
+            OnDuplicate::FirstWins => DuplicatePolicy::FirstWins,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Trailing {
+    /// `ACTIVE`, unless the last entry filled it up
+    Auto,
+    /// Always `ACTIVE`, the device continues writing to it
+    Active,
+    /// Always `FULL`, the device starts a new page
+    Full,
+}
+
+impl From<Trailing> for TrailingPage {
+    fn from(trailing: Trailing) -> Self {
+        match trailing {
+            Trailing::Auto => TrailingPage::Auto,
+            Trailing::Active => TrailingPage::Active,
+            Trailing::Full => TrailingPage::Full,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ParseFormat {
+    /// CSV in the format accepted by `generate`
+    Csv,
+    /// JSON array of entries, see `NvsPartition::to_json`
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum HeaderFormat {
+    /// `static const uint8_t` array with a size define
+    C,
+    /// `pub static` array with a size constant
+    Rust,
+}
+
+#[derive(Clone, Copy)]
+enum PartitionSize {
+    Auto,
+    Bytes(usize),
+}
+
+impl PartitionSize {
+    fn resolve(self, partition: &NvsPartition) -> Result<usize, esp_nvs_partition_tool::Error> {
+        match self {
+            PartitionSize::Auto => partition.min_size(),
+            PartitionSize::Bytes(size) => Ok(size),
+        }
+    }
+}
+
+fn parse_size(s: &str) -> Result<PartitionSize, String> {
+    if s == "auto" {
+        Ok(PartitionSize::Auto)
+    } else if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        usize::from_str_radix(hex, 16)
+            .map(PartitionSize::Bytes)
+            .map_err(|e| e.to_string())
+    } else {
+        s.parse::<usize>().map(PartitionSize::Bytes).map_err(|e| e.to_string())
+    }
+}
+
+fn parse_key(s: &str) -> Result<[u8; NVS_KEY_SIZE], String> {
+    let key = hex::decode(s).map_err(|e| e.to_string())?;
+    key.try_into()
+        .map_err(|key: Vec<u8>| format!("expected {NVS_KEY_SIZE} bytes, got {}", key.len()))
+}
+
+fn parse_namespace_index(s: &str) -> Result<(String, u8), String> {
+    let (namespace, index) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=INDEX, got '{s}'"))?;
+    let index = index.parse::<u8>().map_err(|e| e.to_string())?;
+    Ok((namespace.to_string(), index))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+/// Print a status message to stderr, stdout is reserved for the output.
+macro_rules! status {
+    ($verbosity:expr, $($arg:tt)*) => {
+        if $verbosity >= Verbosity::Normal {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Print a status message to stderr, only with `--verbose`.
+macro_rules! detail {
+    ($verbosity:expr, $($arg:tt)*) => {
+        if $verbosity >= Verbosity::Verbose {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Print the number of entries per namespace with `--verbose`.
+fn print_namespaces(verbosity: Verbosity, partition: &NvsPartition) {
+    for (namespace, entries) in partition.by_namespace() {
+        detail!(verbosity, "  {namespace}: {} entries", entries.len());
+    }
 }
 
-fn parse_size(s: &str) -> Result<usize, String> {
-    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
-        usize::from_str_radix(hex, 16).map_err(|e| e.to_string())
+fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+fn display(path: &Path) -> std::path::Display<'_> {
+    if is_stdio(path) {
+        Path::new("<stdio>").display()
     } else {
-        s.parse::<usize>().map_err(|e| e.to_string())
+        path.display()
+    }
+}
+
+/// Read the whole file, or stdin for `-`.
+fn read_input(path: &Path) -> std::io::Result<Vec<u8>> {
+    if !is_stdio(path) {
+        return fs::read(path);
+    }
+    let mut data = Vec::new();
+    std::io::stdin().lock().read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Write the whole file, or stdout for `-`. The standard output handle writes the bytes as they
+/// are, there is no line ending translation on any platform.
+fn write_output(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if !is_stdio(path) {
+        return fs::write(path, data);
+    }
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(data)?;
+    stdout.flush()
+}
+
+/// Read a binary or CSV partition. Relative file paths in a CSV are resolved against the CSV
+/// file's parent directory.
+fn read_partition(input: &Path) -> Result<NvsPartition, Box<dyn std::error::Error>> {
+    let mut partition = NvsPartition::try_from(fs::read(input)?)?;
+    if let Some(base) = input.parent() {
+        resolve_file_paths(&mut partition, base);
+    }
+    Ok(partition)
+}
+
+fn resolve_file_paths(partition: &mut NvsPartition, base: &Path) {
+    for entry in &mut partition.entries {
+        if let EntryContent::File { file_path, .. } = &mut entry.content {
+            if file_path.is_relative() {
+                *file_path = base.join(&file_path);
+            }
+        }
     }
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    let verbosity = match (cli.quiet, cli.verbose) {
+        (true, _) => Verbosity::Quiet,
+        (_, true) => Verbosity::Verbose,
+        _ => Verbosity::Normal,
+    };
 
     match cli.command {
-        Commands::Generate { input, output, size } => {
-            println!("Parsing CSV file: {}", input.display());
-            let content = fs::read_to_string(&input)?;
+        Commands::Generate {
+            input,
+            output,
+            size,
+            on_duplicate,
+            trailing_page,
+            namespace_index,
+            encrypt: _,
+            keys,
+            manifest,
+        } => {
+            status!(verbosity, "Parsing CSV file: {}", display(&input));
+            let content = String::from_utf8(read_input(&input)?)?;
             let mut partition = NvsPartition::try_from_str(&content)?;
 
             // Resolve relative file paths against the CSV file's parent
-            // directory.
-            if let Some(base) = input.parent() {
-                for entry in &mut partition.entries {
-                    if let EntryContent::File { file_path, .. } = &mut entry.content {
-                        if file_path.is_relative() {
-                            *file_path = base.join(&file_path);
-                        }
-                    }
+            // directory, or the working directory when reading from stdin.
+            if let Some(base) = input.parent().filter(|_| !is_stdio(&input)) {
+                resolve_file_paths(&mut partition, base);
+            }
+
+            partition.dedup(on_duplicate.into())?;
+
+            status!(verbosity, "Found {} entries", partition.entries.len());
+            print_namespaces(verbosity, &partition);
+
+            let size = size.resolve(&partition)?;
+
+            status!(verbosity, "Generating partition binary...");
+            let options = GenerateOptions {
+                trailing_page: trailing_page.into(),
+                namespace_indices: namespace_index.into_iter().collect(),
+            };
+            let mut data = partition.generate_partition_with_options(size, &options)?;
+            if let Some(manifest) = manifest {
+                // summarizes the plain partition, an encrypted one can't be inspected
+                status!(verbosity, "Writing manifest: {}", manifest.display());
+                fs::write(
+                    &manifest,
+                    partition.manifest_with_options(size, &options)?.to_json()? + "\n",
+                )?;
+            }
+            if let Some(keys) = keys {
+                status!(verbosity, "Encrypting with keys from: {}", keys.display());
+                let keys = load_keys(&fs::read(&keys)?)?;
+                data = esp_nvs_partition_tool::encryption::encrypt_partition(&data, &keys)?;
+            }
+            write_output(&output, &data)?;
+
+            status!(verbosity, "Successfully generated NVS partition: {}", display(&output));
+            status!(
+                verbosity,
+                "Size: {} bytes ({} pages)",
+                size,
+                size / esp_nvs::FLASH_SECTOR_SIZE
+            );
+
+            Ok(())
+        }
+        Commands::Parse {
+            input,
+            output,
+            namespace,
+            format,
+            decrypt: _,
+            keys,
+        } => {
+            status!(verbosity, "Parsing binary file: {}", display(&input));
+            let mut data = read_input(&input)?;
+            if let Some(keys) = keys {
+                status!(verbosity, "Decrypting with keys from: {}", keys.display());
+                let keys = load_keys(&fs::read(&keys)?)?;
+                data = esp_nvs_partition_tool::encryption::decrypt_partition(&data, &keys)?;
+            }
+            let partition = match namespace {
+                Some(namespace) => NvsPartition::parse_partition_filtered(&data, &namespace)?,
+                None => NvsPartition::try_from_bytes(data)?,
+            };
+            status!(verbosity, "Found {} entries", partition.entries.len());
+            print_namespaces(verbosity, &partition);
+
+            let content = match format {
+                ParseFormat::Csv => {
+                    status!(verbosity, "Writing CSV file...");
+                    partition.to_csv()?
+                }
+                ParseFormat::Json => {
+                    status!(verbosity, "Writing JSON file...");
+                    partition.to_json()? + "\n"
+                }
+            };
+            write_output(&output, content.as_bytes())?;
+
+            status!(verbosity, "Successfully parsed NVS partition to: {}", display(&output));
+
+            Ok(())
+        }
+        Commands::Diff { old, new } => {
+            let diff = read_partition(&old)?.diff(&read_partition(&new)?);
+            if diff.is_empty() {
+                println!("No differences");
+                return Ok(());
+            }
+
+            print!("{diff}");
+            std::process::exit(1);
+        }
+        Commands::Lookup { input, namespace, key } => {
+            let partition = read_partition(&input)?;
+            match partition.lookup(&namespace, &key).map(|entry| &entry.content) {
+                Ok(EntryContent::Data(value)) => println!("{value}"),
+                Ok(EntryContent::File { encoding, file_path }) => {
+                    println!("file {} {}", encoding, file_path.display())
+                }
+                // only fails for a missing namespace or key
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(1);
+                }
+            }
+
+            Ok(())
+        }
+        Commands::Fingerprint { input } => {
+            println!("{:016x}", read_partition(&input)?.content_fingerprint()?);
+            Ok(())
+        }
+        Commands::Validate { input } => {
+            let report = NvsPartition::validate(&fs::read(&input)?)?;
+            if report.is_valid() {
+                println!("No problems found");
+                return Ok(());
+            }
+
+            print!("{report}");
+            std::process::exit(1);
+        }
+        Commands::Dump { input } => {
+            print!("{}", NvsPartition::annotate(&read_input(&input)?));
+            Ok(())
+        }
+        Commands::EmitHeader {
+            input,
+            output,
+            size,
+            symbol,
+            format,
+        } => {
+            let mut data = read_input(&input)?;
+            // same detection as `NvsPartition::try_from`, binaries are embedded as they are
+            if data.first().is_some_and(|&b| b < 0x80) || data.starts_with("\u{feff}".as_bytes()) {
+                let mut partition = NvsPartition::try_from_str(String::from_utf8(data)?)?;
+                if let Some(base) = input.parent().filter(|_| !is_stdio(&input)) {
+                    resolve_file_paths(&mut partition, base);
                 }
+                let size = size.unwrap_or(PartitionSize::Auto).resolve(&partition)?;
+                data = partition.generate_partition(size)?;
+            } else if size.is_some() {
+                return Err("--size only applies to CSV input".into());
             }
 
-            println!("Found {} entries", partition.entries.len());
+            let content = match format {
+                HeaderFormat::C => emit_c_header(&data, &symbol),
+                HeaderFormat::Rust => emit_rust_static(&data, &symbol),
+            };
+            write_output(&output, content.as_bytes())?;
+
+            status!(
+                verbosity,
+                "Successfully wrote {} bytes to: {}",
+                data.len(),
+                display(&output)
+            );
+
+            Ok(())
+        }
+        Commands::Merge {
+            inputs,
+            output,
+            size,
+            policy,
+        } => {
+            let mut partition = NvsPartition::default();
+            for input in &inputs {
+                status!(verbosity, "Merging: {}", input.display());
+                partition.merge(read_partition(input)?, policy.into())?;
+            }
+            status!(verbosity, "Found {} entries", partition.entries.len());
+            print_namespaces(verbosity, &partition);
 
-            println!("Generating partition binary...");
-            let data = partition.generate_partition(size)?;
-            fs::write(&output, &data)?;
+            match size {
+                Some(size) => fs::write(&output, partition.generate_partition(size.resolve(&partition)?)?)?,
+                None => fs::write(&output, partition.to_csv()?)?,
+            }
 
-            println!("Successfully generated NVS partition: {}", output.display());
-            println!("Size: {} bytes ({} pages)", size, size / esp_nvs::FLASH_SECTOR_SIZE);
+            status!(verbosity, "Successfully merged partitions to: {}", output.display());
 
             Ok(())
         }
-        Commands::Parse { input, output } => {
-            println!("Parsing binary file: {}", input.display());
-            let data = fs::read(&input)?;
-            let partition = NvsPartition::try_from_bytes(data)?;
-            println!("Found {} entries", partition.entries.len());
+        Commands::Keys {
+            command:
+                KeysCommand::Generate {
+                    output,
+                    data_key,
+                    tweak_key,
+                },
+        } => {
+            let keys = match data_key.zip(tweak_key) {
+                Some((eky, tky)) => NvsEncryptionKeys::new(eky, tky),
+                None => {
+                    let mut keys = [0; 2 * NVS_KEY_SIZE];
+                    getrandom::fill(&mut keys)?;
+                    load_keys(&keys)?
+                }
+            };
+            write_output(&output, &generate_keys_partition(&keys))?;
 
-            println!("Writing CSV file...");
-            let csv_content = partition.to_csv()?;
-            fs::write(&output, &csv_content)?;
+            status!(
+                verbosity,
+                "Successfully generated NVS keys partition: {}",
+                display(&output)
+            );
 
-            println!("Successfully parsed NVS partition to: {}", output.display());
+            Ok(())
+        }
+        Commands::Keys {
+            command: KeysCommand::Parse { input },
+        } => {
+            let keys = load_keys(&read_input(&input)?)?;
+            println!("eky: {}", hex::encode(keys.eky));
+            println!("tky: {}", hex::encode(keys.tky));
 
             Ok(())
         }
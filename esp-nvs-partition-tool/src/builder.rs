@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+
+use crate::NvsPartition;
+use crate::error::Error;
+use crate::partition::{
+    DataValue,
+    FileEncoding,
+    NvsEntry,
+    validate_key,
+};
+
+/// Fluent builder for an [`NvsPartition`], see [`NvsPartition::builder`].
+///
+/// Entries are added to the namespace selected by the last call to
+/// [`NvsPartitionBuilder::namespace`]. Problems like a missing namespace or
+/// a key that is too long are reported by [`NvsPartitionBuilder::build`].
+#[derive(Debug, Default)]
+pub struct NvsPartitionBuilder {
+    namespace: Option<String>,
+    entries: Vec<NvsEntry>,
+    error: Option<Error>,
+}
+
+macro_rules! primitive_setter {
+    ($name:ident, $type:ty, $variant:ident) => {
+        #[doc = concat!("Add a `", stringify!($type), "` entry.")]
+        pub fn $name(self, key: &str, value: $type) -> Self {
+            self.value(key, DataValue::$variant(value))
+        }
+    };
+}
+
+impl NvsPartitionBuilder {
+    /// Select the namespace for the following entries.
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+
+    primitive_setter!(u8, u8, U8);
+    primitive_setter!(i8, i8, I8);
+    primitive_setter!(u16, u16, U16);
+    primitive_setter!(i16, i16, I16);
+    primitive_setter!(u32, u32, U32);
+    primitive_setter!(i32, i32, I32);
+    primitive_setter!(u64, u64, U64);
+    primitive_setter!(i64, i64, I64);
+
+    /// Add a string entry.
+    pub fn string(self, key: &str, value: &str) -> Self {
+        self.value(key, DataValue::String(value.to_string()))
+    }
+
+    /// Add a binary entry.
+    pub fn blob(self, key: &str, value: impl Into<Vec<u8>>) -> Self {
+        self.value(key, DataValue::Binary(value.into()))
+    }
+
+    /// Add an entry with the given value.
+    pub fn value(self, key: &str, value: DataValue) -> Self {
+        self.push(|namespace| NvsEntry::new_data(namespace, key.to_string(), value))
+    }
+
+    /// Add an entry whose content is read from a file at generation time.
+    pub fn file(self, key: &str, encoding: FileEncoding, file_path: impl Into<PathBuf>) -> Self {
+        self.push(|namespace| NvsEntry::new_file(namespace, key.to_string(), encoding, file_path.into()))
+    }
+
+    /// Return the partition or the first problem encountered while adding entries.
+    pub fn build(self) -> Result<NvsPartition, Error> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(NvsPartition {
+                entries: self.entries,
+                empty_namespaces: vec![],
+            }),
+        }
+    }
+
+    fn push(mut self, entry: impl FnOnce(String) -> NvsEntry) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+
+        let Some(namespace) = self.namespace.clone() else {
+            self.error = Some(Error::MissingNamespace);
+            return self;
+        };
+
+        let entry = entry(namespace);
+        match validate_key(&entry.namespace).and_then(|_| validate_key(&entry.key)) {
+            Ok(()) => self.entries.push(entry),
+            Err(e) => self.error = Some(e),
+        }
+        self
+    }
+}
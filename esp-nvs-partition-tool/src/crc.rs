@@ -0,0 +1,23 @@
+//! The CRC32 checksums of the NVS format.
+//!
+//! All checksums are the little endian CRC32 used by ESP-IDF (`crc32_le` with an initial value
+//! of `0xFFFFFFFF`), they only differ in the bytes they cover.
+
+use esp_nvs::platform::software_crc32;
+
+/// CRC of variable length data, stored in the entry of a string, blob or blob chunk.
+pub fn crc32(data: &[u8]) -> u32 {
+    software_crc32(u32::MAX, data)
+}
+
+/// CRC of an entry, stored in its bytes 4..8. Covers the namespace index, type, span and chunk
+/// index (bytes 0..4) and the key and data (bytes 8..32).
+pub fn crc32_entry(entry: &[u8; 32]) -> u32 {
+    software_crc32(software_crc32(u32::MAX, &entry[0..4]), &entry[8..32])
+}
+
+/// CRC of a page header, stored in its bytes 28..32. Covers the sequence number, version and
+/// reserved bytes (bytes 4..28) but not the page state, which changes over the page's lifetime.
+pub fn crc32_page_header(header: &[u8; 32]) -> u32 {
+    software_crc32(u32::MAX, &header[4..28])
+}
@@ -1,16 +1,19 @@
+use std::num::IntErrorKind;
 use std::path::Path;
 
-use base64::Engine;
-
 use crate::NvsPartition;
 use crate::error::Error;
 use crate::partition::{
+    ArrayValue,
     DataValue,
     FileEncoding,
     NvsEntry,
+    decode_base64,
     validate_key,
 };
 
+pub(crate) const UTF8_BOM: &str = "\u{feff}";
+
 #[derive(Debug, serde::Deserialize)]
 struct CsvRow {
     key: String,
@@ -20,14 +23,42 @@ struct CsvRow {
     value: String,
 }
 
+impl CsvRow {
+    /// Trim all fields and lowercase `type` and `encoding`, as CSVs from other tools may contain
+    /// e.g. `U8` or `String `. Values of the `string` encoding are kept as they are, leading and
+    /// trailing spaces are part of the stored string.
+    fn normalize(self) -> Self {
+        let encoding = self.encoding.trim().to_ascii_lowercase();
+        let value = match encoding.as_str() {
+            "string" => self.value,
+            _ => self.value.trim().to_string(),
+        };
+
+        Self {
+            key: self.key.trim().to_string(),
+            entry_type: self.entry_type.trim().to_ascii_lowercase(),
+            encoding,
+            value,
+        }
+    }
+}
+
 /// Parse NVS CSV content from a string into an [`NvsPartition`].
+///
+/// Lines starting with `#` (after optional whitespace) are comments and are skipped together with
+/// blank lines, like `nvs_partition_gen.py` does. A leading UTF-8 BOM is ignored and `\r\n` or
+/// `\r` line endings, e.g. from Excel, are treated like `\n` outside of quoted values.
 pub(crate) fn parse_csv(content: &str) -> Result<NvsPartition, Error> {
-    let mut partition = NvsPartition { entries: vec![] };
+    let mut partition = NvsPartition::default();
+    let content = content.strip_prefix(UTF8_BOM).unwrap_or(content);
+    let content = blank_comments(&normalize_line_endings(content));
     let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers()?.clone();
+    let mut record = csv::StringRecord::new();
     let mut current_namespace: Option<String> = None;
 
-    for result in reader.deserialize() {
-        let row: CsvRow = result?;
+    while reader.read_record(&mut record)? {
+        let row = record.deserialize::<CsvRow>(Some(&headers)).map(CsvRow::normalize)?;
 
         if row.entry_type == "namespace" {
             validate_key(&row.key)?;
@@ -36,18 +67,88 @@ pub(crate) fn parse_csv(content: &str) -> Result<NvsPartition, Error> {
                     "namespace entries must have empty encoding and value".to_string(),
                 ));
             }
+            if !partition.empty_namespaces.contains(&row.key) {
+                partition.empty_namespaces.push(row.key.clone());
+            }
             current_namespace = Some(row.key);
             continue;
         }
 
-        let namespace = current_namespace.clone().ok_or(Error::MissingNamespace)?;
+        let Some(namespace) = current_namespace.clone() else {
+            return Err(Error::DataBeforeNamespace {
+                line: line_number(&content, &record),
+                key: row.key,
+            });
+        };
         let entry = parse_row(row, namespace)?;
         partition.entries.push(entry);
     }
 
+    // namespace rows without data rows following them
+    let entries = &partition.entries;
+    partition
+        .empty_namespaces
+        .retain(|namespace| !entries.iter().any(|entry| entry.namespace == *namespace));
+
     Ok(partition)
 }
 
+/// The line a record starts at. The reader neither counts the blank lines before a record, e.g.
+/// former comments, nor does its byte position skip them.
+fn line_number(content: &str, record: &csv::StringRecord) -> u64 {
+    let offset = record.position().map_or(0, |position| position.byte() as usize);
+    let (before, after) = content.split_at(offset);
+    let blank_lines = after.len() - after.trim_start_matches('\n').len();
+    (before.matches('\n').count() + blank_lines + 1) as u64
+}
+
+/// Replaces `\r\n` and `\r` by `\n`. Line breaks within a quoted value are part of the value
+/// and kept as they are.
+fn normalize_line_endings(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\r' if !quoted => {
+                chars.next_if_eq(&'\n');
+                result.push('\n');
+                continue;
+            }
+            _ => {}
+        }
+        result.push(c);
+    }
+
+    result
+}
+
+/// Replaces comment and whitespace-only lines by empty lines, which the csv reader skips. Lines
+/// within a quoted value are kept as is, so a `#` in a multi-line value is still data. The line
+/// count stays the same to keep the positions in error messages intact.
+fn blank_comments(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut quoted = false;
+
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if !quoted && (trimmed.is_empty() || trimmed.starts_with('#')) {
+            if line.ends_with('\n') {
+                result.push('\n');
+            }
+            continue;
+        }
+
+        // escaped quotes ("") toggle twice and keep the state
+        quoted ^= line.matches('"').count() % 2 == 1;
+        result.push_str(line);
+    }
+
+    result
+}
+
 fn parse_row(row: CsvRow, namespace: String) -> Result<NvsEntry, Error> {
     validate_key(&row.key)?;
 
@@ -72,15 +173,57 @@ fn parse_row(row: CsvRow, namespace: String) -> Result<NvsEntry, Error> {
 }
 
 macro_rules! parse_numeric {
+    ($value:expr, $ty:ty, $variant:ident) => {
+        parse_int!($value, $ty).map(DataValue::$variant)
+    };
+}
+
+/// Parse the elements of an array, separated by commas and/or whitespace.
+macro_rules! parse_array {
     ($value:expr, $ty:ty, $variant:ident) => {
         $value
-            .parse::<$ty>()
-            .map(DataValue::$variant)
-            .map_err(|e| Error::InvalidValue(format!("invalid {} value: {}", stringify!($ty), e)))
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|element| !element.is_empty())
+            .map(|element| parse_int!(element, $ty))
+            .collect::<Result<Vec<_>, _>>()
+            .map(|values| DataValue::Array(ArrayValue::$variant(values)))
     };
 }
 
-fn parse_value(value: &str, encoding: &str) -> Result<DataValue, Error> {
+macro_rules! parse_int {
+    ($value:expr, $ty:ty) => {{
+        let (digits, radix) = split_radix($value);
+        <$ty>::from_str_radix(&digits, radix).map_err(|e| match e.kind() {
+            IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => Error::InvalidValue(format!(
+                "value '{}' is out of range for {} ({}..={})",
+                $value,
+                stringify!($ty),
+                <$ty>::MIN,
+                <$ty>::MAX
+            )),
+            _ => Error::InvalidValue(format!("invalid {} value '{}': {}", stringify!($ty), $value, e)),
+        })
+    }};
+}
+
+/// Split a `0x` (hex) or `0b` (binary) prefix off an integer, keeping a leading sign.
+fn split_radix(value: &str) -> (String, u32) {
+    let (sign, unsigned) = match value.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", value),
+    };
+
+    let prefixes = [("0x", 16), ("0X", 16), ("0b", 2), ("0B", 2)];
+    match prefixes
+        .iter()
+        .find_map(|&(prefix, radix)| unsigned.strip_prefix(prefix).map(|digits| (digits, radix)))
+    {
+        Some((digits, radix)) => (format!("{sign}{digits}"), radix),
+        None => (value.to_string(), 10),
+    }
+}
+
+pub(crate) fn parse_value(value: &str, encoding: &str) -> Result<DataValue, Error> {
     match encoding {
         "u8" => parse_numeric!(value, u8, U8),
         "i8" => parse_numeric!(value, i8, I8),
@@ -90,15 +233,29 @@ fn parse_value(value: &str, encoding: &str) -> Result<DataValue, Error> {
         "i32" => parse_numeric!(value, i32, I32),
         "u64" => parse_numeric!(value, u64, U64),
         "i64" => parse_numeric!(value, i64, I64),
+        "i8[]" => parse_array!(value, i8, I8),
+        "u16[]" => parse_array!(value, u16, U16),
+        "i16[]" => parse_array!(value, i16, I16),
+        "u32[]" => parse_array!(value, u32, U32),
+        "i32[]" => parse_array!(value, i32, I32),
+        "u64[]" => parse_array!(value, u64, U64),
+        "i64[]" => parse_array!(value, i64, I64),
+        "u8[]" => Err(Error::InvalidEncoding(
+            "u8[] is not supported, use hex or base64 for byte arrays".to_string(),
+        )),
         "string" => Ok(DataValue::String(value.to_string())),
-        "hex2bin" => {
+        "hex" | "hex2bin" => {
             let bytes = hex::decode(value.trim())?;
             Ok(DataValue::Binary(bytes))
         }
         "base64" => {
-            let bytes = base64::engine::general_purpose::STANDARD.decode(value.trim())?;
+            let bytes = decode_base64(value)?;
             Ok(DataValue::Binary(bytes))
         }
+        "legacy_base64" => {
+            let bytes = decode_base64(value)?;
+            Ok(DataValue::LegacyBinary(bytes))
+        }
         _ => Err(Error::InvalidEncoding(encoding.to_string())),
     }
 }
@@ -8,9 +8,10 @@ use crate::error::Error;
 ///
 /// Entries are written in their original insertion order. A namespace header
 /// row is emitted whenever the namespace changes between consecutive entries.
+/// Empty namespaces are written as namespace rows after all entries.
 ///
-/// `Binary` data values are serialized as base64, matching the ESP-IDF
-/// `nvs_partition_tool` convention.
+/// Small `Binary` data values are serialized as inline hex, larger ones as
+/// base64, matching the ESP-IDF `nvs_partition_tool` convention.
 pub(crate) fn write_csv_content(partition: NvsPartition) -> Result<String, Error> {
     let mut wtr = WriterBuilder::new().has_headers(false).from_writer(Vec::new());
 
@@ -37,6 +38,10 @@ fn write_records<W: std::io::Write>(wtr: &mut csv::Writer<W>, partition: NvsPart
         wtr.serialize(PartitionRow::from(entry))?;
     }
 
+    for namespace in partition.empty_namespaces {
+        wtr.write_record([&namespace, "namespace", "", ""])?;
+    }
+
     wtr.flush()?;
     Ok(())
 }
@@ -0,0 +1,68 @@
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+
+use crate::NvsPartition;
+use crate::error::Error;
+
+/// How [`NvsPartition::dedup`] handles entries with the same namespace and key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Abort with [`Error::DuplicateEntries`] listing all duplicates, leaving the partition
+    /// unchanged.
+    Error,
+    /// Keep the content of the last occurrence at the position of the first one. This is what
+    /// the driver ends up with when writing all entries.
+    LastWins,
+    /// Keep the first occurrence and drop the later ones.
+    FirstWins,
+}
+
+pub(crate) fn dedup(partition: &mut NvsPartition, policy: DuplicatePolicy) -> Result<(), Error> {
+    // position of the first occurrence and index of the winning one per (namespace, key)
+    let mut first: HashMap<(&str, &str), (usize, usize)> = HashMap::new();
+    let mut duplicates = Vec::new();
+
+    for (idx, entry) in partition.entries.iter().enumerate() {
+        let name = (entry.namespace.as_str(), entry.key.as_str());
+        match first.get_mut(&name) {
+            None => {
+                first.insert(name, (idx, idx));
+            }
+            Some((_, winner)) => {
+                if !duplicates.contains(&name) {
+                    duplicates.push(name);
+                }
+                if policy == DuplicatePolicy::LastWins {
+                    *winner = idx;
+                }
+            }
+        }
+    }
+
+    if duplicates.is_empty() {
+        return Ok(());
+    }
+
+    if policy == DuplicatePolicy::Error {
+        return Err(Error::DuplicateEntries(
+            duplicates
+                .iter()
+                .map(|(namespace, key)| format!("{namespace}/{key}"))
+                .collect(),
+        ));
+    }
+
+    let moves: Vec<(usize, usize)> = first.into_values().filter(|(pos, winner)| pos != winner).collect();
+    for (pos, winner) in moves {
+        partition.entries[pos].content = partition.entries[winner].content.clone();
+    }
+
+    let mut seen = HashSet::new();
+    partition
+        .entries
+        .retain(|entry| seen.insert((entry.namespace.clone(), entry.key.clone())));
+
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::NvsPartition;
+use crate::partition::{
+    EntryContent,
+    NvsEntry,
+};
+
+/// Differences between two partitions, see [`NvsPartition::diff`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PartitionDiff {
+    /// Entries only present in the new partition, in its order.
+    pub added: Vec<NvsEntry>,
+    /// Entries only present in the old partition, in its order.
+    pub removed: Vec<NvsEntry>,
+    /// Entries present in both partitions with different content, in the order of the old
+    /// partition.
+    pub changed: Vec<ChangedEntry>,
+}
+
+/// An entry whose content differs between two partitions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedEntry {
+    /// The namespace of the entry.
+    pub namespace: String,
+    /// The key of the entry.
+    pub key: String,
+    /// The content in the old partition.
+    pub old: EntryContent,
+    /// The content in the new partition.
+    pub new: EntryContent,
+}
+
+impl PartitionDiff {
+    /// Returns `true` if both partitions contain the same entries.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compare the entries of both partitions by `(namespace, key)`. If a key occurs multiple times
+/// in a partition the last occurrence wins, like it does when generating the binary.
+pub(crate) fn diff(old: &NvsPartition, new: &NvsPartition) -> PartitionDiff {
+    let old_entries = index(old);
+    let new_entries = index(new);
+
+    let mut result = PartitionDiff::default();
+
+    for entry in unique(old, &old_entries) {
+        match new_entries.get(&(entry.namespace.as_str(), entry.key.as_str())) {
+            None => result.removed.push(entry.clone()),
+            Some(new_entry) if new_entry.content != entry.content => result.changed.push(ChangedEntry {
+                namespace: entry.namespace.clone(),
+                key: entry.key.clone(),
+                old: entry.content.clone(),
+                new: new_entry.content.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    for entry in unique(new, &new_entries) {
+        if !old_entries.contains_key(&(entry.namespace.as_str(), entry.key.as_str())) {
+            result.added.push(entry.clone());
+        }
+    }
+
+    result
+}
+
+type Index<'a> = HashMap<(&'a str, &'a str), &'a NvsEntry>;
+
+fn index(partition: &NvsPartition) -> Index<'_> {
+    partition
+        .entries
+        .iter()
+        .map(|entry| ((entry.namespace.as_str(), entry.key.as_str()), entry))
+        .collect()
+}
+
+/// The entries of the partition without the ones overwritten by a later duplicate.
+fn unique<'a>(partition: &'a NvsPartition, index: &'a Index<'a>) -> impl Iterator<Item = &'a NvsEntry> {
+    partition
+        .entries
+        .iter()
+        .filter(|entry| core::ptr::eq(index[&(entry.namespace.as_str(), entry.key.as_str())], *entry))
+}
+
+struct Content<'a>(&'a EntryContent);
+
+impl fmt::Display for Content<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            EntryContent::Data(value) => write!(f, "{} {}", value.encoding_str(), value),
+            EntryContent::File { encoding, file_path } => {
+                write!(f, "file {} {}", encoding, file_path.display())
+            }
+        }
+    }
+}
+
+/// One line per difference: `+` for added, `-` for removed and `~` for changed entries.
+impl fmt::Display for PartitionDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.removed {
+            writeln!(f, "- {}/{}: {}", entry.namespace, entry.key, Content(&entry.content))?;
+        }
+        for entry in &self.added {
+            writeln!(f, "+ {}/{}: {}", entry.namespace, entry.key, Content(&entry.content))?;
+        }
+        for entry in &self.changed {
+            writeln!(
+                f,
+                "~ {}/{}: {} -> {}",
+                entry.namespace,
+                entry.key,
+                Content(&entry.old),
+                Content(&entry.new)
+            )?;
+        }
+        Ok(())
+    }
+}
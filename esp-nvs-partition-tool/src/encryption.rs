@@ -0,0 +1,125 @@
+//! Encrypted partitions, see [`esp_nvs::encryption`] for the format.
+
+use embedded_storage::nor_flash::{
+    NorFlash,
+    ReadNorFlash,
+};
+/// The XTS keys of an encrypted partition.
+pub use esp_nvs::encryption::NvsKeys as NvsEncryptionKeys;
+use esp_nvs::encryption::{
+    EncryptedPlatform,
+    NVS_KEY_SIZE,
+};
+use esp_nvs::mem_flash::MemFlash;
+use esp_nvs::{
+    ENTRIES_PER_PAGE,
+    FLASH_SECTOR_SIZE,
+    ITEM_SIZE,
+};
+
+use crate::crc::crc32_entry;
+use crate::error::Error;
+use crate::partition::validator::{
+    ENTRY_OFFSET,
+    ENTRY_STATE_EMPTY,
+    ENTRY_STATE_WRITTEN,
+    entry_state,
+};
+
+/// Read encryption keys from the content of a keys file.
+///
+/// Accepts the 64 bytes of both keys, or an `nvs_keys` partition as written by ESP-IDF or
+/// `nvs_partition_gen.py generate-key`, which appends a CRC over the keys.
+pub fn load_keys(data: &[u8]) -> Result<NvsEncryptionKeys, Error> {
+    if data.len() == 2 * NVS_KEY_SIZE {
+        return Ok(NvsEncryptionKeys::new(
+            data[..NVS_KEY_SIZE].try_into().unwrap(),
+            data[NVS_KEY_SIZE..].try_into().unwrap(),
+        ));
+    }
+
+    Ok(NvsEncryptionKeys::from_partition(data)?)
+}
+
+/// Write the `nvs_keys` partition holding `keys`, like `nvs_partition_gen.py generate-key`.
+///
+/// The result is a whole flash sector: both keys and the CRC over them, followed by erased bytes.
+/// Use [`NvsEncryptionKeys::from_partition`] or [`load_keys`] to read it back.
+pub fn generate_keys_partition(keys: &NvsEncryptionKeys) -> Vec<u8> {
+    let mut data = vec![0xFF; FLASH_SECTOR_SIZE];
+    let keys = keys.to_partition();
+    data[..keys.len()].copy_from_slice(&keys);
+    data
+}
+
+/// Encrypt a plaintext partition binary like ESP-IDF does.
+///
+/// Every entry that isn't empty according to the entry state bitmap is encrypted with
+/// XTS-AES, using its offset within the partition as tweak. Page headers, bitmaps and empty
+/// entries are copied as they are.
+pub fn encrypt_partition(data: &[u8], keys: &NvsEncryptionKeys) -> Result<Vec<u8>, Error> {
+    if !data.len().is_multiple_of(FLASH_SECTOR_SIZE) {
+        return Err(Error::InvalidPartitionSize(data.len()));
+    }
+
+    let flash = MemFlash::new(data.len() / FLASH_SECTOR_SIZE);
+    let mut flash = EncryptedPlatform::new(flash, 0, keys);
+    for (page, raw) in data.chunks_exact(FLASH_SECTOR_SIZE).enumerate() {
+        let address = page * FLASH_SECTOR_SIZE;
+
+        // writes to the in-memory flash only fail if they are out of bounds or misaligned
+        flash.write(address as u32, &raw[..ENTRY_OFFSET]).unwrap();
+        for index in (0..ENTRIES_PER_PAGE).filter(|&index| entry_state(raw, index) != ENTRY_STATE_EMPTY) {
+            let offset = ENTRY_OFFSET + index * ITEM_SIZE;
+            flash
+                .write((address + offset) as u32, &raw[offset..offset + ITEM_SIZE])
+                .unwrap();
+        }
+    }
+
+    Ok(flash.into_inner().into_inner())
+}
+
+/// Decrypt a partition binary encrypted with [`encrypt_partition`] or by ESP-IDF.
+///
+/// The page headers and bitmaps aren't encrypted, so wrong keys are detected by the entries:
+/// returns [`Error::WrongEncryptionKeys`] if the partition has written entries but none of them
+/// matches its CRC after decrypting.
+pub fn decrypt_partition(data: &[u8], keys: &NvsEncryptionKeys) -> Result<Vec<u8>, Error> {
+    if !data.len().is_multiple_of(FLASH_SECTOR_SIZE) {
+        return Err(Error::InvalidPartitionSize(data.len()));
+    }
+
+    let mut flash = EncryptedPlatform::new(MemFlash::from_bytes(data.to_vec()), 0, keys);
+    let mut decrypted = data.to_vec();
+    let mut written = 0;
+    let mut valid = 0;
+    for (page, raw) in decrypted.chunks_exact_mut(FLASH_SECTOR_SIZE).enumerate() {
+        for index in 0..ENTRIES_PER_PAGE {
+            let state = entry_state(raw, index);
+            if state == ENTRY_STATE_EMPTY {
+                continue;
+            }
+
+            let offset = ENTRY_OFFSET + index * ITEM_SIZE;
+            let entry: &mut [u8; ITEM_SIZE] = (&mut raw[offset..offset + ITEM_SIZE]).try_into().unwrap();
+            // reads from the in-memory flash only fail if they are out of bounds or misaligned
+            flash.read((page * FLASH_SECTOR_SIZE + offset) as u32, entry).unwrap();
+
+            // data entries of strings and blobs don't have a CRC of their own, they just make
+            // the share of valid entries smaller
+            if state == ENTRY_STATE_WRITTEN {
+                written += 1;
+                if u32::from_le_bytes(entry[4..8].try_into().unwrap()) == crc32_entry(entry) {
+                    valid += 1;
+                }
+            }
+        }
+    }
+
+    if written > 0 && valid == 0 {
+        return Err(Error::WrongEncryptionKeys);
+    }
+
+    Ok(decrypted)
+}
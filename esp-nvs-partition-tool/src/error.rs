@@ -7,6 +7,10 @@ pub enum Error {
     #[error("failed to parse CSV: {0}")]
     CsvError(#[from] csv::Error),
 
+    #[cfg(feature = "serde")]
+    #[error("failed to parse JSON: {0}")]
+    JsonError(#[from] serde_json::Error),
+
     #[error("io error: {0}")]
     IoError(#[from] std::io::Error),
 
@@ -28,6 +32,15 @@ pub enum Error {
     #[error("missing namespace")]
     MissingNamespace,
 
+    #[error("line {line}: entry '{key}' comes before the first namespace row")]
+    DataBeforeNamespace { line: u64, key: String },
+
+    #[error("namespace '{0}' not found")]
+    NamespaceNotFound(String),
+
+    #[error("entry '{namespace}/{key}' not found")]
+    KeyNotFound { namespace: String, key: String },
+
     #[error("invalid key: {0}")]
     InvalidKey(String),
 
@@ -37,9 +50,24 @@ pub enum Error {
     #[error("invalid partition size {0}: must be a multiple of 4096 bytes")]
     InvalidPartitionSize(usize),
 
+    #[error("page {page} has the unknown format version 0x{version:02x}")]
+    UnsupportedPageVersion { page: usize, version: u8 },
+
     #[error("too many namespaces (max 255)")]
     TooManyNamespaces,
 
+    #[error("invalid namespace index: {0}")]
+    InvalidNamespaceIndex(String),
+
+    #[error("entry '{namespace}/{key}' exists in both partitions")]
+    MergeConflict { namespace: String, key: String },
+
+    #[error("duplicate entries: {}", .0.join(", "))]
+    DuplicateEntries(Vec<String>),
+
+    #[error("no entry could be decrypted, the encryption keys don't match the partition")]
+    WrongEncryptionKeys,
+
     #[error("nvs error: {0}")]
     NvsError(#[from] esp_nvs::error::Error),
 }
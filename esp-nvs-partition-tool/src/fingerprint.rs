@@ -0,0 +1,93 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use esp_nvs::ItemType;
+
+use crate::NvsPartition;
+use crate::error::Error;
+use crate::partition::generator::parse_file_content;
+use crate::partition::{
+    DataValue,
+    EntryContent,
+};
+
+/// 64 bit FNV-1a. `DefaultHasher` isn't used as its algorithm may change between Rust versions,
+/// which would make fingerprints of different builds incomparable.
+struct Fnv(u64);
+
+impl Fnv {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    /// Write the length in front of the bytes, so neighboring fields can't be confused.
+    fn write_field(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+}
+
+pub(crate) fn content_fingerprint(partition: &NvsPartition) -> Result<u64, Error> {
+    // later duplicates win, like when generating
+    let mut entries: BTreeMap<(&str, &str), &EntryContent> = BTreeMap::new();
+    for entry in &partition.entries {
+        entries.insert((&entry.namespace, &entry.key), &entry.content);
+    }
+
+    let mut hash = Fnv(Fnv::OFFSET_BASIS);
+    for ((namespace, key), content) in &entries {
+        let value = match content {
+            EntryContent::Data(value) => Cow::Borrowed(value),
+            EntryContent::File { encoding, file_path } => {
+                Cow::Owned(parse_file_content(&std::fs::read(file_path)?, encoding)?)
+            }
+        };
+
+        hash.write_field(namespace.as_bytes());
+        hash.write_field(key.as_bytes());
+        write_value(&mut hash, &value);
+    }
+
+    // namespaces without entries exist on the device as well, unlike the position of entries
+    let mut empty_namespaces: Vec<&str> = partition
+        .empty_namespaces
+        .iter()
+        .map(String::as_str)
+        .filter(|namespace| !entries.keys().any(|(other, _)| other == namespace))
+        .collect();
+    empty_namespaces.sort_unstable();
+    empty_namespaces.dedup();
+
+    hash.write(&[0]);
+    for namespace in empty_namespaces {
+        hash.write_field(namespace.as_bytes());
+    }
+
+    Ok(hash.0)
+}
+
+/// Write the type and the bytes of a value. All blobs are written as the same type, regardless
+/// of their format, as they read back the same on the device.
+fn write_value(hash: &mut Fnv, value: &DataValue) {
+    let (type_, bytes) = match value {
+        DataValue::U8(v) => (ItemType::U8, v.to_le_bytes().to_vec()),
+        DataValue::I8(v) => (ItemType::I8, v.to_le_bytes().to_vec()),
+        DataValue::U16(v) => (ItemType::U16, v.to_le_bytes().to_vec()),
+        DataValue::I16(v) => (ItemType::I16, v.to_le_bytes().to_vec()),
+        DataValue::U32(v) => (ItemType::U32, v.to_le_bytes().to_vec()),
+        DataValue::I32(v) => (ItemType::I32, v.to_le_bytes().to_vec()),
+        DataValue::U64(v) => (ItemType::U64, v.to_le_bytes().to_vec()),
+        DataValue::I64(v) => (ItemType::I64, v.to_le_bytes().to_vec()),
+        DataValue::String(v) => (ItemType::Sized, v.as_bytes().to_vec()),
+        DataValue::Binary(v) | DataValue::LegacyBinary(v) => (ItemType::BlobIndex, v.clone()),
+        DataValue::Array(v) => (ItemType::BlobIndex, v.to_blob()),
+    };
+
+    hash.write(&[type_ as u8]);
+    hash.write_field(&bytes);
+}
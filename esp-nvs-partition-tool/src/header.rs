@@ -0,0 +1,53 @@
+use std::fmt::Write;
+
+const BYTES_PER_LINE: usize = 12;
+
+/// Format a partition image as C header with a `static const uint8_t <symbol>[]` array and a
+/// `<SYMBOL>_SIZE` define, e.g. to embed it in firmware instead of flashing a separate partition.
+///
+/// `symbol` must be a valid C identifier.
+pub fn emit_c_header(data: &[u8], symbol: &str) -> String {
+    let size_define = format!("{}_SIZE", symbol.to_uppercase());
+    let guard = format!("{}_H", symbol.to_uppercase());
+
+    let mut out = String::new();
+    writeln!(out, "#ifndef {guard}").unwrap();
+    writeln!(out, "#define {guard}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "#include <stdint.h>").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "#define {size_define} {}", data.len()).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "static const uint8_t {symbol}[{size_define}] = {{").unwrap();
+    write_bytes(&mut out, data);
+    writeln!(out, "}};").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "#endif /* {guard} */").unwrap();
+    out
+}
+
+/// Format a partition image as Rust source with a `pub static <SYMBOL>: [u8; N]` array and a
+/// `<SYMBOL>_SIZE` constant, meant to be pulled in with `include!`.
+///
+/// The symbol is converted to upper case and must be a valid Rust identifier.
+pub fn emit_rust_static(data: &[u8], symbol: &str) -> String {
+    let symbol = symbol.to_uppercase();
+
+    let mut out = String::new();
+    writeln!(out, "pub const {symbol}_SIZE: usize = {};", data.len()).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "pub static {symbol}: [u8; {symbol}_SIZE] = [").unwrap();
+    write_bytes(&mut out, data);
+    writeln!(out, "];").unwrap();
+    out
+}
+
+fn write_bytes(out: &mut String, data: &[u8]) {
+    for line in data.chunks(BYTES_PER_LINE) {
+        out.push_str("   ");
+        for byte in line {
+            write!(out, " 0x{byte:02x},").unwrap();
+        }
+        out.push('\n');
+    }
+}
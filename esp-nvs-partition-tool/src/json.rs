@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+
+use base64::Engine;
+use serde_json::Value;
+
+use crate::NvsPartition;
+use crate::csv::parser::parse_value;
+use crate::error::Error;
+use crate::partition::{
+    DataValue,
+    EntryContent,
+    FileEncoding,
+    NvsEntry,
+    for_each_array,
+    validate_key,
+};
+
+/// A single entry in the JSON representation.
+///
+/// `type` uses the CSV encoding names, binary values are always written as `base64` and arrays
+/// as JSON arrays of numbers. File
+/// entries use the type `file` and store the file encoding in the additional `encoding` field.
+/// The namespace index of entries parsed from a binary is kept in `namespace_index`.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct JsonEntry {
+    namespace: String,
+    key: String,
+    #[serde(rename = "type")]
+    entry_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encoding: Option<String>,
+    value: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    namespace_index: Option<u8>,
+}
+
+impl From<&NvsEntry> for JsonEntry {
+    fn from(entry: &NvsEntry) -> Self {
+        let (entry_type, encoding, value) = match &entry.content {
+            EntryContent::Data(DataValue::U8(v)) => ("u8", None, Value::from(*v)),
+            EntryContent::Data(DataValue::I8(v)) => ("i8", None, Value::from(*v)),
+            EntryContent::Data(DataValue::U16(v)) => ("u16", None, Value::from(*v)),
+            EntryContent::Data(DataValue::I16(v)) => ("i16", None, Value::from(*v)),
+            EntryContent::Data(DataValue::U32(v)) => ("u32", None, Value::from(*v)),
+            EntryContent::Data(DataValue::I32(v)) => ("i32", None, Value::from(*v)),
+            EntryContent::Data(DataValue::U64(v)) => ("u64", None, Value::from(*v)),
+            EntryContent::Data(DataValue::I64(v)) => ("i64", None, Value::from(*v)),
+            EntryContent::Data(DataValue::String(v)) => ("string", None, Value::from(v.as_str())),
+            EntryContent::Data(DataValue::Binary(v)) => (
+                "base64",
+                None,
+                Value::from(base64::engine::general_purpose::STANDARD.encode(v)),
+            ),
+            EntryContent::Data(DataValue::LegacyBinary(v)) => (
+                "legacy_base64",
+                None,
+                Value::from(base64::engine::general_purpose::STANDARD.encode(v)),
+            ),
+            EntryContent::Data(DataValue::Array(v)) => (
+                v.encoding_str(),
+                None,
+                Value::from(for_each_array!(v, values => values.iter().map(|&v| Value::from(v)).collect::<Vec<_>>())),
+            ),
+            EntryContent::File { encoding, file_path } => (
+                "file",
+                Some(encoding.as_str().to_string()),
+                Value::from(file_path.to_string_lossy()),
+            ),
+        };
+
+        Self {
+            namespace: entry.namespace.clone(),
+            key: entry.key.clone(),
+            entry_type: entry_type.to_string(),
+            encoding,
+            value,
+            namespace_index: entry.namespace_index,
+        }
+    }
+}
+
+impl TryFrom<JsonEntry> for NvsEntry {
+    type Error = Error;
+
+    fn try_from(entry: JsonEntry) -> Result<Self, Error> {
+        validate_key(&entry.namespace)?;
+        validate_key(&entry.key)?;
+
+        // numbers are accepted as JSON numbers or strings and parsed like CSV values
+        let value = match entry.value {
+            Value::String(value) => value,
+            Value::Number(value) => value.to_string(),
+            Value::Array(elements) if entry.entry_type.ends_with("[]") => {
+                let elements = elements
+                    .into_iter()
+                    .map(|element| match element {
+                        Value::String(value) => Ok(value),
+                        Value::Number(value) => Ok(value.to_string()),
+                        element => Err(Error::InvalidValue(format!(
+                            "unsupported array element {element} for key '{}'",
+                            entry.key
+                        ))),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                elements.join(" ")
+            }
+            value => {
+                return Err(Error::InvalidValue(format!(
+                    "unsupported value {value} for key '{}'",
+                    entry.key
+                )));
+            }
+        };
+
+        if entry.entry_type == "file" {
+            let encoding: FileEncoding = entry
+                .encoding
+                .ok_or_else(|| Error::InvalidEncoding("file entries must have an encoding".to_string()))?
+                .parse()?;
+            let mut file = NvsEntry::new_file(entry.namespace, entry.key, encoding, PathBuf::from(value));
+            file.namespace_index = entry.namespace_index;
+            return Ok(file);
+        }
+
+        let value = parse_value(&value, &entry.entry_type)?;
+        let mut data = NvsEntry::new_data(entry.namespace, entry.key, value);
+        data.namespace_index = entry.namespace_index;
+        Ok(data)
+    }
+}
+
+/// Serialize an NVS partition to a JSON array of entries, in insertion order.
+pub(crate) fn write_json_content(partition: &NvsPartition) -> Result<String, Error> {
+    let entries: Vec<JsonEntry> = partition.entries.iter().map(JsonEntry::from).collect();
+    Ok(serde_json::to_string_pretty(&entries)?)
+}
+
+/// Parse the JSON written by [`write_json_content`].
+pub(crate) fn parse_json(content: &str) -> Result<NvsPartition, Error> {
+    let entries: Vec<JsonEntry> = serde_json::from_str(content)?;
+    let entries = entries.into_iter().map(NvsEntry::try_from).collect::<Result<_, _>>()?;
+    Ok(NvsPartition {
+        entries,
+        empty_namespaces: vec![],
+    })
+}
@@ -1,18 +1,65 @@
 //! ESP-IDF compatible NVS (Non-Volatile Storage) partition table parser and
 //! generator.
 
+use std::collections::{
+    BTreeMap,
+    HashSet,
+};
+
+pub mod crc;
+pub mod encryption;
 pub mod error;
 pub mod partition;
 
+mod builder;
 mod csv;
+mod dedup;
+mod diff;
+mod fingerprint;
+mod header;
+#[cfg(feature = "serde")]
+mod json;
+mod manifest;
+mod merge;
+mod plan;
 
+pub use builder::NvsPartitionBuilder;
+pub use dedup::DuplicatePolicy;
+pub use diff::{
+    ChangedEntry,
+    PartitionDiff,
+};
+pub use encryption::NvsEncryptionKeys;
 pub use error::Error;
+pub use header::{
+    emit_c_header,
+    emit_rust_static,
+};
+pub use manifest::{
+    Manifest,
+    NamespaceManifest,
+};
+pub use merge::MergePolicy;
+pub use partition::generator::{
+    GenerateOptions,
+    TrailingPage,
+};
 pub use partition::{
+    ArrayValue,
     DataValue,
     EntryContent,
     FileEncoding,
     MAX_KEY_LENGTH,
     NvsEntry,
+    ParseWarning,
+    Problem,
+    ProblemKind,
+    ValidationReport,
+};
+pub use plan::{
+    PackingPlan,
+    PageLayout,
+    PlacedEntry,
 };
 
 /// A collection of NVS key-value entries, optionally spanning multiple
@@ -20,13 +67,22 @@ pub use partition::{
 ///
 /// This is the primary in-memory representation used by the CSV and binary
 /// parsers/generators.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct NvsPartition {
     /// The ordered list of entries in this partition.
     pub entries: Vec<NvsEntry>,
+    /// Namespaces declared without any entry, in declaration order. They are written after the
+    /// entries, so they exist on the device before their first value is stored.
+    pub empty_namespaces: Vec<String>,
 }
 
 impl NvsPartition {
+    /// Start building a partition, e.g.
+    /// `NvsPartition::builder().namespace("storage").u8("flag", 1).build()`.
+    pub fn builder() -> NvsPartitionBuilder {
+        NvsPartitionBuilder::default()
+    }
+
     /// Attempt to parse either a binary or CSV NVS partition from the given
     /// input.
     ///
@@ -43,8 +99,8 @@ impl NvsPartition {
         // Valid page states (Active = 0xFE, Full = 0xFC, Freeing = 0xF8, etc.)
         // all have their first byte well above 0x80, while CSV text is always
         // valid ASCII (< 0x80). We use 0x80 as the threshold to reliably
-        // distinguish the two formats.
-        if input.first().is_some_and(|&b| b >= 0x80) {
+        // distinguish the two formats. The only exception is a CSV with a UTF-8 BOM.
+        if input.first().is_some_and(|&b| b >= 0x80) && !input.starts_with(csv::parser::UTF8_BOM.as_bytes()) {
             Self::try_from_bytes(input)
         } else {
             Self::try_from_str(
@@ -68,24 +124,293 @@ impl NvsPartition {
     where
         B: Into<Vec<u8>>,
     {
-        partition::parser::parse_binary_data(&bytes.into())
+        partition::parser::parse_binary_data(&bytes.into(), None)
+    }
+
+    /// Parse an encrypted binary NVS partition like [`NvsPartition::try_from_bytes`], see
+    /// [`encryption::decrypt_partition`].
+    pub fn parse_encrypted(data: &[u8], keys: &NvsEncryptionKeys) -> Result<Self, Error> {
+        Self::try_from_bytes(encryption::decrypt_partition(data, keys)?)
+    }
+
+    /// Parse a binary NVS partition like [`NvsPartition::try_from_bytes`], but skip whatever
+    /// can't be read instead of failing, e.g. to recover the intact entries of a damaged dump.
+    ///
+    /// Every skipped page or entry is described by a warning. Only an invalid partition size is
+    /// returned as error.
+    pub fn parse_partition_lenient(data: &[u8]) -> Result<(Self, Vec<ParseWarning>), Error> {
+        partition::parser::parse_binary_data_lenient(data)
+    }
+
+    /// Parse a binary NVS partition like [`NvsPartition::try_from_bytes`], but only keep the
+    /// entries of `namespace`. The values of other namespaces aren't read, e.g. their blobs aren't
+    /// assembled, while all pages are still loaded and checked.
+    ///
+    /// Fails if the partition doesn't define `namespace`.
+    pub fn parse_partition_filtered(data: &[u8], namespace: &str) -> Result<Self, Error> {
+        partition::validate_key(namespace)?;
+        partition::parser::parse_binary_data(data, Some(namespace))
+    }
+
+    /// Calculate the smallest partition size, a multiple of 4096, that fits all entries including
+    /// the page ESP-IDF keeps free for compaction. If all entries fit into a single page, a single
+    /// page image without a free page is enough, see [`NvsPartition::generate_partition`].
+    ///
+    /// File entries are read to determine their size, so this fails if they can't be read or
+    /// would fail to generate for other reasons.
+    pub fn min_size(&self) -> Result<usize, Error> {
+        partition::generator::min_partition_size(self)
+    }
+
+    /// Check the structure of a binary partition without converting it.
+    ///
+    /// Unlike [`NvsPartition::try_from_bytes`], which goes through the driver and silently drops
+    /// inconsistent entries, this collects all problems with their page and entry index. Only an
+    /// invalid partition size is returned as error.
+    pub fn validate(data: &[u8]) -> Result<ValidationReport, Error> {
+        partition::validator::validate(data)
+    }
+
+    /// Describe the raw content of a binary partition, one line per page header and entry: the
+    /// page state, sequence, version and header CRC, the entry states from the bitmap and for
+    /// every entry its namespace index, type, span, key and CRC.
+    ///
+    /// Meant for debugging a partition that doesn't parse, so nothing is rejected.
+    pub fn annotate(data: &[u8]) -> String {
+        partition::dump::annotate(data)
     }
 
     /// Serialize this partition to CSV and return the content as a `String`.
     ///
     /// Entries are written in their original insertion order. A namespace
     /// header row is emitted whenever the namespace changes between
-    /// consecutive entries. `Encoding::Binary` values are serialized as
-    /// base64, matching the ESP-IDF `nvs_partition_tool` convention.
+    /// consecutive entries. Small `DataValue::Binary` values are serialized
+    /// as inline hex, larger ones as base64, matching the ESP-IDF
+    /// `nvs_partition_tool` convention.
     pub fn to_csv(self) -> Result<String, Error> {
         csv::writer::write_csv_content(self)
     }
 
+    /// Serialize this partition to a JSON array of `{namespace, key, type, value}` objects.
+    ///
+    /// Entries keep their insertion order. `type` uses the CSV encoding names and binary values
+    /// are base64 encoded. File entries have the type `file` and an additional `encoding` field.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, Error> {
+        json::write_json_content(self)
+    }
+
+    /// Parse a partition from the JSON written by [`NvsPartition::to_json`].
+    #[cfg(feature = "serde")]
+    pub fn from_json(content: &str) -> Result<Self, Error> {
+        json::parse_json(content)
+    }
+
     /// Generate an NVS partition binary in memory.
     ///
     /// `size` must be a multiple of 4096 (the ESP-IDF flash sector size).
     pub fn generate_partition(&self, size: usize) -> Result<Vec<u8>, Error> {
-        partition::generator::generate_partition_data(self, size)
+        self.generate_partition_with_options(size, &GenerateOptions::default())
+    }
+
+    /// Same as [`NvsPartition::generate_partition`], with control over details of the layout.
+    pub fn generate_partition_with_options(&self, size: usize, options: &GenerateOptions) -> Result<Vec<u8>, Error> {
+        partition::generator::generate_partition_data(self, size, options)
+    }
+
+    /// Generate the partition like [`NvsPartition::generate_partition`] and summarize the result,
+    /// e.g. to record which namespace got which index.
+    pub fn manifest(&self, size: usize) -> Result<Manifest, Error> {
+        self.manifest_with_options(size, &GenerateOptions::default())
+    }
+
+    /// Same as [`NvsPartition::manifest`], for a partition generated with
+    /// [`NvsPartition::generate_partition_with_options`].
+    pub fn manifest_with_options(&self, size: usize, options: &GenerateOptions) -> Result<Manifest, Error> {
+        Ok(manifest::manifest(
+            &self.generate_partition_with_options(size, options)?,
+        ))
+    }
+
+    /// Generate the partition like [`NvsPartition::generate_partition`] and record which entries
+    /// landed on which page instead of returning the bytes.
+    ///
+    /// The entries are placed by the driver, so the plan matches the generated binary exactly,
+    /// e.g. strings and blob chunks that didn't fit onto the rest of a page start a new one.
+    pub fn plan(&self, size: usize) -> Result<PackingPlan, Error> {
+        self.plan_with_options(size, &GenerateOptions::default())
+    }
+
+    /// Same as [`NvsPartition::plan`], for a partition generated with
+    /// [`NvsPartition::generate_partition_with_options`].
+    pub fn plan_with_options(&self, size: usize, options: &GenerateOptions) -> Result<PackingPlan, Error> {
+        Ok(plan::plan(&self.generate_partition_with_options(size, options)?))
+    }
+
+    /// Same as [`NvsPartition::generate_partition`], with all entries encrypted with `keys` for a
+    /// device using encrypted NVS, see [`encryption::encrypt_partition`].
+    pub fn generate_encrypted(&self, size: usize, keys: &NvsEncryptionKeys) -> Result<Vec<u8>, Error> {
+        encryption::encrypt_partition(&self.generate_partition(size)?, keys)
+    }
+
+    /// Generate an NVS partition binary and write it to `writer`, e.g. a file or stdout.
+    ///
+    /// The binary is still built in memory, as the driver may update earlier pages while adding
+    /// entries, e.g. to erase a replaced value. Nothing is written if the generation fails.
+    pub fn generate_to<W: std::io::Write>(&self, writer: &mut W, size: usize) -> Result<(), Error> {
+        writer.write_all(&self.generate_partition(size)?)?;
+        Ok(writer.flush()?)
+    }
+
+    /// Compare this partition against `other`, e.g. the image a device shipped with against the
+    /// one read back later. Entries are matched by namespace and key.
+    pub fn diff(&self, other: &NvsPartition) -> PartitionDiff {
+        diff::diff(self, other)
+    }
+
+    /// Hash the logical content of this partition: the values by namespace and key, independent
+    /// of the order of the entries and the layout of a generated binary.
+    ///
+    /// So a partition parsed from a binary has the same fingerprint as the CSV it was generated
+    /// from, as long as the values are the same. Entries defined multiple times count with their
+    /// last value, namespace indices are ignored and all blob formats, including arrays, hash as
+    /// their bytes. File entries are hashed with the content of their file, which fails if it
+    /// can't be read. The hash doesn't depend on the Rust version or platform.
+    pub fn content_fingerprint(&self) -> Result<u64, Error> {
+        fingerprint::content_fingerprint(self)
+    }
+
+    /// Merge the entries of `other` into this partition, e.g. per-device overrides into a shared
+    /// base. Entries with a new namespace and key are appended in their original order,
+    /// collisions are resolved according to `policy`.
+    pub fn merge(&mut self, other: NvsPartition, policy: MergePolicy) -> Result<(), Error> {
+        merge::merge(self, other, policy)
+    }
+
+    /// Look up the entry with the given namespace and key.
+    ///
+    /// If the key occurs multiple times the last occurrence is returned, as it's the one that ends
+    /// up in the generated binary.
+    pub fn get(&self, namespace: &str, key: &str) -> Option<&NvsEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| e.namespace == namespace && e.key == key)
+    }
+
+    /// Like [`NvsPartition::get`], but reports whether the namespace or only the key is missing.
+    ///
+    /// A namespace counts as present if it has entries or is one of the
+    /// [empty namespaces](NvsPartition::empty_namespaces).
+    pub fn lookup(&self, namespace: &str, key: &str) -> Result<&NvsEntry, Error> {
+        if let Some(entry) = self.get(namespace, key) {
+            return Ok(entry);
+        }
+
+        if !self.namespaces().any(|name| name == namespace) {
+            return Err(Error::NamespaceNotFound(namespace.to_string()));
+        }
+        Err(Error::KeyNotFound {
+            namespace: namespace.to_string(),
+            key: key.to_string(),
+        })
+    }
+
+    /// Look up the entry with the given namespace and key, returning a mutable reference.
+    pub fn get_mut(&mut self, namespace: &str, key: &str) -> Option<&mut NvsEntry> {
+        self.entries
+            .iter_mut()
+            .rev()
+            .find(|e| e.namespace == namespace && e.key == key)
+    }
+
+    /// Set the value of the entry with the given namespace and key and return the previous content.
+    ///
+    /// An existing entry is overwritten in place. A new entry is inserted after the last entry of
+    /// its namespace, or appended if the namespace is new, so entries stay grouped by namespace
+    /// in the CSV output.
+    pub fn set(&mut self, namespace: &str, key: &str, value: DataValue) -> Option<EntryContent> {
+        if let Some(entry) = self.get_mut(namespace, key) {
+            return Some(std::mem::replace(&mut entry.content, EntryContent::Data(value)));
+        }
+
+        let entry = NvsEntry::new_data(namespace.to_string(), key.to_string(), value);
+        match self.entries.iter().rposition(|e| e.namespace == namespace) {
+            Some(idx) => self.entries.insert(idx + 1, entry),
+            None => self.entries.push(entry),
+        }
+        None
+    }
+
+    /// Remove the entry with the given namespace and key.
+    ///
+    /// Duplicates are removed as well, the returned entry is the last occurrence, see
+    /// [`NvsPartition::get`].
+    pub fn remove(&mut self, namespace: &str, key: &str) -> Option<NvsEntry> {
+        let idx = self
+            .entries
+            .iter()
+            .rposition(|e| e.namespace == namespace && e.key == key)?;
+        let entry = self.entries.remove(idx);
+        self.entries.retain(|e| e.namespace != namespace || e.key != key);
+        Some(entry)
+    }
+
+    /// Returns the names of all namespaces, in the order they first appear in, followed by the
+    /// [empty namespaces](NvsPartition::empty_namespaces).
+    pub fn namespaces(&self) -> impl Iterator<Item = &str> {
+        let mut seen = HashSet::new();
+        self.entries
+            .iter()
+            .map(|e| e.namespace.as_str())
+            .chain(self.empty_namespaces.iter().map(String::as_str))
+            .filter(move |namespace| seen.insert(*namespace))
+    }
+
+    /// Group the entries by namespace, each group in the order of [`NvsPartition::entries`].
+    /// Empty namespaces map to an empty group.
+    pub fn by_namespace(&self) -> BTreeMap<&str, Vec<&NvsEntry>> {
+        let mut groups: BTreeMap<&str, Vec<&NvsEntry>> = self
+            .empty_namespaces
+            .iter()
+            .map(|namespace| (namespace.as_str(), Vec::new()))
+            .collect();
+        for entry in &self.entries {
+            groups.entry(&entry.namespace).or_default().push(entry);
+        }
+        groups
+    }
+
+    /// Copy a single namespace into a partition of its own, e.g. to split up an image.
+    ///
+    /// The result stands on its own: written as CSV it starts with the namespace row and it
+    /// generates like any other partition. An empty namespace is kept as an
+    /// [empty namespace](NvsPartition::empty_namespaces), an unknown one gives an empty partition.
+    pub fn extract_namespace(&self, name: &str) -> NvsPartition {
+        let entries: Vec<NvsEntry> = self.entries.iter().filter(|e| e.namespace == name).cloned().collect();
+        let empty_namespaces = match entries.is_empty() && self.empty_namespaces.iter().any(|ns| ns == name) {
+            true => vec![name.to_string()],
+            false => vec![],
+        };
+
+        NvsPartition {
+            entries,
+            empty_namespaces,
+        }
+    }
+
+    /// Drop the namespace indices recorded when parsing a binary, see
+    /// [`NvsEntry::namespace_index`], so the generator assigns them from scratch.
+    pub fn clear_namespace_indices(&mut self) {
+        for entry in &mut self.entries {
+            entry.namespace_index = None;
+        }
+    }
+
+    /// Resolve entries with the same namespace and key according to `policy`, so every key
+    /// occurs only once.
+    pub fn dedup(&mut self, policy: DuplicatePolicy) -> Result<(), Error> {
+        dedup::dedup(self, policy)
     }
 
     /// Find an entry with the given name in the NVS partition.
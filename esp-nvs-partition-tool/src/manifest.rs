@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+
+use esp_nvs::{
+    ENTRIES_PER_PAGE,
+    FLASH_SECTOR_SIZE,
+    ITEM_SIZE,
+    ItemType,
+    PageState,
+};
+
+#[cfg(feature = "serde")]
+use crate::error::Error;
+use crate::partition::validator::{
+    ENTRY_OFFSET,
+    ENTRY_STATE_EMPTY,
+    ENTRY_STATE_WRITTEN,
+    entry_state,
+};
+
+/// A summary of a generated partition, see [`crate::NvsPartition::manifest`].
+///
+/// Meant to be compared between builds, so it also covers changes that don't show up in the
+/// entries, e.g. a namespace getting a different index.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize)]
+pub struct Manifest {
+    /// The size of the partition in bytes.
+    pub size: usize,
+    /// The bytes of all written entries, including the namespace entries and the entries holding
+    /// the data of strings and blobs.
+    pub used_bytes: usize,
+    /// The number of pages holding entries.
+    pub pages_used: usize,
+    /// The pages holding entries that still have empty entries, usually only the last one.
+    pub partial_pages: Vec<usize>,
+    /// The namespaces ordered by their index.
+    pub namespaces: Vec<NamespaceManifest>,
+}
+
+/// A namespace of a [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct NamespaceManifest {
+    /// The name of the namespace.
+    pub name: String,
+    /// The index the entries of the namespace refer to.
+    pub index: u8,
+    /// The number of keys, a blob counts once regardless of its chunks.
+    pub entries: usize,
+}
+
+impl Manifest {
+    /// Returns `true` if any used page still has empty entries.
+    pub fn has_partial_pages(&self) -> bool {
+        !self.partial_pages.is_empty()
+    }
+
+    /// Serialize the manifest to pretty printed JSON.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Summarize a partition binary as written by the generator, so only written entries of
+/// `ACTIVE` and `FULL` pages are taken into account.
+pub(crate) fn manifest(data: &[u8]) -> Manifest {
+    let mut result = Manifest {
+        size: data.len(),
+        ..Default::default()
+    };
+    let mut names = BTreeMap::new();
+    let mut entries: BTreeMap<u8, usize> = BTreeMap::new();
+
+    for (page_index, page) in data.chunks_exact(FLASH_SECTOR_SIZE).enumerate() {
+        let state = u32::from_le_bytes(page[0..4].try_into().unwrap());
+        if state != PageState::Active as u32 && state != PageState::Full as u32 {
+            continue;
+        }
+        result.pages_used += 1;
+
+        let mut index = 0;
+        while index < ENTRIES_PER_PAGE {
+            match entry_state(page, index) {
+                ENTRY_STATE_WRITTEN => {}
+                ENTRY_STATE_EMPTY => {
+                    if !result.partial_pages.contains(&page_index) {
+                        result.partial_pages.push(page_index);
+                    }
+                    index += 1;
+                    continue;
+                }
+                _ => {
+                    index += 1;
+                    continue;
+                }
+            }
+
+            let entry = &page[ENTRY_OFFSET + index * ITEM_SIZE..ENTRY_OFFSET + (index + 1) * ITEM_SIZE];
+            let span = (entry[2] as usize).clamp(1, ENTRIES_PER_PAGE - index);
+            result.used_bytes += span * ITEM_SIZE;
+
+            let key = &entry[8..24];
+            let key = &key[..key.iter().position(|&b| b == 0).unwrap_or(key.len())];
+            match entry[0] {
+                0 => {
+                    names.insert(entry[24], String::from_utf8_lossy(key).into_owned());
+                }
+                // the chunks of a blob are counted with its index
+                _ if entry[1] == ItemType::BlobData as u8 => {}
+                namespace => *entries.entry(namespace).or_default() += 1,
+            }
+
+            index += span;
+        }
+    }
+
+    result.namespaces = names
+        .into_iter()
+        .map(|(index, name)| NamespaceManifest {
+            name,
+            index,
+            entries: entries.get(&index).copied().unwrap_or_default(),
+        })
+        .collect();
+    result
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use crate::NvsPartition;
+use crate::error::Error;
+
+/// How [`NvsPartition::merge`] resolves entries with the same namespace and key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Keep the content of the existing entry and drop the other one.
+    KeepExisting,
+    /// Replace the content of the existing entry, the entry keeps its position.
+    Overwrite,
+    /// Abort with [`Error::MergeConflict`], leaving the partition unchanged.
+    Error,
+}
+
+pub(crate) fn merge(partition: &mut NvsPartition, other: NvsPartition, policy: MergePolicy) -> Result<(), Error> {
+    let existing: HashMap<(String, String), usize> = partition
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(idx, entry)| ((entry.namespace.clone(), entry.key.clone()), idx))
+        .collect();
+
+    if policy == MergePolicy::Error {
+        if let Some(entry) = other
+            .entries
+            .iter()
+            .find(|entry| existing.contains_key(&(entry.namespace.clone(), entry.key.clone())))
+        {
+            return Err(Error::MergeConflict {
+                namespace: entry.namespace.clone(),
+                key: entry.key.clone(),
+            });
+        }
+    }
+
+    for entry in other.entries {
+        match existing.get(&(entry.namespace.clone(), entry.key.clone())) {
+            None => partition.entries.push(entry),
+            Some(&idx) => {
+                if policy == MergePolicy::Overwrite {
+                    partition.entries[idx].content = entry.content;
+                }
+            }
+        }
+    }
+
+    for namespace in other.empty_namespaces {
+        if !partition.empty_namespaces.contains(&namespace) {
+            partition.empty_namespaces.push(namespace);
+        }
+    }
+    let entries = &partition.entries;
+    partition
+        .empty_namespaces
+        .retain(|namespace| !entries.iter().any(|entry| entry.namespace == *namespace));
+
+    Ok(())
+}
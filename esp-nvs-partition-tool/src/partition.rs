@@ -1,9 +1,17 @@
+pub(crate) mod dump;
 pub(crate) mod generator;
 pub(crate) mod parser;
+pub(crate) mod validator;
 
 use std::path::PathBuf;
 
 pub use esp_nvs::MAX_KEY_LENGTH;
+pub use parser::ParseWarning;
+pub use validator::{
+    Problem,
+    ProblemKind,
+    ValidationReport,
+};
 
 use crate::error::Error;
 
@@ -16,6 +24,17 @@ pub struct NvsEntry {
     pub key: String,
     /// The payload — either inline data or a reference to an external file.
     pub content: EntryContent,
+    /// The chunk start of a [`DataValue::Binary`] blob, 0 or 128.
+    ///
+    /// The driver alternates between both when a blob is overwritten. It is recorded when parsing
+    /// a binary so the generator can reproduce it, other sources always use 0.
+    pub chunk_start: u8,
+    /// The index the namespace is stored with.
+    ///
+    /// Recorded when parsing a binary, the generator uses it unless
+    /// [`GenerateOptions::namespace_indices`](crate::GenerateOptions::namespace_indices) fixes
+    /// another one. Without an index, the namespace gets the next free one.
+    pub namespace_index: Option<u8>,
 }
 
 /// The content of an NVS entry — either inline data or a file reference.
@@ -81,6 +100,16 @@ impl std::fmt::Display for FileEncoding {
     }
 }
 
+/// Page format version byte written by ESP-IDF before v4.0. These pages store blobs only in the
+/// legacy single-page format.
+pub(crate) const PAGE_VERSION_1: u8 = 0xFF;
+
+/// Page format version byte written by ESP-IDF v4.0 and later, which introduced multi-page blobs.
+pub(crate) const PAGE_VERSION_2: u8 = 0xFE;
+
+/// `Binary` values up to this size are written to CSV as inline `hex`, larger ones as `base64`.
+const MAX_INLINE_HEX_SIZE: usize = 32;
+
 /// A concrete data value stored in an NVS entry.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DataValue {
@@ -104,14 +133,25 @@ pub enum DataValue {
     String(String),
     /// Opaque byte blob.
     Binary(Vec<u8>),
+    /// Opaque byte blob in the legacy single-page format (type `0x41`) understood by firmware
+    /// built with ESP-IDF before v4.0, at most 4000 bytes.
+    ///
+    /// Parsing a binary keeps legacy blobs in this format, so they are written back unchanged.
+    LegacyBinary(Vec<u8>),
+    /// Typed array, stored as blob in the layout of [`esp_nvs::ArrayElement`].
+    ///
+    /// The blob isn't marked as array, so parsing a binary returns it as [`DataValue::Binary`],
+    /// see [`ArrayValue::from_blob`].
+    Array(ArrayValue),
 }
 
 impl DataValue {
     /// Return the CSV encoding column string for this value.
     ///
-    /// `Binary` maps to `"base64"` because blobs parsed from a binary
-    /// partition have no original CSV encoding, and the ESP-IDF convention
-    /// is base64.
+    /// Blobs parsed from a binary partition have no original CSV encoding.
+    /// Small `Binary` values (up to 32 bytes, e.g. a MAC address) map to
+    /// `"hex"` to keep them readable, larger ones to `"base64"` which is the
+    /// ESP-IDF convention.
     pub fn encoding_str(&self) -> &'static str {
         match self {
             Self::U8(_) => "u8",
@@ -123,11 +163,132 @@ impl DataValue {
             Self::U64(_) => "u64",
             Self::I64(_) => "i64",
             Self::String(_) => "string",
+            Self::Binary(b) if b.len() <= MAX_INLINE_HEX_SIZE => "hex",
             Self::Binary(_) => "base64",
+            Self::LegacyBinary(_) => "legacy_base64",
+            Self::Array(array) => array.encoding_str(),
         }
     }
 }
 
+/// The elements of a [`DataValue::Array`].
+///
+/// The element types match the driver's [`esp_nvs::ArrayElement`], so there are no `u8` arrays,
+/// those are plain blobs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArrayValue {
+    I8(Vec<i8>),
+    U16(Vec<u16>),
+    I16(Vec<i16>),
+    U32(Vec<u32>),
+    I32(Vec<i32>),
+    U64(Vec<u64>),
+    I64(Vec<i64>),
+}
+
+macro_rules! for_each_array {
+    ($array:expr, $values:ident => $body:expr) => {
+        match $array {
+            $crate::partition::ArrayValue::I8($values) => $body,
+            $crate::partition::ArrayValue::U16($values) => $body,
+            $crate::partition::ArrayValue::I16($values) => $body,
+            $crate::partition::ArrayValue::U32($values) => $body,
+            $crate::partition::ArrayValue::I32($values) => $body,
+            $crate::partition::ArrayValue::U64($values) => $body,
+            $crate::partition::ArrayValue::I64($values) => $body,
+        }
+    };
+}
+pub(crate) use for_each_array;
+
+impl ArrayValue {
+    /// Return the CSV encoding column string, the element type followed by `[]`.
+    pub fn encoding_str(&self) -> &'static str {
+        match self {
+            Self::I8(_) => "i8[]",
+            Self::U16(_) => "u16[]",
+            Self::I16(_) => "i16[]",
+            Self::U32(_) => "u32[]",
+            Self::I32(_) => "i32[]",
+            Self::U64(_) => "u64[]",
+            Self::I64(_) => "i64[]",
+        }
+    }
+
+    /// Return the number of elements.
+    pub fn len(&self) -> usize {
+        for_each_array!(self, values => values.len())
+    }
+
+    /// Return `true` if the array has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Encode the array as the blob the driver writes for it, the inverse of
+    /// [`ArrayValue::from_blob`].
+    pub fn to_blob(&self) -> Vec<u8> {
+        fn blob<const N: usize, E: Copy>(tag: esp_nvs::ItemType, values: &[E], to_le: fn(E) -> [u8; N]) -> Vec<u8> {
+            let mut blob = Vec::with_capacity(1 + values.len() * N);
+            blob.push(tag as u8);
+            blob.extend(values.iter().flat_map(|&value| to_le(value)));
+            blob
+        }
+
+        match self {
+            Self::I8(values) => blob(esp_nvs::ItemType::I8, values, i8::to_le_bytes),
+            Self::U16(values) => blob(esp_nvs::ItemType::U16, values, u16::to_le_bytes),
+            Self::I16(values) => blob(esp_nvs::ItemType::I16, values, i16::to_le_bytes),
+            Self::U32(values) => blob(esp_nvs::ItemType::U32, values, u32::to_le_bytes),
+            Self::I32(values) => blob(esp_nvs::ItemType::I32, values, i32::to_le_bytes),
+            Self::U64(values) => blob(esp_nvs::ItemType::U64, values, u64::to_le_bytes),
+            Self::I64(values) => blob(esp_nvs::ItemType::I64, values, i64::to_le_bytes),
+        }
+    }
+
+    /// Decode a blob written by the driver for a slice of primitives, e.g. a
+    /// [`DataValue::Binary`] parsed from a binary.
+    ///
+    /// Returns `None` if the blob doesn't start with the type of a supported element or its
+    /// length isn't a multiple of the element size. As any blob might look like an array, this
+    /// should only be used for keys known to hold one.
+    pub fn from_blob(blob: &[u8]) -> Option<Self> {
+        fn elements<const N: usize, E>(data: &[u8], from_le: fn([u8; N]) -> E) -> Option<Vec<E>> {
+            if !data.len().is_multiple_of(N) {
+                return None;
+            }
+            Some(data.chunks_exact(N).map(|c| from_le(c.try_into().unwrap())).collect())
+        }
+
+        let (&tag, data) = blob.split_first()?;
+        match esp_nvs::ItemType::from_repr(tag)? {
+            esp_nvs::ItemType::I8 => elements(data, i8::from_le_bytes).map(Self::I8),
+            esp_nvs::ItemType::U16 => elements(data, u16::from_le_bytes).map(Self::U16),
+            esp_nvs::ItemType::I16 => elements(data, i16::from_le_bytes).map(Self::I16),
+            esp_nvs::ItemType::U32 => elements(data, u32::from_le_bytes).map(Self::U32),
+            esp_nvs::ItemType::I32 => elements(data, i32::from_le_bytes).map(Self::I32),
+            esp_nvs::ItemType::U64 => elements(data, u64::from_le_bytes).map(Self::U64),
+            esp_nvs::ItemType::I64 => elements(data, i64::from_le_bytes).map(Self::I64),
+            _ => None,
+        }
+    }
+}
+
+/// The elements separated by spaces, e.g. `256 512`.
+impl std::fmt::Display for ArrayValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for_each_array!(self, values => {
+            for (idx, value) in values.iter().enumerate() {
+                if idx > 0 {
+                    f.write_str(" ")?;
+                }
+                write!(f, "{value}")?;
+            }
+            Ok(())
+        })
+    }
+}
+
 impl std::fmt::Display for DataValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -140,10 +301,12 @@ impl std::fmt::Display for DataValue {
             Self::U64(v) => write!(f, "{v}"),
             Self::I64(v) => write!(f, "{v}"),
             Self::String(s) => f.write_str(s),
-            Self::Binary(b) => {
+            Self::Binary(b) if b.len() <= MAX_INLINE_HEX_SIZE => f.write_str(&hex::encode(b)),
+            Self::Binary(b) | Self::LegacyBinary(b) => {
                 use base64::Engine;
                 f.write_str(&base64::engine::general_purpose::STANDARD.encode(b))
             }
+            Self::Array(array) => array.fmt(f),
         }
     }
 }
@@ -157,6 +320,8 @@ impl NvsEntry {
             namespace,
             key,
             content: EntryContent::Data(value),
+            chunk_start: 0,
+            namespace_index: None,
         }
     }
 
@@ -169,6 +334,8 @@ impl NvsEntry {
             namespace,
             key,
             content: EntryContent::File { encoding, file_path },
+            chunk_start: 0,
+            namespace_index: None,
         }
     }
 
@@ -192,11 +359,18 @@ impl NvsEntry {
     }
 }
 
-/// Validate that `key` is non-empty and within the NVS maximum key length.
+/// Validate that `key` is non-empty, within the NVS maximum key length and free of null bytes,
+/// which would end the key early on the device.
 pub(crate) fn validate_key(key: &str) -> Result<(), Error> {
     if key.is_empty() {
         return Err(Error::InvalidKey("key must not be empty".to_string()));
     }
+    if key.contains('\0') {
+        return Err(Error::InvalidKey(format!(
+            "key '{}' must not contain null bytes",
+            key.escape_debug()
+        )));
+    }
     if key.len() > MAX_KEY_LENGTH {
         return Err(Error::InvalidKey(format!(
             "key '{}' is too long (max {} characters)",
@@ -205,3 +379,25 @@ pub(crate) fn validate_key(key: &str) -> Result<(), Error> {
     }
     Ok(())
 }
+
+/// Decode base64 with or without padding, in the standard or the URL-safe alphabet. Other tools
+/// don't necessarily emit the padded standard variant written by this crate.
+///
+/// If no variant matches, the error of the padded standard variant is returned.
+pub(crate) fn decode_base64(value: &str) -> Result<Vec<u8>, Error> {
+    use base64::Engine;
+    use base64::engine::general_purpose::{
+        STANDARD,
+        STANDARD_NO_PAD,
+        URL_SAFE,
+        URL_SAFE_NO_PAD,
+    };
+
+    let value = value.trim();
+    STANDARD.decode(value).or_else(|e| {
+        [STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD]
+            .iter()
+            .find_map(|engine| engine.decode(value).ok())
+            .ok_or(Error::Base64Error(e))
+    })
+}
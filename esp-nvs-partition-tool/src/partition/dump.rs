@@ -0,0 +1,177 @@
+use std::fmt;
+use std::fmt::Write;
+
+use esp_nvs::{
+    ENTRIES_PER_PAGE,
+    FLASH_SECTOR_SIZE,
+    ITEM_SIZE,
+    ItemType,
+    PAGE_HEADER_SIZE,
+    PageState,
+};
+
+use super::validator::{
+    ENTRY_OFFSET,
+    ENTRY_STATE_EMPTY,
+    ENTRY_STATE_ILLEGAL,
+    ENTRY_STATE_WRITTEN,
+    entry_state,
+};
+use crate::crc::{
+    crc32,
+    crc32_entry,
+    crc32_page_header,
+};
+
+/// Describe every page and entry of a partition binary, see [`crate::NvsPartition::annotate`].
+///
+/// Nothing is interpreted across entries or pages, so even a badly damaged partition can be
+/// dumped. A trailing partial page is ignored.
+pub(crate) fn annotate(data: &[u8]) -> String {
+    let mut out = String::new();
+    for (page, raw) in data.chunks_exact(FLASH_SECTOR_SIZE).enumerate() {
+        // writing to a String can't fail
+        annotate_page(&mut out, page, raw).unwrap();
+    }
+    out
+}
+
+fn annotate_page(out: &mut String, page: usize, raw: &[u8]) -> fmt::Result {
+    let state = u32::from_le_bytes(raw[0..4].try_into().unwrap());
+    let state_name = match PageState::from_repr(state) {
+        Some(state) => state.to_string(),
+        None => "unknown state".to_string(),
+    };
+    write!(
+        out,
+        "page {page} @ 0x{:06x}: {state_name} (0x{state:08x})",
+        page * FLASH_SECTOR_SIZE
+    )?;
+
+    if raw.iter().all(|&b| b == 0xFF) {
+        return writeln!(out, ", erased");
+    }
+
+    let sequence = u32::from_le_bytes(raw[4..8].try_into().unwrap());
+    let crc = u32::from_le_bytes(raw[28..32].try_into().unwrap());
+    writeln!(
+        out,
+        ", sequence {sequence}, version 0x{:02x}, header CRC {}",
+        raw[8],
+        crc_status(crc == crc32_page_header(raw[..PAGE_HEADER_SIZE].try_into().unwrap()))
+    )?;
+
+    let mut index = 0;
+    while index < ENTRIES_PER_PAGE {
+        let state = entry_state(raw, index);
+
+        // runs of empty entries are shown as a single line
+        if state == ENTRY_STATE_EMPTY {
+            let end = (index..ENTRIES_PER_PAGE)
+                .find(|&i| entry_state(raw, i) != ENTRY_STATE_EMPTY)
+                .unwrap_or(ENTRIES_PER_PAGE);
+            writeln!(out, "  {}: empty", Entries(index, end - 1))?;
+            index = end;
+            continue;
+        }
+
+        let state_name = match state {
+            ENTRY_STATE_WRITTEN => "written",
+            ENTRY_STATE_ILLEGAL => "illegal",
+            _ => "erased",
+        };
+        let entry = &raw[ENTRY_OFFSET + index * ITEM_SIZE..ENTRY_OFFSET + (index + 1) * ITEM_SIZE];
+        write!(out, "  entry {index}: {state_name}")?;
+        if state == ENTRY_STATE_ILLEGAL {
+            writeln!(out)?;
+            index += 1;
+            continue;
+        }
+
+        let span = annotate_entry(out, raw, index, entry)?;
+        // the span of erased entries isn't trustworthy, their data is shown as separate entries
+        let data_entries = match state {
+            ENTRY_STATE_WRITTEN => span.saturating_sub(1).min(ENTRIES_PER_PAGE - index - 1),
+            _ => 0,
+        };
+        if data_entries > 0 {
+            writeln!(
+                out,
+                "  {}: data of entry {index}",
+                Entries(index + 1, index + data_entries)
+            )?;
+        }
+        index += 1 + data_entries;
+    }
+
+    Ok(())
+}
+
+/// Describe a single entry and return its span.
+fn annotate_entry(out: &mut String, page: &[u8], index: usize, entry: &[u8]) -> Result<usize, fmt::Error> {
+    let namespace_index = entry[0];
+    let span = entry[2] as usize;
+    let chunk_index = entry[3];
+    let key = &entry[8..24];
+    let key = &key[..key.iter().position(|&b| b == 0).unwrap_or(key.len())];
+    let data = &entry[24..32];
+
+    let crc_ok = crc32_entry(entry.try_into().unwrap()) == u32::from_le_bytes(entry[4..8].try_into().unwrap());
+
+    write!(out, ", namespace {namespace_index}")?;
+    let type_ = ItemType::from_repr(entry[1]);
+    match type_ {
+        Some(type_) => write!(out, ", {type_}")?,
+        None => write!(out, ", unknown type 0x{:02x}", entry[1])?,
+    }
+    write!(
+        out,
+        ", span {span}, chunk 0x{chunk_index:02x}, key {:?}, CRC {}",
+        String::from_utf8_lossy(key),
+        crc_status(crc_ok)
+    )?;
+
+    match type_ {
+        _ if namespace_index == 0 => write!(out, ", namespace index {}", data[0])?,
+        Some(ItemType::Sized | ItemType::Blob | ItemType::BlobData) => {
+            let size = u16::from_le_bytes(data[0..2].try_into().unwrap()) as usize;
+            write!(out, ", size {size}")?;
+
+            let start = ENTRY_OFFSET + (index + 1) * ITEM_SIZE;
+            if span > 0 && size <= (span - 1) * ITEM_SIZE && start + size <= page.len() {
+                let data_crc = u32::from_le_bytes(data[4..8].try_into().unwrap());
+                let data_ok = data_crc == crc32(&page[start..start + size]);
+                write!(out, ", data CRC {}", crc_status(data_ok))?;
+            } else {
+                write!(out, ", size exceeds span")?;
+            }
+        }
+        Some(ItemType::BlobIndex) => write!(
+            out,
+            ", size {}, chunks {}, chunk start {}",
+            u32::from_le_bytes(data[0..4].try_into().unwrap()),
+            data[4],
+            data[5]
+        )?,
+        _ => write!(out, ", data {}", hex::encode(data))?,
+    }
+    writeln!(out)?;
+
+    Ok(span)
+}
+
+/// An inclusive range of entries.
+struct Entries(usize, usize);
+
+impl fmt::Display for Entries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entries(first, last) if first == last => write!(f, "entry {first}"),
+            Entries(first, last) => write!(f, "entries {first}-{last}"),
+        }
+    }
+}
+
+fn crc_status(ok: bool) -> &'static str {
+    if ok { "ok" } else { "mismatch" }
+}
@@ -1,69 +1,308 @@
+use std::borrow::Cow;
+use std::collections::{
+    BTreeMap,
+    HashMap,
+};
 use std::fs::read;
 
-use base64::Engine;
 use esp_nvs::mem_flash::MemFlash;
 use esp_nvs::{
     Key,
+    LegacyBlob,
     Nvs,
+    PageState,
 };
 
 use super::{
     DataValue,
     EntryContent,
     FileEncoding,
+    decode_base64,
+    for_each_array,
+    validate_key,
 };
 use crate::NvsPartition;
 use crate::error::Error;
 
+/// Options for [`NvsPartition::generate_partition_with_options`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GenerateOptions {
+    /// The state of the last page the entries were written to.
+    pub trailing_page: TrailingPage,
+    /// Fixed indices for namespaces, e.g. to match the partition of an existing device.
+    /// Namespaces without an entry get the lowest index not used otherwise, in the order they
+    /// first appear in.
+    pub namespace_indices: HashMap<String, u8>,
+}
+
+/// State of the last used page of a generated partition.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrailingPage {
+    /// Leave the page like the driver does: `ACTIVE`, unless the last entry filled it up.
+    #[default]
+    Auto,
+    /// Keep the page `ACTIVE` even if it is full, so the device continues writing to it.
+    Active,
+    /// Mark the page `FULL`, so the device starts a new page with its first write.
+    Full,
+}
+
 /// Generate an NVS partition binary in memory and return it as a `Vec<u8>`.
 ///
 /// `size` must be a multiple of 4096 (the ESP-IDF flash sector size).
-pub(crate) fn generate_partition_data(partition: &NvsPartition, size: usize) -> Result<Vec<u8>, Error> {
+pub(crate) fn generate_partition_data(
+    partition: &NvsPartition,
+    size: usize,
+    options: &GenerateOptions,
+) -> Result<Vec<u8>, Error> {
     if size < esp_nvs::FLASH_SECTOR_SIZE {
         return Err(Error::PartitionTooSmall(size));
     } else if !size.is_multiple_of(esp_nvs::FLASH_SECTOR_SIZE) {
         return Err(Error::InvalidPartitionSize(size));
     }
 
+    validate_keys(partition)?;
+    let indices = namespace_indices(partition, &options.namespace_indices)?;
+    let values = resolve_values(partition)?;
+    let mut data = if size == esp_nvs::FLASH_SECTOR_SIZE {
+        generate_single_page(&values, &partition.empty_namespaces, &indices)?
+    } else {
+        generate(&values, &partition.empty_namespaces, &indices, size)?
+    };
+    set_trailing_page(&mut data, options.trailing_page);
+    Ok(data)
+}
+
+/// Generate a partition of a single page.
+///
+/// The driver refuses to write to the last free page, as it keeps it for reclaiming pages, which
+/// a single page image doesn't need. So the entries are written to two pages and the second one
+/// has to stay unused.
+fn generate_single_page(
+    values: &[ResolvedValue<'_>],
+    empty_namespaces: &[String],
+    namespace_indices: &BTreeMap<Key, u8>,
+) -> Result<Vec<u8>, Error> {
+    let mut data = generate(
+        values,
+        empty_namespaces,
+        namespace_indices,
+        2 * esp_nvs::FLASH_SECTOR_SIZE,
+    )?;
+    if data[esp_nvs::FLASH_SECTOR_SIZE..].iter().any(|&b| b != 0xFF) {
+        return Err(Error::NvsError(esp_nvs::error::Error::FlashFull));
+    }
+    data.truncate(esp_nvs::FLASH_SECTOR_SIZE);
+    Ok(data)
+}
+
+/// Change the state of the page with the highest sequence number. The state isn't covered by the
+/// header CRC, so only the state word has to be rewritten.
+fn set_trailing_page(data: &mut [u8], trailing_page: TrailingPage) {
+    let (from, to) = match trailing_page {
+        TrailingPage::Auto => return,
+        TrailingPage::Active => (PageState::Full, PageState::Active),
+        TrailingPage::Full => (PageState::Active, PageState::Full),
+    };
+
+    let state = |page: &[u8]| u32::from_le_bytes(page[0..4].try_into().unwrap());
+    let sequence = |page: &[u8]| u32::from_le_bytes(page[4..8].try_into().unwrap());
+
+    let last = data
+        .chunks_exact_mut(esp_nvs::FLASH_SECTOR_SIZE)
+        .filter(|page| state(page) == PageState::Active as u32 || state(page) == PageState::Full as u32)
+        .max_by_key(|page| sequence(page));
+
+    if let Some(page) = last
+        && state(page) == from as u32
+    {
+        page[0..4].copy_from_slice(&(to as u32).to_le_bytes());
+    }
+}
+
+/// Find the smallest partition size the entries fit into.
+///
+/// Instead of replicating the page layout, the partition is generated with an increasing number
+/// of pages until the driver stops reporting a full flash, or a blob too long for a partition of
+/// that size. This accounts for everything the driver does, including the page it keeps free for
+/// compaction. A single page is tried first like [`generate_single_page`] creates it, without a
+/// page kept free.
+pub(crate) fn min_partition_size(partition: &NvsPartition) -> Result<usize, Error> {
+    validate_keys(partition)?;
+    let values = resolve_values(partition)?;
+    let indices = namespace_indices(partition, &HashMap::new())?;
+
+    let mut pages = 1;
+    loop {
+        let size = pages * esp_nvs::FLASH_SECTOR_SIZE;
+        let result = if pages == 1 {
+            generate_single_page(&values, &partition.empty_namespaces, &indices)
+        } else {
+            generate(&values, &partition.empty_namespaces, &indices, size)
+        };
+        match result {
+            Ok(_) => return Ok(size),
+            Err(Error::NvsError(esp_nvs::error::Error::FlashFull)) => pages += 1,
+            // the size of blobs is limited by the number of pages up to MAX_BLOB_SIZE
+            Err(Error::NvsError(esp_nvs::error::Error::ValueTooLong))
+                if Nvs::new(0, size, MemFlash::new(pages))?.max_blob_size() < esp_nvs::MAX_BLOB_SIZE =>
+            {
+                pages += 1
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Validate the namespace and key of every entry, as entries created in code, e.g. with
+/// [`NvsPartition::set`], aren't validated when they are added.
+fn validate_keys(partition: &NvsPartition) -> Result<(), Error> {
+    for entry in &partition.entries {
+        validate_key(&entry.namespace)?;
+        validate_key(&entry.key)?;
+    }
+    for namespace in &partition.empty_namespaces {
+        validate_key(namespace)?;
+    }
+    Ok(())
+}
+
+/// Assign an index to every namespace of the partition, see
+/// [`GenerateOptions::namespace_indices`]. Without fixed indices this is the order the driver
+/// assigns them in.
+fn namespace_indices(partition: &NvsPartition, fixed: &HashMap<String, u8>) -> Result<BTreeMap<Key, u8>, Error> {
+    // the indices recorded in the entries apply to namespaces the options don't fix
+    let mut recorded: HashMap<String, u8> = HashMap::new();
+    for entry in &partition.entries {
+        let Some(index) = entry.namespace_index else {
+            continue;
+        };
+        match recorded.insert(entry.namespace.clone(), index) {
+            Some(other) if other != index && !fixed.contains_key(&entry.namespace) => {
+                return Err(Error::InvalidNamespaceIndex(format!(
+                    "entries of namespace '{}' use the indices {} and {}",
+                    entry.namespace,
+                    other.min(index),
+                    other.max(index)
+                )));
+            }
+            _ => {}
+        }
+    }
+    recorded.extend(fixed.iter().map(|(namespace, &index)| (namespace.clone(), index)));
+    let fixed = &recorded;
+
+    let mut used = BTreeMap::new();
+    for (namespace, &index) in fixed {
+        validate_key(namespace)?;
+        if index == 0 {
+            return Err(Error::InvalidNamespaceIndex(format!(
+                "namespace '{namespace}' can't use index 0"
+            )));
+        }
+        if let Some(other) = used.insert(index, namespace) {
+            let (first, second) = if other < namespace {
+                (other, namespace)
+            } else {
+                (namespace, other)
+            };
+            return Err(Error::InvalidNamespaceIndex(format!(
+                "namespaces '{first}' and '{second}' both use index {index}"
+            )));
+        }
+    }
+
+    let mut indices: BTreeMap<Key, u8> = fixed
+        .iter()
+        .map(|(namespace, &index)| Ok((Key::try_from_str(namespace)?, index)))
+        .collect::<Result<_, Error>>()?;
+
+    let namespaces = partition.entries.iter().map(|entry| &entry.namespace);
+    let mut next = 1u8;
+    for name in namespaces.chain(&partition.empty_namespaces) {
+        let namespace = Key::try_from_str(name)?;
+        if indices.contains_key(&namespace) {
+            continue;
+        }
+
+        while used.contains_key(&next) {
+            next = next.checked_add(1).ok_or(Error::TooManyNamespaces)?;
+        }
+        indices.insert(namespace, next);
+        used.insert(next, name);
+    }
+
+    Ok(indices)
+}
+
+/// The namespace, key, value and blob chunk start of an entry.
+type ResolvedValue<'a> = (Key, Key, Cow<'a, DataValue>, u8);
+
+/// Resolve the value of every entry. For file entries, read the file and convert it to a
+/// `DataValue`.
+fn resolve_values(partition: &NvsPartition) -> Result<Vec<ResolvedValue<'_>>, Error> {
+    partition
+        .entries
+        .iter()
+        .map(|entry| {
+            let namespace = Key::try_from_str(&entry.namespace)?;
+            let key = Key::try_from_str(&entry.key)?;
+
+            let value = match &entry.content {
+                EntryContent::Data(val) => Cow::Borrowed(val),
+                EntryContent::File { encoding, file_path } => {
+                    let content = read(file_path)?;
+                    Cow::Owned(parse_file_content(&content, encoding)?)
+                }
+            };
+
+            Ok((namespace, key, value, entry.chunk_start))
+        })
+        .collect()
+}
+
+fn generate(
+    values: &[ResolvedValue<'_>],
+    empty_namespaces: &[String],
+    namespace_indices: &BTreeMap<Key, u8>,
+    size: usize,
+) -> Result<Vec<u8>, Error> {
     let pages = size / esp_nvs::FLASH_SECTOR_SIZE;
     let flash = MemFlash::new(pages);
     let mut nvs = Nvs::new(0, size, flash)?;
 
-    for entry in &partition.entries {
-        let namespace = Key::from_str(&entry.namespace);
-        let key = Key::from_str(&entry.key);
-
-        // Resolve the value from the entry content.
-        // For file entries, read the file and convert to a DataValue at generation
-        // time.
-        let resolved_value;
-        let value = match &entry.content {
-            EntryContent::Data(val) => val,
-            EntryContent::File { encoding, file_path } => {
-                let content = read(file_path)?;
-                resolved_value = parse_file_content(&content, encoding)?;
-                &resolved_value
-            }
-        };
+    for (namespace, key, value, chunk_start) in values {
+        // writes the namespace entry in front of the first value, like the driver would
+        nvs.create_namespace(namespace, namespace_indices[namespace])?;
 
-        match value {
-            DataValue::U8(v) => nvs.set(&namespace, &key, *v)?,
-            DataValue::I8(v) => nvs.set(&namespace, &key, *v)?,
-            DataValue::U16(v) => nvs.set(&namespace, &key, *v)?,
-            DataValue::I16(v) => nvs.set(&namespace, &key, *v)?,
-            DataValue::U32(v) => nvs.set(&namespace, &key, *v)?,
-            DataValue::I32(v) => nvs.set(&namespace, &key, *v)?,
-            DataValue::U64(v) => nvs.set(&namespace, &key, *v)?,
-            DataValue::I64(v) => nvs.set(&namespace, &key, *v)?,
-            DataValue::String(s) => nvs.set(&namespace, &key, s.as_str())?,
-            DataValue::Binary(b) => nvs.set(&namespace, &key, b.as_slice())?,
+        match value.as_ref() {
+            DataValue::U8(v) => nvs.set(namespace, key, *v)?,
+            DataValue::I8(v) => nvs.set(namespace, key, *v)?,
+            DataValue::U16(v) => nvs.set(namespace, key, *v)?,
+            DataValue::I16(v) => nvs.set(namespace, key, *v)?,
+            DataValue::U32(v) => nvs.set(namespace, key, *v)?,
+            DataValue::I32(v) => nvs.set(namespace, key, *v)?,
+            DataValue::U64(v) => nvs.set(namespace, key, *v)?,
+            DataValue::I64(v) => nvs.set(namespace, key, *v)?,
+            DataValue::String(s) => nvs.set(namespace, key, s.as_str())?,
+            DataValue::Binary(b) if *chunk_start != 0 => {
+                nvs.set_blob_with_chunk_start(namespace, key, b.as_slice(), *chunk_start)?
+            }
+            DataValue::Binary(b) => nvs.set(namespace, key, b.as_slice())?,
+            DataValue::LegacyBinary(b) => nvs.set(namespace, key, LegacyBlob(b))?,
+            DataValue::Array(array) => for_each_array!(array, values => nvs.set(namespace, key, values.as_slice())?),
         }
     }
 
+    for namespace in empty_namespaces {
+        let namespace = Key::try_from_str(namespace)?;
+        nvs.create_namespace(&namespace, namespace_indices[&namespace])?;
+    }
+
     Ok(nvs.into_inner().into_inner())
 }
 
-fn parse_file_content(content: &[u8], encoding: &FileEncoding) -> Result<DataValue, Error> {
+pub(crate) fn parse_file_content(content: &[u8], encoding: &FileEncoding) -> Result<DataValue, Error> {
     match encoding {
         FileEncoding::String => {
             let s = std::str::from_utf8(content)
@@ -79,7 +318,7 @@ fn parse_file_content(content: &[u8], encoding: &FileEncoding) -> Result<DataVal
         FileEncoding::Base64 => {
             let b64_str = std::str::from_utf8(content)
                 .map_err(|e| Error::InvalidValue(format!("invalid UTF-8 in base64 file: {}", e)))?;
-            let bytes = base64::engine::general_purpose::STANDARD.decode(b64_str.trim())?;
+            let bytes = decode_base64(b64_str)?;
             Ok(DataValue::Binary(bytes))
         }
         FileEncoding::Binary => Ok(DataValue::Binary(content.to_vec())),
@@ -1,19 +1,106 @@
+use std::fmt;
+
 use esp_nvs::mem_flash::MemFlash;
 use esp_nvs::{
     ItemType,
     Key,
     Nvs,
+    PageState,
 };
 
+use super::validator::Problem;
 use super::{
     DataValue,
     NvsEntry,
+    PAGE_VERSION_1,
+    PAGE_VERSION_2,
+    validator,
 };
 use crate::NvsPartition;
 use crate::error::Error;
 
 /// Parse an NVS partition binary from an in-memory byte slice.
-pub(crate) fn parse_binary_data(data: &[u8]) -> Result<NvsPartition, Error> {
+///
+/// With a `namespace`, only its entries are read. All pages are still loaded and checked.
+pub(crate) fn parse_binary_data(data: &[u8], namespace: Option<&str>) -> Result<NvsPartition, Error> {
+    check_size(data)?;
+    check_page_versions(data)?;
+
+    let mut nvs = Nvs::from_image(data)?;
+
+    let filter = match namespace {
+        Some(name) => {
+            let key = Key::from_str(name);
+            if !nvs.namespaces().any(|namespace| *namespace == key) {
+                return Err(Error::NamespaceNotFound(name.to_string()));
+            }
+            Some(key)
+        }
+        None => None,
+    };
+
+    read_entries(&mut nvs, filter, |_, e| Err(e))
+}
+
+/// Parse an NVS partition binary, skipping everything that can't be read instead of failing.
+///
+/// The problems found by the validator are reported as warnings, the driver ignores the affected
+/// entries when loading the pages. Pages of an unknown version are dropped entirely.
+pub(crate) fn parse_binary_data_lenient(data: &[u8]) -> Result<(NvsPartition, Vec<ParseWarning>), Error> {
+    check_size(data)?;
+
+    let mut warnings: Vec<ParseWarning> = validator::validate(data)?
+        .problems
+        .into_iter()
+        .map(ParseWarning::Problem)
+        .collect();
+
+    // already reported by the validator
+    let mut data = data.to_vec();
+    for raw in data.chunks_exact_mut(esp_nvs::FLASH_SECTOR_SIZE) {
+        if is_loaded(raw) && !is_known_version(raw[8]) {
+            raw.fill(0xFF);
+        }
+    }
+
+    let mut nvs = Nvs::from_image(&data)?;
+    let partition = read_entries(&mut nvs, None, |entry, e| {
+        warnings.push(ParseWarning::UnreadableEntry {
+            namespace: entry.0.to_string(),
+            key: entry.1.to_string(),
+            reason: e.to_string(),
+        });
+        Ok(())
+    })?;
+
+    Ok((partition, warnings))
+}
+
+/// Something [`crate::NvsPartition::parse_partition_lenient`] skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseWarning {
+    /// A structural problem, the affected page or entry was ignored.
+    Problem(Problem),
+    /// The value of an entry couldn't be read and was left out.
+    UnreadableEntry {
+        namespace: String,
+        key: String,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Problem(problem) => write!(f, "{problem}"),
+            Self::UnreadableEntry { namespace, key, reason } => {
+                write!(f, "entry '{namespace}/{key}' skipped: {reason}")
+            }
+        }
+    }
+}
+
+fn check_size(data: &[u8]) -> Result<(), Error> {
     if data.is_empty() {
         return Err(Error::InvalidValue(
             "binary data is empty; an NVS partition requires at least one page (4096 bytes)".to_string(),
@@ -28,42 +115,102 @@ pub(crate) fn parse_binary_data(data: &[u8]) -> Result<NvsPartition, Error> {
         )));
     }
 
-    let size = data.len();
-    let flash = MemFlash::from_bytes(data.to_vec());
-    let mut nvs = Nvs::new(0, size, flash)?;
+    Ok(())
+}
 
+/// Read the values of all entries, or only those of the `filter` namespace. If a value can't be
+/// read, `on_error` decides whether to skip it or to abort.
+fn read_entries(
+    nvs: &mut Nvs<MemFlash>,
+    filter: Option<Key>,
+    mut on_error: impl FnMut((Key, Key), Error) -> Result<(), Error>,
+) -> Result<NvsPartition, Error> {
     let mut entries = Vec::new();
 
     // Collect all typed entries first, then read values by type
-    let typed: Vec<(Key, Key, ItemType)> = nvs.typed_entries().collect::<Result<Vec<_>, _>>()?;
+    let typed: Vec<(Key, Key, ItemType)> = nvs
+        .typed_entries()
+        .filter(|entry| match (entry, filter) {
+            (Ok((namespace, _, _)), Some(filter)) => *namespace == filter,
+            _ => true,
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
     for (ns_key, entry_key, item_type) in typed {
-        let namespace = ns_key.as_str().to_string();
-        let key = entry_key.as_str().to_string();
-
-        let value = match item_type {
-            ItemType::U8 => DataValue::U8(nvs.get::<u8>(&ns_key, &entry_key)?),
-            ItemType::I8 => DataValue::I8(nvs.get::<i8>(&ns_key, &entry_key)?),
-            ItemType::U16 => DataValue::U16(nvs.get::<u16>(&ns_key, &entry_key)?),
-            ItemType::I16 => DataValue::I16(nvs.get::<i16>(&ns_key, &entry_key)?),
-            ItemType::U32 => DataValue::U32(nvs.get::<u32>(&ns_key, &entry_key)?),
-            ItemType::I32 => DataValue::I32(nvs.get::<i32>(&ns_key, &entry_key)?),
-            ItemType::U64 => DataValue::U64(nvs.get::<u64>(&ns_key, &entry_key)?),
-            ItemType::I64 => DataValue::I64(nvs.get::<i64>(&ns_key, &entry_key)?),
-            ItemType::Sized => DataValue::String(nvs.get::<String>(&ns_key, &entry_key)?),
-            ItemType::BlobIndex | ItemType::BlobData | ItemType::Blob => {
-                DataValue::Binary(nvs.get::<Vec<u8>>(&ns_key, &entry_key)?)
-            }
-            ItemType::Any => {
-                return Err(Error::InvalidValue(format!(
-                    "unexpected item type {:?} for key '{}'",
-                    item_type, key
-                )));
-            }
-        };
+        match read_entry(nvs, &ns_key, &entry_key, item_type) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => on_error((ns_key, entry_key), e)?,
+        }
+    }
 
-        entries.push(NvsEntry::new_data(namespace, key, value));
+    let empty_namespaces = nvs
+        .namespaces()
+        .filter(|namespace| filter.is_none_or(|filter| **namespace == filter))
+        .map(|namespace| namespace.to_string())
+        .filter(|namespace| !entries.iter().any(|entry| entry.namespace == *namespace))
+        .collect();
+
+    Ok(NvsPartition {
+        entries,
+        empty_namespaces,
+    })
+}
+
+fn read_entry(nvs: &mut Nvs<MemFlash>, ns_key: &Key, entry_key: &Key, item_type: ItemType) -> Result<NvsEntry, Error> {
+    let value = read_value(nvs, ns_key, entry_key, item_type)?;
+    let chunk_start = match value {
+        DataValue::Binary(_) => nvs.blob_chunk_start(ns_key, entry_key)?,
+        _ => 0,
+    };
+    let mut entry = NvsEntry::new_data(ns_key.to_string(), entry_key.to_string(), value);
+    entry.chunk_start = chunk_start;
+    entry.namespace_index = nvs.namespace_index(ns_key);
+    Ok(entry)
+}
+
+fn read_value(nvs: &mut Nvs<MemFlash>, ns_key: &Key, entry_key: &Key, item_type: ItemType) -> Result<DataValue, Error> {
+    let value = match item_type {
+        ItemType::U8 => DataValue::U8(nvs.get::<u8>(ns_key, entry_key)?),
+        ItemType::I8 => DataValue::I8(nvs.get::<i8>(ns_key, entry_key)?),
+        ItemType::U16 => DataValue::U16(nvs.get::<u16>(ns_key, entry_key)?),
+        ItemType::I16 => DataValue::I16(nvs.get::<i16>(ns_key, entry_key)?),
+        ItemType::U32 => DataValue::U32(nvs.get::<u32>(ns_key, entry_key)?),
+        ItemType::I32 => DataValue::I32(nvs.get::<i32>(ns_key, entry_key)?),
+        ItemType::U64 => DataValue::U64(nvs.get::<u64>(ns_key, entry_key)?),
+        ItemType::I64 => DataValue::I64(nvs.get::<i64>(ns_key, entry_key)?),
+        ItemType::Sized => DataValue::String(nvs.get::<String>(ns_key, entry_key)?),
+        ItemType::BlobIndex | ItemType::BlobData => DataValue::Binary(nvs.get::<Vec<u8>>(ns_key, entry_key)?),
+        ItemType::Blob => DataValue::LegacyBinary(nvs.get::<Vec<u8>>(ns_key, entry_key)?),
+        ItemType::Any => {
+            return Err(Error::InvalidValue(format!(
+                "unexpected item type {:?} for key '{}'",
+                item_type, entry_key
+            )));
+        }
+    };
+    Ok(value)
+}
+
+/// Both known page versions share the same layout, version 1 pages just never contain multi-page
+/// blobs. Pages of a newer version might not, so they are rejected like ESP-IDF does.
+fn check_page_versions(data: &[u8]) -> Result<(), Error> {
+    for (page, raw) in data.chunks_exact(esp_nvs::FLASH_SECTOR_SIZE).enumerate() {
+        if is_loaded(raw) && !is_known_version(raw[8]) {
+            return Err(Error::UnsupportedPageVersion { page, version: raw[8] });
+        }
     }
+    Ok(())
+}
+
+/// Returns `true` for the page states whose entries are loaded by the driver.
+fn is_loaded(page: &[u8]) -> bool {
+    let state = u32::from_le_bytes(page[0..4].try_into().unwrap());
+    matches!(
+        PageState::from_repr(state),
+        Some(PageState::Active | PageState::Full | PageState::Freeing)
+    )
+}
 
-    Ok(NvsPartition { entries })
+fn is_known_version(version: u8) -> bool {
+    version == PAGE_VERSION_1 || version == PAGE_VERSION_2
 }
@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use esp_nvs::{
+    ENTRIES_PER_PAGE,
+    FLASH_SECTOR_SIZE,
+    ITEM_SIZE,
+    ItemType,
+    Key,
+    PAGE_HEADER_SIZE,
+    PageState,
+};
+
+use crate::crc::{
+    crc32,
+    crc32_entry,
+    crc32_page_header,
+};
+use crate::error::Error;
+use crate::partition::{
+    PAGE_VERSION_1,
+    PAGE_VERSION_2,
+};
+
+pub(crate) const ENTRY_OFFSET: usize = PAGE_HEADER_SIZE + esp_nvs::ENTRY_STATE_BITMAP_SIZE;
+
+pub(crate) const ENTRY_STATE_EMPTY: u8 = 0b11;
+pub(crate) const ENTRY_STATE_WRITTEN: u8 = 0b10;
+pub(crate) const ENTRY_STATE_ILLEGAL: u8 = 0b01;
+pub(crate) const ENTRY_STATE_ERASED: u8 = 0b00;
+
+/// Chunk index of a blob index entry
+const CHUNK_ANY: u8 = 0xFF;
+
+/// All problems found by [`crate::NvsPartition::validate`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ValidationReport {
+    /// The problems in the order they were found.
+    pub problems: Vec<Problem>,
+}
+
+impl ValidationReport {
+    /// Returns `true` if no problems were found.
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for problem in &self.problems {
+            writeln!(f, "{problem}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A single problem and its location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    /// Index of the page, starting at 0 for the beginning of the partition.
+    pub page: usize,
+    /// Index of the entry within the page, `None` for problems of the page header.
+    pub entry: Option<usize>,
+    /// What is wrong.
+    pub kind: ProblemKind,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.entry {
+            Some(entry) => write!(f, "page {} entry {}: {}", self.page, entry, self.kind),
+            None => write!(f, "page {}: {}", self.page, self.kind),
+        }
+    }
+}
+
+/// The kinds of problems detected by [`crate::NvsPartition::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProblemKind {
+    /// The page state is not one of the states defined by ESP-IDF.
+    InvalidPageState(u32),
+    /// The CRC of the page header doesn't match.
+    HeaderCrcMismatch,
+    /// The page format version is neither 1 nor 2.
+    UnknownPageVersion(u8),
+    /// Another page uses the same sequence number.
+    DuplicateSequence(u32),
+    /// The entry state bitmap contains the illegal state `0b01`.
+    IllegalEntryState,
+    /// The entry is marked as empty but contains data.
+    EmptyEntryNotErased,
+    /// The CRC of the entry doesn't match.
+    EntryCrcMismatch,
+    /// The entry has an unknown type.
+    InvalidItemType(u8),
+    /// The span is zero, exceeds the page or doesn't fit the item type.
+    InvalidSpan(u8),
+    /// An entry covered by the span of a preceding entry isn't marked as written.
+    SpanNotWritten,
+    /// An entry covered by the span of an erased entry is still marked as written, e.g. because
+    /// erasing a string or blob chunk was interrupted. The driver erases it when loading the page.
+    OrphanedEntry,
+    /// The CRC of the variable length data doesn't match.
+    DataCrcMismatch,
+    /// The size of the variable length data exceeds the span.
+    DataSizeOutOfBounds(u16),
+    /// The namespace name is used by multiple namespace entries.
+    DuplicateNamespace(String),
+    /// The namespace index is used by multiple namespace entries.
+    DuplicateNamespaceIndex(u8),
+    /// The entry references a namespace index without namespace entry.
+    UnknownNamespace(u8),
+    /// The number of blob chunks doesn't match the blob index.
+    BlobChunkCountMismatch { key: String, expected: u8, found: usize },
+    /// The total size of the blob chunks doesn't match the blob index.
+    BlobSizeMismatch { key: String, expected: u32, found: usize },
+}
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPageState(state) => write!(f, "invalid page state 0x{state:08x}"),
+            Self::HeaderCrcMismatch => write!(f, "page header CRC mismatch"),
+            Self::UnknownPageVersion(version) => write!(f, "unknown page version 0x{version:02x}"),
+            Self::DuplicateSequence(sequence) => write!(f, "duplicate page sequence {sequence}"),
+            Self::IllegalEntryState => write!(f, "illegal entry state"),
+            Self::EmptyEntryNotErased => write!(f, "entry is marked as empty but not erased"),
+            Self::EntryCrcMismatch => write!(f, "entry CRC mismatch"),
+            Self::InvalidItemType(type_) => write!(f, "invalid item type 0x{type_:02x}"),
+            Self::InvalidSpan(span) => write!(f, "invalid span {span}"),
+            Self::SpanNotWritten => write!(f, "entry within span is not marked as written"),
+            Self::OrphanedEntry => write!(f, "entry within span of an erased entry is marked as written"),
+            Self::DataCrcMismatch => write!(f, "data CRC mismatch"),
+            Self::DataSizeOutOfBounds(size) => write!(f, "data size {size} exceeds span"),
+            Self::DuplicateNamespace(name) => write!(f, "duplicate namespace '{name}'"),
+            Self::DuplicateNamespaceIndex(index) => write!(f, "duplicate namespace index {index}"),
+            Self::UnknownNamespace(index) => write!(f, "unknown namespace index {index}"),
+            Self::BlobChunkCountMismatch { key, expected, found } => {
+                write!(f, "blob '{key}' expects {expected} chunks, found {found}")
+            }
+            Self::BlobSizeMismatch { key, expected, found } => {
+                write!(f, "blob '{key}' expects {expected} bytes, found {found}")
+            }
+        }
+    }
+}
+
+/// A written entry, kept for the checks across pages.
+struct Entry<'a> {
+    page: usize,
+    index: usize,
+    raw: &'a [u8],
+}
+
+impl Entry<'_> {
+    fn namespace_index(&self) -> u8 {
+        self.raw[0]
+    }
+
+    fn type_(&self) -> u8 {
+        self.raw[1]
+    }
+
+    fn chunk_index(&self) -> u8 {
+        self.raw[3]
+    }
+
+    fn key(&self) -> &[u8] {
+        &self.raw[8..24]
+    }
+
+    fn data(&self) -> &[u8] {
+        &self.raw[24..32]
+    }
+
+    fn problem(&self, kind: ProblemKind) -> Problem {
+        Problem {
+            page: self.page,
+            entry: Some(self.index),
+            kind,
+        }
+    }
+}
+
+pub(crate) fn validate(data: &[u8]) -> Result<ValidationReport, Error> {
+    if !data.len().is_multiple_of(FLASH_SECTOR_SIZE) {
+        return Err(Error::InvalidPartitionSize(data.len()));
+    }
+
+    let mut problems = Vec::new();
+    let mut sequences: HashMap<u32, usize> = HashMap::new();
+    let mut entries = Vec::new();
+
+    for (page, raw) in data.chunks_exact(FLASH_SECTOR_SIZE).enumerate() {
+        let state = u32::from_le_bytes(raw[0..4].try_into().unwrap());
+        match PageState::from_repr(state) {
+            Some(PageState::Uninitialized) => continue,
+            Some(PageState::Active | PageState::Full | PageState::Freeing) => {}
+            // corrupt pages are kept for diagnostics only and ignored by the driver
+            Some(PageState::Corrupt) => continue,
+            Some(PageState::Invalid) | None => {
+                problems.push(Problem {
+                    page,
+                    entry: None,
+                    kind: ProblemKind::InvalidPageState(state),
+                });
+                continue;
+            }
+        }
+
+        let crc = u32::from_le_bytes(raw[28..32].try_into().unwrap());
+        if crc != crc32_page_header(raw[..PAGE_HEADER_SIZE].try_into().unwrap()) {
+            problems.push(Problem {
+                page,
+                entry: None,
+                kind: ProblemKind::HeaderCrcMismatch,
+            });
+            continue;
+        }
+
+        let version = raw[8];
+        if version != PAGE_VERSION_1 && version != PAGE_VERSION_2 {
+            problems.push(Problem {
+                page,
+                entry: None,
+                kind: ProblemKind::UnknownPageVersion(version),
+            });
+            continue;
+        }
+
+        let sequence = u32::from_le_bytes(raw[4..8].try_into().unwrap());
+        if sequences.insert(sequence, page).is_some() {
+            problems.push(Problem {
+                page,
+                entry: None,
+                kind: ProblemKind::DuplicateSequence(sequence),
+            });
+        }
+
+        validate_page(page, raw, &mut entries, &mut problems);
+    }
+
+    validate_namespaces(&entries, &mut problems);
+    validate_blobs(&entries, &mut problems);
+
+    Ok(ValidationReport { problems })
+}
+
+pub(crate) fn entry_state(page: &[u8], index: usize) -> u8 {
+    (page[PAGE_HEADER_SIZE + index / 4] >> ((index % 4) * 2)) & 0b11
+}
+
+fn validate_page<'a>(page: usize, raw: &'a [u8], entries: &mut Vec<Entry<'a>>, problems: &mut Vec<Problem>) {
+    // end of the span of the last erased entry
+    let mut erased_span_end = 0;
+    let mut index = 0;
+    while index < ENTRIES_PER_PAGE {
+        let entry = Entry {
+            page,
+            index,
+            raw: &raw[ENTRY_OFFSET + index * ITEM_SIZE..ENTRY_OFFSET + (index + 1) * ITEM_SIZE],
+        };
+
+        match entry_state(raw, index) {
+            ENTRY_STATE_ILLEGAL => problems.push(entry.problem(ProblemKind::IllegalEntryState)),
+            ENTRY_STATE_EMPTY if entry.raw.iter().any(|&b| b != 0xFF) => {
+                problems.push(entry.problem(ProblemKind::EmptyEntryNotErased))
+            }
+            ENTRY_STATE_WRITTEN if index < erased_span_end => problems.push(entry.problem(ProblemKind::OrphanedEntry)),
+            ENTRY_STATE_WRITTEN => {
+                // on a span error the following entries can't be interpreted reliably
+                let span = validate_entry(&entry, raw, problems).unwrap_or(1);
+                entries.push(entry);
+                index += span;
+                continue;
+            }
+            ENTRY_STATE_ERASED => erased_span_end = erased_span_end.max(index + erased_span(&entry)),
+            _ => {}
+        }
+
+        index += 1;
+    }
+}
+
+/// Returns the span of an erased entry, or 1 if it can't be trusted.
+///
+/// Erasing an item only changes the state of its entries, so the header still holds the span.
+fn erased_span(entry: &Entry) -> usize {
+    let span = entry.raw[2] as usize;
+    let variable_length = [ItemType::Sized, ItemType::Blob, ItemType::BlobData]
+        .iter()
+        .any(|type_| *type_ as u8 == entry.type_());
+    let crc_ok = crc32_entry(entry.raw.try_into().unwrap()) == u32::from_le_bytes(entry.raw[4..8].try_into().unwrap());
+
+    if crc_ok && variable_length && span > 0 && entry.index + span <= ENTRIES_PER_PAGE {
+        span
+    } else {
+        1
+    }
+}
+
+/// Returns the span of the entry if it is valid.
+fn validate_entry(entry: &Entry, page: &[u8], problems: &mut Vec<Problem>) -> Option<usize> {
+    if crc32_entry(entry.raw.try_into().unwrap()) != u32::from_le_bytes(entry.raw[4..8].try_into().unwrap()) {
+        problems.push(entry.problem(ProblemKind::EntryCrcMismatch));
+        return None;
+    }
+
+    let Some(type_) = ItemType::from_repr(entry.type_()).filter(|type_| *type_ != ItemType::Any) else {
+        problems.push(entry.problem(ProblemKind::InvalidItemType(entry.type_())));
+        return None;
+    };
+
+    let span = entry.raw[2];
+    let variable_length = matches!(type_, ItemType::Sized | ItemType::Blob | ItemType::BlobData);
+    if span == 0 || entry.index + span as usize > ENTRIES_PER_PAGE || (!variable_length && span != 1) {
+        problems.push(entry.problem(ProblemKind::InvalidSpan(span)));
+        return None;
+    }
+    let span = span as usize;
+
+    if (entry.index + 1..entry.index + span).any(|index| entry_state(page, index) != ENTRY_STATE_WRITTEN) {
+        problems.push(entry.problem(ProblemKind::SpanNotWritten));
+    }
+
+    if variable_length {
+        let size = u16::from_le_bytes(entry.data()[0..2].try_into().unwrap());
+        if size as usize > (span - 1) * ITEM_SIZE {
+            problems.push(entry.problem(ProblemKind::DataSizeOutOfBounds(size)));
+        } else {
+            let start = ENTRY_OFFSET + (entry.index + 1) * ITEM_SIZE;
+            let data_crc = u32::from_le_bytes(entry.data()[4..8].try_into().unwrap());
+            if data_crc != crc32(&page[start..start + size as usize]) {
+                problems.push(entry.problem(ProblemKind::DataCrcMismatch));
+            }
+        }
+    }
+
+    Some(span)
+}
+
+fn validate_namespaces(entries: &[Entry], problems: &mut Vec<Problem>) {
+    let mut names = HashMap::new();
+    let mut indices = HashMap::new();
+
+    for entry in entries.iter().filter(|entry| entry.namespace_index() == 0) {
+        let name = Key::from_slice(&entry.key()[..esp_nvs::MAX_KEY_LENGTH]).to_string();
+        let index = entry.data()[0];
+        if indices.insert(index, name.clone()).is_some() {
+            problems.push(entry.problem(ProblemKind::DuplicateNamespaceIndex(index)));
+        }
+        if names.insert(name.clone(), index).is_some() {
+            problems.push(entry.problem(ProblemKind::DuplicateNamespace(name)));
+        }
+    }
+
+    for entry in entries.iter().filter(|entry| entry.namespace_index() != 0) {
+        if !indices.contains_key(&entry.namespace_index()) {
+            problems.push(entry.problem(ProblemKind::UnknownNamespace(entry.namespace_index())));
+        }
+    }
+}
+
+fn validate_blobs(entries: &[Entry], problems: &mut Vec<Problem>) {
+    for index in entries
+        .iter()
+        .filter(|entry| entry.type_() == ItemType::BlobIndex as u8 && entry.chunk_index() == CHUNK_ANY)
+    {
+        let size = u32::from_le_bytes(index.data()[0..4].try_into().unwrap());
+        let chunk_count = index.data()[4];
+        let chunk_start = index.data()[5];
+
+        let chunks: Vec<&Entry> = entries
+            .iter()
+            .filter(|entry| {
+                entry.type_() == ItemType::BlobData as u8
+                    && entry.namespace_index() == index.namespace_index()
+                    && entry.key() == index.key()
+                    && (chunk_start..chunk_start.saturating_add(chunk_count)).contains(&entry.chunk_index())
+            })
+            .collect();
+
+        let key = Key::from_slice(&index.key()[..esp_nvs::MAX_KEY_LENGTH]).to_string();
+        if chunks.len() != chunk_count as usize {
+            problems.push(index.problem(ProblemKind::BlobChunkCountMismatch {
+                key,
+                expected: chunk_count,
+                found: chunks.len(),
+            }));
+            continue;
+        }
+
+        let found: usize = chunks
+            .iter()
+            .map(|chunk| u16::from_le_bytes(chunk.data()[0..2].try_into().unwrap()) as usize)
+            .sum();
+        if found != size as usize {
+            problems.push(index.problem(ProblemKind::BlobSizeMismatch {
+                key,
+                expected: size,
+                found,
+            }));
+        }
+    }
+}
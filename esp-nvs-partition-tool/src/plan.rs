@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+
+use esp_nvs::{
+    ENTRIES_PER_PAGE,
+    FLASH_SECTOR_SIZE,
+    ITEM_SIZE,
+    ItemType,
+    PageState,
+};
+
+use crate::partition::validator::{
+    ENTRY_OFFSET,
+    ENTRY_STATE_EMPTY,
+    ENTRY_STATE_WRITTEN,
+    entry_state,
+};
+
+/// Where the entries of a partition end up, see [`crate::NvsPartition::plan`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackingPlan {
+    /// The size of the partition in bytes.
+    pub size: usize,
+    /// Every page of the partition, in the order of their addresses.
+    pub pages: Vec<PageLayout>,
+}
+
+/// A page of a [`PackingPlan`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageLayout {
+    /// The index of the page, its address is `index * FLASH_SECTOR_SIZE`.
+    pub index: usize,
+    /// The state the generator left the page in, `None` for unknown states.
+    pub state: Option<PageState>,
+    /// The entries written to the page, in the order of their position.
+    pub entries: Vec<PlacedEntry>,
+    /// The number of entries still empty.
+    pub free_entries: usize,
+}
+
+/// An entry of a [`PageLayout`], together with the entries holding its data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlacedEntry {
+    /// The namespace of the entry, empty for the entry defining the namespace `key`.
+    pub namespace: String,
+    /// The key of the entry.
+    pub key: String,
+    /// The type of the entry, a blob is split into a [`ItemType::BlobIndex`] and one
+    /// [`ItemType::BlobData`] entry per chunk.
+    pub item_type: ItemType,
+    /// The chunk index, `0xFF` for everything but blob data.
+    pub chunk_index: u8,
+    /// The position of the entry within the page.
+    pub position: usize,
+    /// The number of entries used, including the ones holding the data.
+    pub span: usize,
+}
+
+impl PackingPlan {
+    /// The pages holding at least one entry.
+    pub fn used_pages(&self) -> impl Iterator<Item = &PageLayout> {
+        self.pages.iter().filter(|page| !page.entries.is_empty())
+    }
+
+    /// The entries used by every namespace, including the entry defining it.
+    pub fn namespace_entries(&self) -> BTreeMap<String, usize> {
+        let mut result = BTreeMap::new();
+        for entry in self.pages.iter().flat_map(|page| &page.entries) {
+            let namespace = match entry.namespace.as_str() {
+                "" => &entry.key,
+                namespace => namespace,
+            };
+            *result.entry(namespace.to_string()).or_default() += entry.span;
+        }
+        result
+    }
+}
+
+/// Record the placement of the written entries of a partition binary as written by the
+/// generator.
+pub(crate) fn plan(data: &[u8]) -> PackingPlan {
+    let pages = data.chunks_exact(FLASH_SECTOR_SIZE);
+
+    // namespaces are defined before they are used, but not necessarily on the same page
+    let mut names = BTreeMap::new();
+    for page in pages.clone() {
+        for_each_written(page, |entry, _, _| {
+            if entry[0] == 0 {
+                names.insert(entry[24], key(entry));
+            }
+        });
+    }
+
+    let pages = pages
+        .enumerate()
+        .map(|(index, page)| {
+            let state = PageState::from_repr(u32::from_le_bytes(page[0..4].try_into().unwrap()));
+            let mut entries = vec![];
+            if matches!(state, Some(PageState::Active | PageState::Full)) {
+                for_each_written(page, |entry, position, span| {
+                    entries.push(PlacedEntry {
+                        namespace: match entry[0] {
+                            0 => String::new(),
+                            namespace => names
+                                .get(&namespace)
+                                .cloned()
+                                .unwrap_or_else(|| format!("#{namespace}")),
+                        },
+                        key: key(entry),
+                        item_type: ItemType::from_repr(entry[1]).unwrap_or(ItemType::Any),
+                        chunk_index: entry[3],
+                        position,
+                        span,
+                    });
+                });
+            }
+            PageLayout {
+                index,
+                state,
+                entries,
+                free_entries: (0..ENTRIES_PER_PAGE)
+                    .filter(|&index| entry_state(page, index) == ENTRY_STATE_EMPTY)
+                    .count(),
+            }
+        })
+        .collect();
+
+    PackingPlan {
+        size: data.len(),
+        pages,
+    }
+}
+
+/// Call `f` with every written entry of `page`, its position and its span.
+fn for_each_written(page: &[u8], mut f: impl FnMut(&[u8], usize, usize)) {
+    let mut index = 0;
+    while index < ENTRIES_PER_PAGE {
+        if entry_state(page, index) != ENTRY_STATE_WRITTEN {
+            index += 1;
+            continue;
+        }
+
+        let entry = &page[ENTRY_OFFSET + index * ITEM_SIZE..ENTRY_OFFSET + (index + 1) * ITEM_SIZE];
+        let span = (entry[2] as usize).clamp(1, ENTRIES_PER_PAGE - index);
+        f(entry, index, span);
+        index += span;
+    }
+}
+
+fn key(entry: &[u8]) -> String {
+    let key = &entry[8..24];
+    let key = &key[..key.iter().position(|&b| b == 0).unwrap_or(key.len())];
+    String::from_utf8_lossy(key).into_owned()
+}
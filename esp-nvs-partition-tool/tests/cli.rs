@@ -0,0 +1,157 @@
+#![cfg(feature = "cli")]
+
+use std::io::Write;
+use std::process::{
+    Command,
+    Stdio,
+};
+
+use esp_nvs_partition_tool::NvsPartition;
+
+fn run(args: &[&str], stdin: &[u8]) -> Vec<u8> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_esp-nvs-partition-tool"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    output.stdout
+}
+
+#[test]
+fn test_stdin_stdout() {
+    let csv = std::fs::read_to_string("tests/assets/roundtrip_basic.csv").unwrap();
+    let expected = NvsPartition::try_from_str(&csv)
+        .unwrap()
+        .generate_partition(16384)
+        .unwrap();
+
+    let binary = run(&["generate", "-", "-", "--size", "16384"], csv.as_bytes());
+    assert_eq!(binary, expected);
+
+    let parsed = run(&["parse", "-", "-"], &binary);
+    let expected = NvsPartition::try_from_bytes(expected).unwrap().to_csv().unwrap();
+    assert_eq!(String::from_utf8(parsed).unwrap(), expected);
+}
+
+#[test]
+fn test_parse_json_quiet() {
+    let csv = std::fs::read_to_string("tests/assets/roundtrip_basic.csv").unwrap();
+    let binary = NvsPartition::try_from_str(&csv)
+        .unwrap()
+        .generate_partition(16384)
+        .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_esp-nvs-partition-tool"))
+        .args(["parse", "-", "-", "--format", "json", "--quiet"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            child.stdin.take().unwrap().write_all(&binary)?;
+            child.wait_with_output()
+        })
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let json = String::from_utf8(output.stdout).unwrap();
+    let expected = NvsPartition::try_from_bytes(binary).unwrap();
+    assert_eq!(NvsPartition::from_json(&json).unwrap().entries, expected.entries);
+}
+
+#[test]
+fn test_lookup() {
+    let output = run(
+        &["lookup", "tests/assets/roundtrip_basic.csv", "storage", "int8_test"],
+        &[],
+    );
+    assert_eq!(String::from_utf8(output).unwrap(), "100\n");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_esp-nvs-partition-tool"))
+        .args(["lookup", "tests/assets/roundtrip_basic.csv", "storage", "missing"])
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(1));
+}
+
+#[test]
+fn test_generate_manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = dir.path().join("manifest.json");
+    let csv = std::fs::read_to_string("tests/assets/roundtrip_basic.csv").unwrap();
+
+    run(
+        &[
+            "generate",
+            "-",
+            "-",
+            "--size",
+            "16384",
+            "--manifest",
+            manifest.to_str().unwrap(),
+        ],
+        csv.as_bytes(),
+    );
+
+    let expected = NvsPartition::try_from_str(&csv).unwrap().manifest(16384).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(manifest).unwrap(),
+        expected.to_json().unwrap() + "\n"
+    );
+}
+
+#[test]
+fn test_keys() {
+    let expected = std::fs::read("tests/assets/test_nvs_keys.bin").unwrap();
+    let eky: String = (0..32u8).map(|b| format!("{b:02x}")).collect();
+    let tky: String = (32..64u8).map(|b| format!("{b:02x}")).collect();
+
+    let generated = run(&["keys", "generate", "-", "--data-key", &eky, "--tweak-key", &tky], &[]);
+    assert!(generated == expected);
+
+    let printed = run(&["keys", "parse", "-"], &expected);
+    assert_eq!(String::from_utf8(printed).unwrap(), format!("eky: {eky}\ntky: {tky}\n"));
+
+    // random keys differ between runs, but are read back like any other keys
+    let first = run(&["keys", "generate", "-"], &[]);
+    let second = run(&["keys", "generate", "-"], &[]);
+    assert_ne!(first, second);
+    let printed = String::from_utf8(run(&["keys", "parse", "-"], &first)).unwrap();
+    assert_eq!(
+        printed,
+        format!(
+            "eky: {}\ntky: {}\n",
+            hex::encode(&first[..32]),
+            hex::encode(&first[32..64])
+        )
+    );
+}
+
+#[test]
+fn test_fingerprint() {
+    let dir = tempfile::tempdir().unwrap();
+    let binary = dir.path().join("nvs.bin");
+    run(
+        &[
+            "generate",
+            "tests/assets/roundtrip_basic.csv",
+            binary.to_str().unwrap(),
+            "--size",
+            "16384",
+        ],
+        &[],
+    );
+
+    let from_csv = run(&["fingerprint", "tests/assets/roundtrip_basic.csv"], &[]);
+    let from_binary = run(&["fingerprint", binary.to_str().unwrap()], &[]);
+    assert_eq!(from_csv, from_binary);
+    assert_eq!(from_csv.len(), 17, "{}", String::from_utf8_lossy(&from_csv));
+}
@@ -0,0 +1,72 @@
+use esp_nvs_partition_tool::{
+    ChangedEntry,
+    DataValue,
+    EntryContent,
+    NvsEntry,
+    NvsPartition,
+};
+
+mod common;
+
+fn entry(namespace: &str, key: &str, value: DataValue) -> NvsEntry {
+    NvsEntry::new_data(namespace.to_string(), key.to_string(), value)
+}
+
+#[test]
+fn test_diff() {
+    let old = NvsPartition {
+        entries: vec![
+            entry("storage", "kept", DataValue::U8(1)),
+            entry("storage", "changed", DataValue::U8(1)),
+            entry("storage", "removed", DataValue::U8(1)),
+            entry("other", "kept", DataValue::U8(1)),
+        ],
+        empty_namespaces: vec![],
+    };
+    let new = NvsPartition {
+        entries: vec![
+            entry("other", "kept", DataValue::U8(1)),
+            entry("other", "added", DataValue::String("hello".to_string())),
+            entry("storage", "changed", DataValue::U16(1)),
+            entry("storage", "kept", DataValue::U8(1)),
+        ],
+        empty_namespaces: vec![],
+    };
+
+    let diff = old.diff(&new);
+    assert_eq!(
+        diff.added,
+        vec![entry("other", "added", DataValue::String("hello".to_string()))]
+    );
+    assert_eq!(diff.removed, vec![entry("storage", "removed", DataValue::U8(1))]);
+    assert_eq!(
+        diff.changed,
+        vec![ChangedEntry {
+            namespace: "storage".to_string(),
+            key: "changed".to_string(),
+            old: EntryContent::Data(DataValue::U8(1)),
+            new: EntryContent::Data(DataValue::U16(1)),
+        }]
+    );
+    assert_eq!(
+        diff.to_string(),
+        "- storage/removed: u8 1\n+ other/added: string hello\n~ storage/changed: u8 1 -> u16 1\n"
+    );
+
+    assert!(new.diff(&new).is_empty());
+}
+
+#[test]
+fn test_diff_binaries() {
+    let partition = common::read_csv_file("tests/assets/multiple_namespaces.csv");
+    let binary = partition.generate_partition(0x6000).unwrap();
+    let reference = NvsPartition::try_from_bytes(binary.clone()).unwrap();
+    let mut parsed = NvsPartition::try_from_bytes(binary).unwrap();
+    assert!(reference.diff(&parsed).is_empty());
+
+    parsed.entries[0].set_data(DataValue::I8(-1));
+    let diff = reference.diff(&parsed);
+    assert!(diff.added.is_empty());
+    assert!(diff.removed.is_empty());
+    assert_eq!(diff.changed.len(), 1);
+}
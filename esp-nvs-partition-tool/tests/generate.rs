@@ -1,11 +1,19 @@
+use std::fs;
 use std::path::PathBuf;
 
 use esp_nvs_partition_tool::{
     DataValue,
+    DuplicatePolicy,
     EntryContent,
+    Error,
     FileEncoding,
+    GenerateOptions,
+    NamespaceManifest,
     NvsEntry,
     NvsPartition,
+    PlacedEntry,
+    TrailingPage,
+    encryption,
 };
 
 mod common;
@@ -23,7 +31,7 @@ fn test_csv_to_binary() {
 
 #[test]
 fn test_generate_from_api() {
-    let mut partition = NvsPartition { entries: vec![] };
+    let mut partition = NvsPartition::default();
 
     partition.entries.push(NvsEntry::new_data(
         "config".to_string(),
@@ -43,6 +51,51 @@ fn test_generate_from_api() {
 
     let data = partition.generate_partition(8192).unwrap();
     assert_eq!(data.len(), 8192);
+
+    let built = NvsPartition::builder()
+        .namespace("config")
+        .u8("version", 1)
+        .u32("count", 12345)
+        .string("name", "Test Device")
+        .build()
+        .unwrap();
+    assert_eq!(built, partition);
+}
+
+#[test]
+fn test_builder() {
+    let partition = NvsPartition::builder()
+        .namespace("storage")
+        .i64("offset", -1)
+        .blob("mac", [0xa0, 0xb1, 0xc2])
+        .namespace("etc")
+        .file("cert", FileEncoding::Binary, "cert.der")
+        .build()
+        .unwrap();
+    assert_eq!(
+        partition.entries,
+        [
+            NvsEntry::new_data("storage".into(), "offset".into(), DataValue::I64(-1)),
+            NvsEntry::new_data(
+                "storage".into(),
+                "mac".into(),
+                DataValue::Binary(vec![0xa0, 0xb1, 0xc2])
+            ),
+            NvsEntry::new_file("etc".into(), "cert".into(), FileEncoding::Binary, "cert.der".into()),
+        ]
+    );
+
+    assert!(matches!(
+        NvsPartition::builder().u8("flag", 1).build(),
+        Err(Error::MissingNamespace)
+    ));
+    assert!(matches!(
+        NvsPartition::builder()
+            .namespace("storage")
+            .u8("a_key_that_is_too_long", 1)
+            .build(),
+        Err(Error::InvalidKey(_))
+    ));
 }
 
 #[test]
@@ -64,7 +117,7 @@ fn test_large_string() {
 
 #[test]
 fn test_invalid_partition_size() {
-    let mut partition = NvsPartition { entries: vec![] };
+    let mut partition = NvsPartition::default();
     partition.entries.push(NvsEntry::new_data(
         "test".to_string(),
         "dummy".to_string(),
@@ -75,6 +128,51 @@ fn test_invalid_partition_size() {
     assert!(result.is_err());
 }
 
+#[test]
+fn test_single_page() {
+    let partition = NvsPartition::builder()
+        .namespace("config")
+        .u8("version", 1)
+        .string("name", "tiny")
+        .build()
+        .unwrap();
+    let state = |data: &[u8]| u32::from_le_bytes(data[0..4].try_into().unwrap());
+
+    let data = partition.generate_partition(4096).unwrap();
+    assert_eq!(data.len(), 4096);
+    assert_eq!(state(&data), esp_nvs::PageState::Active as u32);
+    let mut parsed = NvsPartition::try_from_bytes(data.clone()).unwrap();
+    parsed.clear_namespace_indices();
+    assert_eq!(parsed, partition);
+
+    let mut nvs = esp_nvs::Nvs::new(0, 4096, esp_nvs::mem_flash::MemFlash::from_bytes(data)).unwrap();
+    assert_eq!(
+        nvs.get::<String>(&esp_nvs::Key::from_str("config"), &esp_nvs::Key::from_str("name")),
+        Ok("tiny".to_string())
+    );
+
+    // the namespace entry and a string filling the other 125 entries use up the page exactly
+    let full = NvsPartition::builder()
+        .namespace("config")
+        .string("name", &"x".repeat(124 * 32 - 1))
+        .build()
+        .unwrap();
+    let data = full.generate_partition(4096).unwrap();
+    assert_eq!(state(&data), esp_nvs::PageState::Full as u32);
+    assert_eq!(NvsPartition::try_from_bytes(data).unwrap().entries.len(), 1);
+
+    let too_large = NvsPartition::builder()
+        .namespace("config")
+        .string("name", &"x".repeat(124 * 32 - 1))
+        .u8("version", 1)
+        .build()
+        .unwrap();
+    assert!(matches!(
+        too_large.generate_partition(4096),
+        Err(Error::NvsError(esp_nvs::error::Error::FlashFull))
+    ));
+}
+
 #[test]
 fn test_entry_edit_methods() {
     let mut entry = NvsEntry::new_data("ns".into(), "key".into(), DataValue::U8(1));
@@ -93,6 +191,7 @@ fn test_entry_edit_methods() {
 fn test_find_mut_and_generate() {
     let mut partition = NvsPartition {
         entries: vec![NvsEntry::new_data("config".into(), "value".into(), DataValue::U32(1))],
+        empty_namespaces: vec![],
     };
 
     partition.find_mut("value").unwrap().set_data(DataValue::U32(42));
@@ -104,3 +203,483 @@ fn test_find_mut_and_generate() {
         EntryContent::Data(DataValue::U32(42))
     ));
 }
+
+#[test]
+fn test_get_by_namespace_and_key() {
+    let mut partition = common::read_csv_file("tests/assets/multiple_namespaces.csv");
+
+    assert_eq!(
+        partition.get("etc", "test_4").unwrap().content,
+        EntryContent::Data(DataValue::I32(4))
+    );
+    assert!(partition.get("etc", "uniq_string_key").is_none());
+    assert!(partition.get("missing", "test_4").is_none());
+
+    assert_eq!(partition.lookup("etc", "test_4").unwrap().key, "test_4");
+    assert!(matches!(
+        partition.lookup("etc", "uniq_string_key"),
+        Err(Error::KeyNotFound { namespace, key }) if namespace == "etc" && key == "uniq_string_key"
+    ));
+    assert!(matches!(
+        partition.lookup("missing", "test_4"),
+        Err(Error::NamespaceNotFound(name)) if name == "missing"
+    ));
+
+    partition
+        .get_mut("abcd", "test_4")
+        .unwrap()
+        .set_data(DataValue::I32(40));
+    assert_eq!(
+        partition.get("abcd", "test_4").unwrap().content,
+        EntryContent::Data(DataValue::I32(40))
+    );
+    assert_eq!(
+        partition.get("storage", "test_4").unwrap().content,
+        EntryContent::Data(DataValue::I32(4))
+    );
+}
+
+#[test]
+fn test_group_by_namespace() {
+    let mut partition = NvsPartition::builder()
+        .namespace("second")
+        .u8("a", 1)
+        .namespace("first")
+        .u8("b", 2)
+        .namespace("second")
+        .u8("c", 3)
+        .build()
+        .unwrap();
+    partition.empty_namespaces.push("empty".to_string());
+
+    assert_eq!(partition.namespaces().collect::<Vec<_>>(), ["second", "first", "empty"]);
+
+    let groups = partition.by_namespace();
+    let keys: Vec<(&str, Vec<&str>)> = groups
+        .iter()
+        .map(|(namespace, entries)| (*namespace, entries.iter().map(|e| e.key.as_str()).collect()))
+        .collect();
+    assert_eq!(
+        keys,
+        [("empty", vec![]), ("first", vec!["b"]), ("second", vec!["a", "c"])]
+    );
+}
+
+#[test]
+fn test_set_and_remove() {
+    let mut partition = common::read_csv_file("tests/assets/roundtrip_basic.csv");
+    let len = partition.entries.len();
+
+    assert_eq!(
+        partition.set("storage", "int8_test", DataValue::I8(-1)),
+        Some(EntryContent::Data(DataValue::I8(100)))
+    );
+    assert_eq!(partition.entries.len(), len);
+    assert_eq!(partition.entries[2].content, EntryContent::Data(DataValue::I8(-1)));
+
+    partition.set("other", "flag", DataValue::U8(1));
+    // grouped with the other entries of the namespace, not appended
+    assert_eq!(partition.set("storage", "new", DataValue::U16(2)), None);
+    let keys: Vec<&str> = partition.entries.iter().map(|e| e.key.as_str()).collect();
+    assert_eq!(keys, ["int32_test", "uint32_test", "int8_test", "new", "flag"]);
+
+    assert_eq!(
+        partition.remove("storage", "new").unwrap().content,
+        EntryContent::Data(DataValue::U16(2))
+    );
+    assert!(partition.remove("storage", "new").is_none());
+    assert!(partition.remove("storage", "flag").is_none());
+    assert_eq!(partition.entries.len(), len + 1);
+}
+
+#[test]
+fn test_dedup() {
+    let partition = NvsPartition::try_from_str(
+        "key,type,encoding,value\n\
+         storage,namespace,,\n\
+         a,data,u8,1\n\
+         b,data,u8,2\n\
+         a,data,u8,3\n\
+         other,namespace,,\n\
+         a,data,u8,4\n\
+         storage,namespace,,\n\
+         b,data,u8,5\n",
+    )
+    .unwrap();
+    let values = |partition: &NvsPartition| -> Vec<(String, String, EntryContent)> {
+        partition
+            .entries
+            .iter()
+            .map(|e| (e.namespace.clone(), e.key.clone(), e.content.clone()))
+            .collect()
+    };
+    let u8 = |v| EntryContent::Data(DataValue::U8(v));
+
+    let mut last_wins = partition.clone();
+    last_wins.dedup(DuplicatePolicy::LastWins).unwrap();
+    assert_eq!(
+        values(&last_wins),
+        [
+            ("storage".into(), "a".into(), u8(3)),
+            ("storage".into(), "b".into(), u8(5)),
+            ("other".into(), "a".into(), u8(4)),
+        ]
+    );
+
+    let mut first_wins = partition.clone();
+    first_wins.dedup(DuplicatePolicy::FirstWins).unwrap();
+    assert_eq!(
+        values(&first_wins),
+        [
+            ("storage".into(), "a".into(), u8(1)),
+            ("storage".into(), "b".into(), u8(2)),
+            ("other".into(), "a".into(), u8(4)),
+        ]
+    );
+
+    let mut error = partition.clone();
+    match error.dedup(DuplicatePolicy::Error) {
+        Err(Error::DuplicateEntries(duplicates)) => assert_eq!(duplicates, ["storage/a", "storage/b"]),
+        result => panic!("unexpected result {result:?}"),
+    }
+    assert_eq!(error, partition);
+}
+
+#[test]
+fn test_min_size() {
+    let partition = common::read_csv_file("tests/assets/multiple_namespaces.csv");
+    let size = partition.min_size().unwrap();
+
+    assert!(size.is_multiple_of(4096));
+    assert!(partition.generate_partition(size).is_ok());
+    assert!(partition.generate_partition(size - 4096).is_err());
+
+    let mut partition = NvsPartition::default();
+    partition.entries.push(NvsEntry::new_data(
+        "config".to_string(),
+        "version".to_string(),
+        DataValue::U8(1),
+    ));
+    // a single page image doesn't need a page kept free
+    assert_eq!(partition.min_size().unwrap(), 4096);
+    assert_eq!(partition.generate_partition(4096).unwrap().len(), 4096);
+
+    // the largest blob is limited by the number of pages, not only by the entries it needs
+    partition.entries.push(NvsEntry::new_data(
+        "config".to_string(),
+        "blob".to_string(),
+        DataValue::Binary(vec![0xAB; 10_000]),
+    ));
+    let size = partition.min_size().unwrap();
+    assert_eq!(size, 4 * 4096);
+    assert!(partition.generate_partition(size).is_ok());
+    assert!(partition.generate_partition(size - 4096).is_err());
+}
+
+#[test]
+fn test_trailing_page() {
+    let partition = common::read_csv_file("tests/assets/roundtrip_basic.csv");
+    let state = |data: &[u8], page: usize| u32::from_le_bytes(data[page * 4096..page * 4096 + 4].try_into().unwrap());
+    let generate = |trailing_page| {
+        partition
+            .generate_partition_with_options(
+                3 * 4096,
+                &GenerateOptions {
+                    trailing_page,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+    };
+
+    let auto = generate(TrailingPage::Auto);
+    assert_eq!(auto, partition.generate_partition(3 * 4096).unwrap());
+    assert_eq!(state(&auto, 0), esp_nvs::PageState::Active as u32);
+    assert_eq!(generate(TrailingPage::Active), auto);
+
+    let full = generate(TrailingPage::Full);
+    assert_eq!(state(&full, 0), esp_nvs::PageState::Full as u32);
+    assert_eq!(full[4..], auto[4..]);
+
+    // the device continues on a new page
+    let flash = esp_nvs::mem_flash::MemFlash::from_bytes(full);
+    let mut nvs = esp_nvs::Nvs::new(0, 3 * 4096, flash).unwrap();
+    nvs.set(&esp_nvs::Key::from_str("storage"), &esp_nvs::Key::from_str("new"), 1u8)
+        .unwrap();
+    let data = nvs.into_inner().into_inner();
+    assert_eq!(state(&data, 0), esp_nvs::PageState::Full as u32);
+    assert_eq!(state(&data, 1), esp_nvs::PageState::Active as u32);
+}
+
+#[test]
+fn test_namespace_indices() {
+    let partition = NvsPartition::builder()
+        .namespace("first")
+        .u8("a", 1)
+        .namespace("second")
+        .u8("b", 2)
+        .namespace("third")
+        .u8("c", 3)
+        .build()
+        .unwrap();
+    let generate = |indices: &[(&str, u8)]| {
+        let options = GenerateOptions {
+            namespace_indices: indices.iter().map(|(ns, idx)| (ns.to_string(), *idx)).collect(),
+            ..Default::default()
+        };
+        partition.generate_partition_with_options(0x3000, &options)
+    };
+    // the namespace entries are the 1st, 3rd and 5th entry of the first page
+    let index = |data: &[u8], entry: usize| data[64 + 32 * entry + 24];
+
+    let data = generate(&[]).unwrap();
+    assert_eq!(data, partition.generate_partition(0x3000).unwrap());
+    assert_eq!([index(&data, 0), index(&data, 2), index(&data, 4)], [1, 2, 3]);
+
+    // unmapped namespaces skip the fixed indices
+    let data = generate(&[("second", 1), ("unused", 3)]).unwrap();
+    assert_eq!([index(&data, 0), index(&data, 2), index(&data, 4)], [2, 1, 4]);
+    let mut parsed = NvsPartition::try_from_bytes(data.clone()).unwrap();
+    let indices: Vec<_> = parsed.entries.iter().map(|entry| entry.namespace_index).collect();
+    assert_eq!(indices, [Some(2), Some(1), Some(4)]);
+
+    // the recorded indices are reused, unless the options fix another one
+    assert_eq!(parsed.generate_partition(0x3000).unwrap(), data);
+    let options = GenerateOptions {
+        namespace_indices: [("first".to_string(), 7)].into(),
+        ..Default::default()
+    };
+    let data = parsed.generate_partition_with_options(0x3000, &options).unwrap();
+    assert_eq!([index(&data, 0), index(&data, 2), index(&data, 4)], [7, 1, 4]);
+
+    parsed.clear_namespace_indices();
+    assert_eq!(parsed, partition);
+    parsed.entries[0].namespace_index = Some(5);
+    parsed.entries.push(parsed.entries[0].clone());
+    parsed.entries[3].namespace_index = Some(6);
+    assert!(matches!(
+        parsed.generate_partition(0x3000),
+        Err(Error::InvalidNamespaceIndex(_))
+    ));
+
+    assert!(matches!(
+        generate(&[("first", 0)]),
+        Err(Error::InvalidNamespaceIndex(_))
+    ));
+    assert!(matches!(
+        generate(&[("first", 7), ("third", 7)]),
+        Err(Error::InvalidNamespaceIndex(msg)) if msg == "namespaces 'first' and 'third' both use index 7"
+    ));
+    assert!(generate(&[("first", 255), ("second", 254), ("third", 253)]).is_ok());
+}
+
+#[test]
+fn test_generate_to() {
+    let partition = common::read_csv_file("tests/assets/roundtrip_basic.csv");
+
+    let mut out = Vec::new();
+    partition.generate_to(&mut out, 0x3000).unwrap();
+    assert_eq!(out, partition.generate_partition(0x3000).unwrap());
+
+    let mut out = Vec::new();
+    assert!(matches!(
+        partition.generate_to(&mut out, 0x3001),
+        Err(Error::InvalidPartitionSize(0x3001))
+    ));
+    assert!(out.is_empty());
+}
+
+#[test]
+fn test_encrypt_matches_esp_idf() {
+    let keys = fs::read("tests/assets/test_nvs_keys.bin").unwrap();
+    let keys = encryption::load_keys(&keys).unwrap();
+    let plain = fs::read("tests/assets/test_nvs_data.bin").unwrap();
+    let encrypted = fs::read("tests/assets/test_nvs_data_encrypted.bin").unwrap();
+    // compared without assert_eq to keep the output of a failure readable
+    assert!(encryption::encrypt_partition(&plain, &keys).unwrap() == encrypted);
+}
+
+#[test]
+fn test_keys_partition_matches_esp_idf() {
+    let expected = fs::read("tests/assets/test_nvs_keys.bin").unwrap();
+    let eky: [u8; 32] = core::array::from_fn(|i| i as u8);
+    let tky: [u8; 32] = core::array::from_fn(|i| 32 + i as u8);
+    let keys = encryption::NvsEncryptionKeys::new(eky, tky);
+
+    let data = encryption::generate_keys_partition(&keys);
+    assert!(data == expected);
+
+    let parsed = encryption::NvsEncryptionKeys::from_partition(&data).unwrap();
+    assert_eq!((parsed.eky, parsed.tky), (eky, tky));
+}
+
+#[test]
+fn test_generate_encrypted() {
+    let partition = NvsPartition::builder()
+        .namespace("storage")
+        .u32("count", 42)
+        .string("name", "encrypted")
+        .build()
+        .unwrap();
+
+    let mut raw_keys = [0x11u8; 64];
+    raw_keys[32..].fill(0x22);
+    let keys = encryption::load_keys(&raw_keys).unwrap();
+    let binary = partition.generate_encrypted(0x3000, &keys).unwrap();
+    assert_ne!(binary, partition.generate_partition(0x3000).unwrap());
+    // empty entries stay erased
+    assert!(binary[0x1000 - 32..].iter().all(|&b| b == 0xFF));
+
+    let flash = esp_nvs::mem_flash::MemFlash::from_bytes(binary.clone());
+    let mut nvs = esp_nvs::Nvs::new_encrypted(0, flash.len(), flash, &keys).unwrap();
+    let namespace = esp_nvs::Key::from_str("storage");
+    assert_eq!(nvs.get::<u32>(&namespace, &esp_nvs::Key::from_str("count")), Ok(42));
+    assert_eq!(
+        nvs.get::<String>(&namespace, &esp_nvs::Key::from_str("name")),
+        Ok("encrypted".to_string())
+    );
+
+    assert!(encryption::load_keys(&raw_keys[..63]).is_err());
+    let mut parsed = NvsPartition::parse_encrypted(&binary, &keys).unwrap();
+    parsed.clear_namespace_indices();
+    assert_eq!(parsed, partition);
+}
+
+#[test]
+fn test_parse_encrypted() {
+    let keys = encryption::load_keys(&fs::read("tests/assets/test_nvs_keys.bin").unwrap()).unwrap();
+    let plain = fs::read("tests/assets/test_nvs_data.bin").unwrap();
+    let encrypted = fs::read("tests/assets/test_nvs_data_encrypted.bin").unwrap();
+
+    assert!(encryption::decrypt_partition(&encrypted, &keys).unwrap() == plain);
+    assert_eq!(
+        NvsPartition::parse_encrypted(&encrypted, &keys).unwrap(),
+        NvsPartition::try_from_bytes(plain).unwrap()
+    );
+
+    let wrong_keys = encryption::NvsEncryptionKeys::new([0x11; 32], [0x22; 32]);
+    assert!(matches!(
+        NvsPartition::parse_encrypted(&encrypted, &wrong_keys),
+        Err(Error::WrongEncryptionKeys)
+    ));
+}
+
+#[test]
+fn test_manifest() {
+    let partition = NvsPartition::try_from_str(
+        "key,type,encoding,value\n\
+         config,namespace,,\n\
+         flag,data,u8,1\n\
+         name,data,string,hello\n\
+         other,namespace,,\n\
+         blob,data,hex2bin,a0b1c2d3\n\
+         empty,namespace,,\n",
+    )
+    .unwrap();
+
+    let options = GenerateOptions {
+        namespace_indices: [("other".to_string(), 5)].into(),
+        ..Default::default()
+    };
+    let manifest = partition.manifest_with_options(0x3000, &options).unwrap();
+    let namespace = |name: &str, index, entries| NamespaceManifest {
+        name: name.to_string(),
+        index,
+        entries,
+    };
+    assert_eq!(
+        manifest.namespaces,
+        [
+            namespace("config", 1, 2),
+            namespace("empty", 2, 0),
+            namespace("other", 5, 1)
+        ]
+    );
+    assert_eq!(manifest.size, 0x3000);
+    // 3 namespaces, the u8, the string with its data and the blob index with its data chunk
+    assert_eq!(manifest.used_bytes, 9 * 32);
+    assert_eq!(manifest.pages_used, 1);
+    assert_eq!(manifest.partial_pages, [0]);
+    assert!(manifest.has_partial_pages());
+
+    // the manifest tells a different namespace order apart, the entries don't
+    let default = partition.manifest(0x3000).unwrap();
+    assert_eq!(default.namespaces[1], namespace("other", 2, 1));
+    assert_ne!(default, manifest);
+}
+
+#[test]
+fn test_plan() {
+    let long = "x".repeat(3000);
+    let partition = NvsPartition::try_from_str(format!(
+        "key,type,encoding,value\n\
+         config,namespace,,\n\
+         flag,data,u8,1\n\
+         first,data,string,{long}\n\
+         second,data,string,{long}\n"
+    ))
+    .unwrap();
+
+    let plan = partition.plan(0x3000).unwrap();
+    assert_eq!(plan.size, 0x3000);
+    assert_eq!(plan.pages.len(), 3);
+
+    let placed = |namespace: &str, key: &str, item_type, position, span| PlacedEntry {
+        namespace: namespace.to_string(),
+        key: key.to_string(),
+        item_type,
+        chunk_index: 0xFF,
+        position,
+        span,
+    };
+    // the second string doesn't fit onto the rest of the first page
+    assert_eq!(
+        plan.pages[0].entries,
+        [
+            placed("", "config", esp_nvs::ItemType::U8, 0, 1),
+            placed("config", "flag", esp_nvs::ItemType::U8, 1, 1),
+            placed("config", "first", esp_nvs::ItemType::Sized, 2, 95),
+        ]
+    );
+    assert_eq!(plan.pages[0].state, Some(esp_nvs::PageState::Full));
+    // the entries left over stay empty
+    assert_eq!(plan.pages[0].free_entries, 126 - 97);
+    assert_eq!(
+        plan.pages[1].entries,
+        [placed("config", "second", esp_nvs::ItemType::Sized, 0, 95)]
+    );
+    assert_eq!(plan.pages[1].free_entries, 126 - 95);
+    assert!(plan.pages[2].entries.is_empty());
+    assert_eq!(plan.used_pages().count(), 2);
+    assert_eq!(
+        plan.namespace_entries(),
+        [("config".to_string(), 1 + 1 + 95 + 95)].into()
+    );
+}
+
+#[test]
+fn test_invalid_keys_of_entries_created_in_code() {
+    for (namespace, key) in [
+        ("config", "a_key_that_is_too_long"),
+        ("a_namespace_that_is_too_long", "key"),
+        ("config", "name\0a"),
+        ("config", ""),
+    ] {
+        let mut partition = NvsPartition::default();
+        partition.set(namespace, key, DataValue::U8(1));
+        assert!(matches!(
+            partition.generate_partition(0x3000),
+            Err(Error::InvalidKey(_))
+        ));
+        assert!(matches!(partition.min_size(), Err(Error::InvalidKey(_))));
+    }
+
+    let mut partition = NvsPartition::default();
+    partition
+        .empty_namespaces
+        .push("a_namespace_that_is_too_long".to_string());
+    assert!(matches!(
+        partition.generate_partition(0x3000),
+        Err(Error::InvalidKey(_))
+    ));
+}
@@ -0,0 +1,38 @@
+use esp_nvs_partition_tool::{
+    emit_c_header,
+    emit_rust_static,
+};
+
+#[test]
+fn test_emit_c_header() {
+    let data: Vec<u8> = (0..14).collect();
+
+    let expected = "\
+#ifndef NVS_IMAGE_H
+#define NVS_IMAGE_H
+
+#include <stdint.h>
+
+#define NVS_IMAGE_SIZE 14
+
+static const uint8_t nvs_image[NVS_IMAGE_SIZE] = {
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b,
+    0x0c, 0x0d,
+};
+
+#endif /* NVS_IMAGE_H */
+";
+    assert_eq!(emit_c_header(&data, "nvs_image"), expected);
+}
+
+#[test]
+fn test_emit_rust_static() {
+    let expected = "\
+pub const NVS_IMAGE_SIZE: usize = 3;
+
+pub static NVS_IMAGE: [u8; NVS_IMAGE_SIZE] = [
+    0xfe, 0xff, 0x00,
+];
+";
+    assert_eq!(emit_rust_static(&[0xfe, 0xff, 0x00], "nvs_image"), expected);
+}
@@ -0,0 +1,92 @@
+#![cfg(feature = "serde")]
+
+use std::path::PathBuf;
+
+use esp_nvs_partition_tool::{
+    DataValue,
+    FileEncoding,
+    NvsEntry,
+    NvsPartition,
+};
+
+mod common;
+
+#[test]
+fn test_json_format() {
+    let partition = NvsPartition {
+        entries: vec![
+            NvsEntry::new_data("storage".to_string(), "count".to_string(), DataValue::U32(42)),
+            NvsEntry::new_data(
+                "storage".to_string(),
+                "blob".to_string(),
+                DataValue::Binary(vec![1, 2, 3]),
+            ),
+            NvsEntry::new_file(
+                "other".to_string(),
+                "config".to_string(),
+                FileEncoding::Binary,
+                PathBuf::from("config.bin"),
+            ),
+        ],
+        empty_namespaces: vec![],
+    };
+
+    let json = partition.to_json().unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!([
+            {"namespace": "storage", "key": "count", "type": "u32", "value": 42},
+            {"namespace": "storage", "key": "blob", "type": "base64", "value": "AQID"},
+            {"namespace": "other", "key": "config", "type": "file", "encoding": "binary", "value": "config.bin"},
+        ])
+    );
+
+    assert_eq!(NvsPartition::from_json(&json).unwrap(), partition);
+}
+
+#[test]
+fn test_json_roundtrip() {
+    let partition = common::read_csv_file("tests/assets/multiple_namespaces.csv");
+    let binary = partition.generate_partition(0x6000).unwrap();
+    let parsed = NvsPartition::try_from_bytes(binary).unwrap();
+
+    let json = parsed.to_json().unwrap();
+    assert_eq!(NvsPartition::from_json(&json).unwrap(), parsed);
+}
+
+#[test]
+fn test_json_validation() {
+    assert!(NvsPartition::from_json(r#"[{"namespace": "ns", "key": "k", "type": "u8", "value": 256}]"#).is_err());
+    assert!(NvsPartition::from_json(r#"[{"namespace": "ns", "key": "k", "type": "u8", "value": [1]}]"#).is_err());
+    assert!(
+        NvsPartition::from_json(r#"[{"namespace": "a_very_long_namespace", "key": "k", "type": "u8", "value": 1}]"#)
+            .is_err()
+    );
+    assert_eq!(
+        NvsPartition::from_json(r#"[{"namespace": "ns", "key": "k", "type": "u8", "value": "7"}]"#)
+            .unwrap()
+            .entries[0],
+        NvsEntry::new_data("ns".to_string(), "k".to_string(), DataValue::U8(7))
+    );
+}
+
+#[test]
+fn test_manifest_json() {
+    let partition = NvsPartition::try_from_str(
+        "key,type,encoding,value\n\
+         config,namespace,,\n\
+         flag,data,u8,1\n\
+         other,namespace,,\n\
+         name,data,string,hello\n",
+    )
+    .unwrap();
+
+    let manifest = partition.manifest(0x3000).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&manifest.to_json().unwrap()).unwrap();
+    assert_eq!(json["size"], 0x3000);
+    assert_eq!(json["namespaces"][1]["name"], "other");
+    assert_eq!(json["namespaces"][1]["index"], 2);
+    assert_eq!(json["namespaces"][1]["entries"], 1);
+    assert_eq!(json["partial_pages"], serde_json::json!([0]));
+}
@@ -0,0 +1,81 @@
+use esp_nvs_partition_tool::{
+    DataValue,
+    Error,
+    MergePolicy,
+    NvsEntry,
+    NvsPartition,
+};
+
+fn entry(namespace: &str, key: &str, value: u8) -> NvsEntry {
+    NvsEntry::new_data(namespace.to_string(), key.to_string(), DataValue::U8(value))
+}
+
+fn base() -> NvsPartition {
+    NvsPartition {
+        entries: vec![entry("storage", "a", 1), entry("storage", "b", 1)],
+        empty_namespaces: vec![],
+    }
+}
+
+fn overrides() -> NvsPartition {
+    NvsPartition {
+        entries: vec![
+            entry("device", "serial", 2),
+            entry("storage", "b", 2),
+            entry("storage", "c", 2),
+        ],
+        empty_namespaces: vec![],
+    }
+}
+
+#[test]
+fn test_merge_overwrite() {
+    let mut partition = base();
+    partition.merge(overrides(), MergePolicy::Overwrite).unwrap();
+    assert_eq!(
+        partition.entries,
+        vec![
+            entry("storage", "a", 1),
+            entry("storage", "b", 2),
+            entry("device", "serial", 2),
+            entry("storage", "c", 2),
+        ]
+    );
+}
+
+#[test]
+fn test_merge_keep_existing() {
+    let mut partition = base();
+    partition.merge(overrides(), MergePolicy::KeepExisting).unwrap();
+    assert_eq!(
+        partition.entries,
+        vec![
+            entry("storage", "a", 1),
+            entry("storage", "b", 1),
+            entry("device", "serial", 2),
+            entry("storage", "c", 2),
+        ]
+    );
+}
+
+#[test]
+fn test_merge_error() {
+    let mut partition = base();
+    let result = partition.merge(overrides(), MergePolicy::Error);
+    assert!(matches!(
+        result,
+        Err(Error::MergeConflict { namespace, key }) if namespace == "storage" && key == "b"
+    ));
+    assert_eq!(partition, base());
+
+    partition
+        .merge(
+            NvsPartition {
+                entries: vec![entry("device", "serial", 2)],
+                empty_namespaces: vec![],
+            },
+            MergePolicy::Error,
+        )
+        .unwrap();
+    assert_eq!(partition.entries.len(), 3);
+}
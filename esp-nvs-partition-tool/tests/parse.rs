@@ -1,9 +1,15 @@
 use std::fs;
 
 use esp_nvs_partition_tool::{
+    ArrayValue,
     DataValue,
     EntryContent,
+    Error,
+    NvsEntry,
     NvsPartition,
+    ParseWarning,
+    Problem,
+    ProblemKind,
 };
 
 mod common;
@@ -31,3 +37,287 @@ fn test_key_length_validation() {
     let result = NvsPartition::try_from_str(&content);
     assert!(result.is_err());
 }
+
+#[test]
+fn test_comments_and_blank_lines() {
+    let content = "# provisioning data\n\
+                   key,type,encoding,value\n\
+                   \n\
+                   storage,namespace,,\n\
+                   \x20 # indented comment, with \"quotes\n\
+                   \x20\x20\n\
+                   greeting,data,string,\"hello\n# not a comment\"\n\
+                   hash,data,string,#1\n";
+
+    let partition = NvsPartition::try_from_str(content).unwrap();
+    assert_eq!(partition.entries.len(), 2);
+    assert_eq!(
+        partition.entries[0].content,
+        EntryContent::Data(DataValue::String("hello\n# not a comment".to_string()))
+    );
+    assert_eq!(
+        partition.entries[1].content,
+        EntryContent::Data(DataValue::String("#1".to_string()))
+    );
+}
+
+#[test]
+fn test_inline_hex_encoding() {
+    let content = "key,type,encoding,value\n\
+                   storage,namespace,,\n\
+                   mac,data,hex,a0B1c2d3e4f5\n";
+
+    let partition = NvsPartition::try_from_str(content).unwrap();
+    assert_eq!(
+        partition.entries[0].content,
+        EntryContent::Data(DataValue::Binary(vec![0xa0, 0xb1, 0xc2, 0xd3, 0xe4, 0xf5]))
+    );
+
+    let csv = partition.to_csv().unwrap();
+    assert!(csv.contains("mac,data,hex,a0b1c2d3e4f5"), "{csv}");
+
+    let content = "key,type,encoding,value\n\
+                   storage,namespace,,\n\
+                   mac,data,hex,a0b\n";
+    assert!(NvsPartition::try_from_str(content).is_err());
+}
+
+#[test]
+fn test_hex_and_binary_integers() {
+    let content = "key,type,encoding,value\n\
+                   storage,namespace,,\n\
+                   reg,data,u32,0x1F4\n\
+                   flags,data,u8,0b1010\n\
+                   offset,data,i16,-0x10\n\
+                   count,data,u64,42\n";
+
+    let partition = NvsPartition::try_from_str(content).unwrap();
+    let values: Vec<_> = partition.entries.iter().map(|entry| entry.content.clone()).collect();
+    assert_eq!(
+        values,
+        vec![
+            EntryContent::Data(DataValue::U32(0x1F4)),
+            EntryContent::Data(DataValue::U8(0b1010)),
+            EntryContent::Data(DataValue::I16(-0x10)),
+            EntryContent::Data(DataValue::U64(42)),
+        ]
+    );
+
+    for value in ["0x100", "0b100000000", "0xZZ", "0x"] {
+        let content = format!("key,type,encoding,value\nstorage,namespace,,\nreg,data,u8,{value}\n");
+        match NvsPartition::try_from_str(&content) {
+            Err(Error::InvalidValue(message)) => assert!(message.contains(value), "{message}"),
+            result => panic!("unexpected result for {value}: {result:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_array_encodings() {
+    let content = "key,type,encoding,value\n\
+                   storage,namespace,,\n\
+                   table,data,u16[],\"0x0100,0x0200\"\n\
+                   offsets,data,i32[],-1 0 0b11\n\
+                   none,data,u64[],\n";
+
+    let partition = NvsPartition::try_from_str(content).unwrap();
+    let values: Vec<_> = partition.entries.iter().map(|entry| entry.content.clone()).collect();
+    assert_eq!(
+        values,
+        vec![
+            EntryContent::Data(DataValue::Array(ArrayValue::U16(vec![0x0100, 0x0200]))),
+            EntryContent::Data(DataValue::Array(ArrayValue::I32(vec![-1, 0, 3]))),
+            EntryContent::Data(DataValue::Array(ArrayValue::U64(vec![]))),
+        ]
+    );
+
+    let content = "key,type,encoding,value\nstorage,namespace,,\ntable,data,i8[],1 128\n";
+    match NvsPartition::try_from_str(content) {
+        Err(Error::InvalidValue(message)) => assert!(message.contains("128"), "{message}"),
+        result => panic!("unexpected result: {result:?}"),
+    }
+
+    let content = "key,type,encoding,value\nstorage,namespace,,\ntable,data,u8[],1 2\n";
+    assert!(matches!(
+        NvsPartition::try_from_str(content),
+        Err(Error::InvalidEncoding(_))
+    ));
+}
+
+#[test]
+fn test_whitespace_and_case_in_columns() {
+    let lowercase = "key,type,encoding,value\n\
+                     storage,namespace,,\n\
+                     count,data,u32,42\n\
+                     name,data,string, padded \n\
+                     blob,data,hex,a0b1\n";
+    let mixed = "key,type,encoding,value\n\
+                 storage,  NameSpace,,\n\
+                 count ,Data,U32 , 42\n\
+                 name,DATA,String , padded \n\
+                 blob,data, HEX,a0b1 \n";
+
+    let partition = NvsPartition::try_from_str(mixed).unwrap();
+    assert_eq!(partition, NvsPartition::try_from_str(lowercase).unwrap());
+    assert_eq!(
+        partition.entries[1].content,
+        EntryContent::Data(DataValue::String(" padded ".to_string()))
+    );
+}
+
+#[test]
+fn test_data_before_namespace() {
+    let content = "key,type,encoding,value\n\
+                   # provisioning data\n\
+                   \n\
+                   serial,data,string,abc\n\
+                   storage,namespace,,\n";
+
+    let err = NvsPartition::try_from_str(content).unwrap_err();
+    assert!(
+        matches!(&err, Error::DataBeforeNamespace { line: 4, key } if key == "serial"),
+        "{err:?}"
+    );
+    assert_eq!(
+        err.to_string(),
+        "line 4: entry 'serial' comes before the first namespace row"
+    );
+}
+
+/// Set the version byte of the first page and fix up the header CRC.
+fn set_page_version(data: &mut [u8], version: u8) {
+    data[8] = version;
+    let crc = esp_nvs_partition_tool::crc::crc32_page_header(data[..32].try_into().unwrap());
+    data[28..32].copy_from_slice(&crc.to_le_bytes());
+}
+
+#[test]
+fn test_page_versions() {
+    let partition = NvsPartition {
+        entries: vec![
+            NvsEntry::new_data("storage".to_string(), "count".to_string(), DataValue::U8(7)),
+            NvsEntry::new_data(
+                "storage".to_string(),
+                "blob".to_string(),
+                DataValue::LegacyBinary(vec![1, 2, 3]),
+            ),
+        ],
+        empty_namespaces: vec![],
+    };
+    let mut data = partition.generate_partition(8192).unwrap();
+
+    // version 1 as written by ESP-IDF before v4.0
+    set_page_version(&mut data, 0xFF);
+    let mut parsed = NvsPartition::try_from_bytes(data.clone()).unwrap();
+    parsed.clear_namespace_indices();
+    assert_eq!(parsed, partition);
+
+    set_page_version(&mut data, 0xFD);
+    assert!(matches!(
+        NvsPartition::try_from_bytes(data),
+        Err(Error::UnsupportedPageVersion { page: 0, version: 0xFD })
+    ));
+}
+
+#[test]
+fn test_bom_and_crlf() {
+    let plain = fs::read_to_string("tests/assets/roundtrip_basic.csv").unwrap();
+    let expected = NvsPartition::try_from_str(plain.as_str()).unwrap();
+
+    let windows = format!("\u{feff}{}", plain.replace('\n', "\r\n"));
+    assert_eq!(NvsPartition::try_from_str(windows.as_str()).unwrap(), expected);
+    assert_eq!(NvsPartition::try_from(windows.into_bytes()).unwrap(), expected);
+
+    let classic_mac = format!("# exported\r{}", plain.replace('\n', "\r"));
+    assert_eq!(NvsPartition::try_from_str(classic_mac).unwrap(), expected);
+}
+
+#[test]
+fn test_parse_filtered() {
+    let partition = NvsPartition::builder()
+        .namespace("storage")
+        .u8("a", 1)
+        .namespace("other")
+        .string("b", "skipped")
+        .namespace("storage")
+        .u16("c", 3)
+        .build()
+        .unwrap();
+    let mut data = partition.generate_partition(0x3000).unwrap();
+
+    let filtered = NvsPartition::parse_partition_filtered(&data, "storage").unwrap();
+    let keys: Vec<&str> = filtered.entries.iter().map(|entry| entry.key.as_str()).collect();
+    assert_eq!(keys, ["a", "c"]);
+    assert!(filtered.entries.iter().all(|entry| entry.namespace == "storage"));
+
+    assert!(matches!(
+        NvsPartition::parse_partition_filtered(&data, "missing"),
+        Err(Error::NamespaceNotFound(name)) if name == "missing"
+    ));
+
+    // the pages are still checked
+    set_page_version(&mut data, 0xFD);
+    assert!(matches!(
+        NvsPartition::parse_partition_filtered(&data, "storage"),
+        Err(Error::UnsupportedPageVersion { page: 0, version: 0xFD })
+    ));
+}
+
+#[test]
+fn test_base64_variants() {
+    let parse = |value: &str| {
+        NvsPartition::try_from_str(format!(
+            "key,type,encoding,value\nstorage,namespace,,\nblob,data,base64,{value}\n"
+        ))
+    };
+    let expected = EntryContent::Data(DataValue::Binary(vec![0xfb, 0xff, 0xbf, 0x01]));
+
+    for value in ["+/+/AQ==", "+/+/AQ", "-_-_AQ==", "-_-_AQ"] {
+        assert_eq!(parse(value).unwrap().entries[0].content, expected, "{value}");
+    }
+    assert!(matches!(parse("+/+/A"), Err(Error::Base64Error(_))));
+}
+
+#[test]
+fn test_parse_lenient() {
+    let partition = common::read_csv_file("tests/assets/multiple_namespaces.csv");
+    let mut data = partition.generate_partition(0x6000).unwrap();
+    let (parsed, warnings) = NvsPartition::parse_partition_lenient(&data).unwrap();
+    assert_eq!(parsed, NvsPartition::try_from_bytes(data.clone()).unwrap());
+    assert!(warnings.is_empty());
+
+    // key of the entry after the namespace
+    data[64 + 32 + 8] = b'X';
+    set_page_version(&mut data[4096..], 0xFD);
+    assert!(NvsPartition::try_from_bytes(data.clone()).is_err());
+
+    let (parsed, warnings) = NvsPartition::parse_partition_lenient(&data).unwrap();
+    assert_eq!(
+        warnings[..2],
+        [
+            ParseWarning::Problem(Problem {
+                page: 0,
+                entry: Some(1),
+                kind: ProblemKind::EntryCrcMismatch
+            }),
+            ParseWarning::Problem(Problem {
+                page: 1,
+                entry: None,
+                kind: ProblemKind::UnknownPageVersion(0xFD)
+            }),
+        ]
+    );
+    assert_eq!(warnings[1].to_string(), "page 1: unknown page version 0xfd");
+    // the definition of the last namespace was on the dropped page
+    assert!(warnings[2..].iter().all(|warning| matches!(
+        warning,
+        ParseWarning::Problem(Problem {
+            kind: ProblemKind::UnknownNamespace(3),
+            ..
+        })
+    )));
+
+    assert!(parsed.get("storage", "test_0").is_none());
+    assert!(parsed.get("storage", "test_1").is_some());
+    assert!(parsed.entries.iter().all(|entry| entry.namespace != "abcd"));
+}
@@ -1,8 +1,10 @@
 use std::fs;
 
 use esp_nvs_partition_tool::{
+    ArrayValue,
     DataValue,
     EntryContent,
+    Error,
     NvsEntry,
     NvsPartition,
 };
@@ -188,12 +190,322 @@ fn test_roundtrip_mixed() {
     }
 }
 
+/// Values containing commas, quotes and newlines are quoted by the writer and
+/// read back unchanged.
+#[test]
+fn test_roundtrip_quoted_values() {
+    let values = [
+        "a,\"b\",c",
+        "{\"json\": [1, 2]}",
+        "first line\n# second line",
+        "trailing\r\n",
+    ];
+
+    let partition = NvsPartition {
+        entries: values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                NvsEntry::new_data(
+                    "storage".to_string(),
+                    format!("value_{i}"),
+                    DataValue::String(value.to_string()),
+                )
+            })
+            .collect(),
+        empty_namespaces: vec![],
+    };
+
+    let csv = partition.clone().to_csv().unwrap();
+    assert!(csv.contains("value_0,data,string,\"a,\"\"b\"\",c\""), "{csv}");
+
+    let parsed = NvsPartition::try_from_str(&csv).unwrap();
+    assert_eq!(parsed.entries.len(), values.len());
+    for (entry, value) in parsed.entries.iter().zip(values) {
+        assert_eq!(entry.content, EntryContent::Data(DataValue::String(value.to_string())));
+    }
+}
+
+/// The driver reads back exactly what was put into the CSV.
+#[test]
+fn test_generated_image_loads_in_driver() {
+    let partition = common::read_csv_file("tests/assets/multiple_namespaces.csv");
+    let bin = partition.generate_partition(0x6000).unwrap();
+
+    let mut nvs = esp_nvs::Nvs::from_image(&bin).unwrap();
+    assert_eq!(
+        nvs.get::<u32>(&esp_nvs::Key::from_str("storage"), &esp_nvs::Key::from_str("test_5")),
+        Ok(5)
+    );
+    assert_eq!(
+        nvs.get::<String>(
+            &esp_nvs::Key::from_str("abcd"),
+            &esp_nvs::Key::from_str("uniq_string_key")
+        ),
+        Ok("I am unique!".to_string())
+    );
+    assert_eq!(
+        nvs.get::<Vec<u8>>(&esp_nvs::Key::from_str("abcd"), &esp_nvs::Key::from_str("blob_key")),
+        Ok(fs::read("tests/assets/sample_multipage_blob.bin").unwrap())
+    );
+
+    assert_eq!(
+        esp_nvs::Nvs::from_image(&bin[..100]).err(),
+        Some(esp_nvs::error::Error::InvalidPartitionSize)
+    );
+}
+
 /// Invalid inputs are properly rejected: non-aligned partition size, bad
 /// binary length, and namespace overflow.
+#[test]
+fn test_roundtrip_legacy_blob() {
+    let mut flash = esp_nvs::mem_flash::MemFlash::new(2);
+    let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+    nvs.set(
+        &esp_nvs::Key::from_str("storage"),
+        &esp_nvs::Key::from_str("old_blob"),
+        esp_nvs::LegacyBlob(&[0xde, 0xad, 0xbe, 0xef]),
+    )
+    .unwrap();
+    drop(nvs);
+    let binary = flash.into_inner();
+
+    let parsed = NvsPartition::try_from_bytes(binary.clone()).unwrap();
+    assert_entry_content(
+        &parsed,
+        0,
+        &EntryContent::Data(DataValue::LegacyBinary(vec![0xde, 0xad, 0xbe, 0xef])),
+    );
+    assert_eq!(parsed.generate_partition(binary.len()).unwrap(), binary);
+
+    let csv = parsed.clone().to_csv().unwrap();
+    assert!(csv.contains("old_blob,data,legacy_base64,3q2+7w=="), "{csv}");
+    let mut parsed = parsed;
+    parsed.clear_namespace_indices();
+    assert_eq!(NvsPartition::try_from_str(&csv).unwrap(), parsed);
+}
+
+#[test]
+fn test_roundtrip_empty_values() {
+    let partition = NvsPartition::builder()
+        .namespace("storage")
+        .string("string", "")
+        .blob("blob", [])
+        .value("legacy", DataValue::LegacyBinary(vec![]))
+        .build()
+        .unwrap();
+    let binary = partition.generate_partition(0x3000).unwrap();
+    assert!(NvsPartition::validate(&binary).unwrap().is_valid());
+
+    // none of the empty values is turned into another type
+    let mut parsed = NvsPartition::try_from_bytes(binary).unwrap();
+    parsed.clear_namespace_indices();
+    assert_eq!(parsed.entries, partition.entries);
+
+    let csv = parsed.clone().to_csv().unwrap();
+    assert_eq!(NvsPartition::try_from_str(&csv).unwrap(), parsed);
+}
+
+#[test]
+fn test_roundtrip_64_bit_limits() {
+    let csv = format!(
+        "key,type,encoding,value\n\
+         storage,namespace,,\n\
+         id,data,u64,{}\n\
+         min,data,i64,{}\n\
+         max,data,i64,{}\n",
+        u64::MAX,
+        i64::MIN,
+        i64::MAX
+    );
+    let partition = NvsPartition::try_from_str(&csv).unwrap();
+    assert_entry_content(&partition, 0, &EntryContent::Data(DataValue::U64(u64::MAX)));
+
+    let binary = partition.generate_partition(0x3000).unwrap();
+    let parsed = NvsPartition::try_from_bytes(binary).unwrap();
+    assert_eq!(parsed.to_csv().unwrap(), csv);
+
+    let csv = "key,type,encoding,value\nstorage,namespace,,\nid,data,u64,18446744073709551616\n";
+    match NvsPartition::try_from_str(csv) {
+        Err(Error::InvalidValue(message)) => assert_eq!(
+            message,
+            "value '18446744073709551616' is out of range for u64 (0..=18446744073709551615)"
+        ),
+        result => panic!("unexpected result: {result:?}"),
+    }
+}
+
+#[test]
+fn test_roundtrip_blob_chunk_start() {
+    let mut flash = esp_nvs::mem_flash::MemFlash::new(2);
+    let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+    let namespace = esp_nvs::Key::from_str("storage");
+    nvs.set_blob_with_chunk_start(&namespace, &esp_nvs::Key::from_str("new"), &[1, 2], 0x80)
+        .unwrap();
+    nvs.set(&namespace, &esp_nvs::Key::from_str("old"), [3u8].as_slice())
+        .unwrap();
+    drop(nvs);
+    let binary = flash.into_inner();
+
+    let parsed = NvsPartition::try_from_bytes(binary.clone()).unwrap();
+    assert_eq!(parsed.entries[0].chunk_start, 0x80);
+    assert_eq!(parsed.entries[1].chunk_start, 0);
+    assert_eq!(parsed.generate_partition(binary.len()).unwrap(), binary);
+}
+
+#[test]
+fn test_roundtrip_empty_namespace() {
+    let csv = "key,type,encoding,value\nempty,namespace,,\nstorage,namespace,,\nflag,data,u8,1\nlater,namespace,,\n";
+    let partition = NvsPartition::try_from_str(csv).unwrap();
+    assert_eq!(partition.entries.len(), 1);
+    assert_eq!(partition.empty_namespaces, ["empty", "later"]);
+
+    let mut parsed = NvsPartition::try_from_bytes(partition.generate_partition(0x3000).unwrap()).unwrap();
+    parsed.clear_namespace_indices();
+    assert_eq!(parsed.entries, partition.entries);
+    assert_eq!(parsed.empty_namespaces, ["empty", "later"]);
+
+    let csv = parsed.clone().to_csv().unwrap();
+    assert!(
+        csv.ends_with("flag,data,u8,1\nempty,namespace,,\nlater,namespace,,\n"),
+        "{csv}"
+    );
+    assert_eq!(NvsPartition::try_from_str(&csv).unwrap(), parsed);
+
+    // the namespace exists on the device before anything is written to it
+    let mut nvs = esp_nvs::Nvs::from_image(&partition.generate_partition(0x3000).unwrap()).unwrap();
+    let namespaces: Vec<String> = nvs.namespaces().map(|ns| ns.to_string()).collect();
+    assert_eq!(namespaces, ["empty", "later", "storage"]);
+    assert_eq!(nvs.keys().count(), 1);
+}
+
+#[test]
+fn test_extract_namespace() {
+    let partition = NvsPartition::try_from_str(
+        "key,type,encoding,value\n\
+         first,namespace,,\n\
+         a,data,u8,1\n\
+         second,namespace,,\n\
+         b,data,string,hello\n\
+         c,data,hex2bin,a0b1\n\
+         empty,namespace,,\n",
+    )
+    .unwrap();
+
+    let second = partition.extract_namespace("second");
+    assert_eq!(second.namespaces().collect::<Vec<_>>(), ["second"]);
+    assert_eq!(second.entries, partition.entries[1..]);
+
+    let csv = second.clone().to_csv().unwrap();
+    assert!(
+        csv.starts_with("key,type,encoding,value\nsecond,namespace,,\n"),
+        "{csv}"
+    );
+    assert_eq!(NvsPartition::try_from_str(&csv).unwrap(), second);
+
+    let mut parsed = NvsPartition::try_from_bytes(second.generate_partition(0x3000).unwrap()).unwrap();
+    parsed.clear_namespace_indices();
+    assert_eq!(parsed, second);
+
+    let empty = partition.extract_namespace("empty");
+    assert!(empty.entries.is_empty());
+    assert_eq!(empty.empty_namespaces, ["empty"]);
+    assert_eq!(partition.extract_namespace("missing"), NvsPartition::default());
+}
+
+#[test]
+fn test_array_roundtrip() {
+    let partition = NvsPartition::try_from_str(
+        "key,type,encoding,value\n\
+         storage,namespace,,\n\
+         table,data,u16[],\"0x0100,0x0200\"\n\
+         offsets,data,i64[],-5 7\n",
+    )
+    .unwrap();
+
+    let csv = partition.clone().to_csv().unwrap();
+    assert!(csv.contains("table,data,u16[],256 512\n"), "{csv}");
+    assert_eq!(NvsPartition::try_from_str(&csv).unwrap(), partition);
+
+    // the driver reads the generated blob as array and the tool decodes it back
+    let binary = partition.generate_partition(0x3000).unwrap();
+    let mut nvs = esp_nvs::Nvs::new(0, 0x3000, esp_nvs::mem_flash::MemFlash::from_bytes(binary.clone())).unwrap();
+    let namespace = esp_nvs::Key::from_str("storage");
+    assert_eq!(
+        nvs.get::<Vec<u16>>(&namespace, &esp_nvs::Key::from_str("table"))
+            .unwrap(),
+        [0x0100, 0x0200]
+    );
+    assert_eq!(
+        nvs.get::<Vec<i64>>(&namespace, &esp_nvs::Key::from_str("offsets"))
+            .unwrap(),
+        [-5, 7]
+    );
+
+    let parsed = NvsPartition::try_from_bytes(binary).unwrap();
+    let decoded: Vec<_> = parsed
+        .entries
+        .iter()
+        .map(|entry| match &entry.content {
+            EntryContent::Data(DataValue::Binary(blob)) => ArrayValue::from_blob(blob),
+            content => panic!("unexpected content {content:?}"),
+        })
+        .collect();
+    assert_eq!(
+        decoded,
+        [
+            Some(ArrayValue::U16(vec![0x0100, 0x0200])),
+            Some(ArrayValue::I64(vec![-5, 7]))
+        ]
+    );
+    assert_eq!(ArrayValue::from_blob(&[esp_nvs::ItemType::U16 as u8, 1]), None);
+    assert_eq!(ArrayValue::from_blob(&[esp_nvs::ItemType::Sized as u8]), None);
+}
+
+#[test]
+fn test_content_fingerprint() {
+    let csv = "key,type,encoding,value\n\
+               config,namespace,,\n\
+               flag,data,u8,1\n\
+               name,data,string,hello\n\
+               table,data,u16[],1 2\n\
+               other,namespace,,\n\
+               blob,data,hex2bin,a0b1c2d3\n\
+               empty,namespace,,\n";
+    let partition = NvsPartition::try_from_str(csv).unwrap();
+    let fingerprint = partition.content_fingerprint().unwrap();
+
+    // the parsed binary holds the array as blob and records the namespace indices
+    let parsed = NvsPartition::try_from_bytes(partition.generate_partition(0x3000).unwrap()).unwrap();
+    assert_eq!(parsed.content_fingerprint().unwrap(), fingerprint);
+
+    let mut reordered = partition.clone();
+    reordered.entries.reverse();
+    assert_eq!(reordered.content_fingerprint().unwrap(), fingerprint);
+
+    let mut changed = partition.clone();
+    changed.set("config", "flag", DataValue::U8(2));
+    assert_ne!(changed.content_fingerprint().unwrap(), fingerprint);
+
+    // same bytes, different type
+    let mut changed = partition.clone();
+    changed.set("config", "flag", DataValue::I8(1));
+    assert_ne!(changed.content_fingerprint().unwrap(), fingerprint);
+
+    let mut moved = partition.clone();
+    moved.get_mut("other", "blob").unwrap().namespace = "config".to_string();
+    moved.empty_namespaces.push("other".to_string());
+    assert_ne!(moved.content_fingerprint().unwrap(), fingerprint);
+
+    let mut without_empty = partition.clone();
+    without_empty.empty_namespaces.clear();
+    assert_ne!(without_empty.content_fingerprint().unwrap(), fingerprint);
+}
+
 #[test]
 fn test_validation_errors() {
     // Non-4096-aligned partition size
-    let partition = NvsPartition { entries: vec![] };
+    let partition = NvsPartition::default();
     assert!(
         partition.generate_partition(5000).is_err(),
         "non-4096-aligned size should be rejected"
@@ -204,7 +516,7 @@ fn test_validation_errors() {
     assert!(NvsPartition::try_from_bytes(bad_data).is_err());
 
     // Too many namespaces (256 > 255 limit)
-    let mut partition = NvsPartition { entries: vec![] };
+    let mut partition = NvsPartition::default();
     for i in 0..256_u16 {
         partition.entries.push(NvsEntry::new_data(
             format!("ns_{i:03}"),
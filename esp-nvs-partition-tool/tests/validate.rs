@@ -0,0 +1,165 @@
+use esp_nvs_partition_tool::{
+    NvsPartition,
+    Problem,
+    ProblemKind,
+    crc,
+};
+
+mod common;
+
+const ENTRY_OFFSET: usize = 64;
+
+fn generate() -> Vec<u8> {
+    let partition = common::read_csv_file("tests/assets/multiple_namespaces.csv");
+    partition.generate_partition(0x6000).unwrap()
+}
+
+#[test]
+fn test_validate_generated() {
+    let report = NvsPartition::validate(&generate()).unwrap();
+    assert!(report.is_valid(), "{report}");
+}
+
+#[test]
+fn test_validate_invalid_size() {
+    assert!(NvsPartition::validate(&[0xFF; 100]).is_err());
+}
+
+#[test]
+fn test_validate_collects_all_problems() {
+    let mut data = generate();
+
+    // page header of the second page
+    data[4096 + 8] ^= 0x01;
+    // entry 3 of the first page
+    data[ENTRY_OFFSET + 3 * 32 + 12] ^= 0x01;
+    // mark the last, still empty entry of the first page as illegal
+    let last_entry = 125;
+    data[32 + last_entry / 4] &= !(0b10 << ((last_entry % 4) * 2));
+
+    let report = NvsPartition::validate(&data).unwrap();
+    assert_eq!(
+        report.problems[..2],
+        [
+            Problem {
+                page: 0,
+                entry: Some(3),
+                kind: ProblemKind::EntryCrcMismatch,
+            },
+            Problem {
+                page: 0,
+                entry: Some(125),
+                kind: ProblemKind::IllegalEntryState,
+            },
+        ]
+    );
+    assert!(report.problems.contains(&Problem {
+        page: 1,
+        entry: None,
+        kind: ProblemKind::HeaderCrcMismatch,
+    }));
+    assert_eq!(report.to_string().lines().count(), report.problems.len());
+}
+
+#[test]
+fn test_validate_orphaned_entries() {
+    let partition = NvsPartition::try_from_str(
+        "key,type,encoding,value\n\
+         storage,namespace,,\n\
+         name,data,string,a string that needs two data entries\n\
+         flag,data,u8,1\n",
+    )
+    .unwrap();
+    let mut data = partition.generate_partition(0x3000).unwrap();
+
+    // erasing the string was interrupted after its header, entries 2 and 3 hold its data
+    data[32] &= !(0b11 << 2);
+
+    let report = NvsPartition::validate(&data).unwrap();
+    let orphan = |entry| Problem {
+        page: 0,
+        entry: Some(entry),
+        kind: ProblemKind::OrphanedEntry,
+    };
+    assert_eq!(report.problems, [orphan(2), orphan(3)]);
+
+    // the driver drops the orphans, the following entry is still read
+    let (parsed, warnings) = NvsPartition::parse_partition_lenient(&data).unwrap();
+    assert_eq!(parsed.entries.len(), 1);
+    assert_eq!(parsed.entries[0].key, "flag");
+    assert_eq!(warnings.len(), 2);
+}
+
+#[test]
+fn test_crc_helpers() {
+    let data = NvsPartition::builder()
+        .namespace("storage")
+        .string("name", "value")
+        .build()
+        .unwrap()
+        .generate_partition(0x3000)
+        .unwrap();
+    let stored = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    let entry = |index: usize| -> &[u8; 32] {
+        data[ENTRY_OFFSET + index * 32..ENTRY_OFFSET + (index + 1) * 32]
+            .try_into()
+            .unwrap()
+    };
+
+    assert_eq!(crc::crc32_page_header(data[..32].try_into().unwrap()), stored(28));
+    // the namespace entry followed by the string entry and its data
+    assert_eq!(crc::crc32_entry(entry(0)), stored(ENTRY_OFFSET + 4));
+    assert_eq!(crc::crc32_entry(entry(1)), stored(ENTRY_OFFSET + 32 + 4));
+    assert_eq!(crc::crc32(b"value\0"), stored(ENTRY_OFFSET + 32 + 28));
+    assert_eq!(&entry(2)[..6], b"value\0");
+}
+
+#[test]
+fn test_default_platform_crc_matches_crc_helpers() {
+    struct DefaultCrc;
+    impl esp_nvs::platform::Crc for DefaultCrc {}
+    let default_crc = <DefaultCrc as esp_nvs::platform::Crc>::crc32;
+
+    for data in [&b""[..], b"123456789", &[0xFF; 4000]] {
+        assert_eq!(default_crc(u32::MAX, data), crc::crc32(data));
+    }
+    let entry: [u8; 32] = std::array::from_fn(|i| i as u8);
+    assert_eq!(
+        default_crc(default_crc(u32::MAX, &entry[0..4]), &entry[8..32]),
+        crc::crc32_entry(&entry)
+    );
+
+    // MemFlash relies on the default as well and reads generated partitions
+    let data = generate();
+    let mut nvs = esp_nvs::Nvs::new(0, data.len(), esp_nvs::mem_flash::MemFlash::from_bytes(data)).unwrap();
+    // entries with a CRC mismatch would have been erased on load
+    assert_eq!(nvs.statistics().unwrap().entries_overall.erased, 0);
+    assert!(!nvs.keys().collect::<Result<Vec<_>, _>>().unwrap().is_empty());
+}
+
+#[test]
+fn test_annotate() {
+    let partition = NvsPartition::builder()
+        .namespace("storage")
+        .u16("count", 0x1234)
+        .string("name", "nvs")
+        .build()
+        .unwrap();
+    let mut data = partition.generate_partition(0x2000).unwrap();
+    // key of the string entry
+    data[ENTRY_OFFSET + 2 * 32 + 8] = b'N';
+    // mark the entry after the string as illegal, entry 4 is the lowest bit pair of the second byte
+    data[32 + 1] &= !0b10;
+
+    assert_eq!(
+        NvsPartition::annotate(&data),
+        "page 0 @ 0x000000: Active (0xfffffffe), sequence 0, version 0xfe, header CRC ok\n\
+         \x20 entry 0: written, namespace 0, U8, span 1, chunk 0xff, key \"storage\", CRC ok, namespace index 1\n\
+         \x20 entry 1: written, namespace 1, U16, span 1, chunk 0xff, key \"count\", CRC ok, data 3412ffffffffffff\n\
+         \x20 entry 2: written, namespace 1, Sized, span 2, chunk 0xff, key \"Name\", CRC mismatch, size 4, data CRC ok\n\
+         \x20 entry 3: data of entry 2\n\
+         \x20 entry 4: illegal\n\
+         \x20 entries 5-125: empty\n\
+         page 1 @ 0x001000: Uninitialized (0xffffffff), erased\n"
+    );
+}
@@ -0,0 +1,91 @@
+//! Typed arrays stored as blobs, see [`ArrayElement`].
+
+use alloc::vec::Vec;
+
+use crate::error::Error;
+use crate::raw::ItemType;
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Primitives which can be stored as typed array with [`crate::Nvs::set`] and read back as `Vec`
+/// with [`crate::Nvs::get`].
+///
+/// ESP-IDF has no native array type, so slices are stored as regular blobs with the following
+/// layout:
+///
+/// | offset | size                | content                                                   |
+/// |--------|---------------------|-----------------------------------------------------------|
+/// | 0      | 1                   | [`ItemType`] of the element, e.g. `0x04` for `u32`        |
+/// | 1      | `len * Self::SIZE`  | the elements in little endian byte order, without padding |
+///
+/// Since they are plain blobs, arrays span multiple pages like any other blob and can be read as
+/// `Vec<u8>` (including the tag byte) by code that doesn't know about this convention.
+///
+/// `u8` is intentionally missing as `&[u8]` and `Vec<u8>` are stored as raw blobs.
+pub trait ArrayElement: private::Sealed + Sized + Copy {
+    /// Type tag stored as first byte of the blob
+    const ITEM_TYPE: ItemType;
+    /// Size of a single element in bytes
+    const SIZE: usize;
+
+    #[doc(hidden)]
+    fn write_le(self, buf: &mut Vec<u8>);
+
+    #[doc(hidden)]
+    fn read_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! array_element {
+    ($type:ty, $item_type:expr) => {
+        impl private::Sealed for $type {}
+
+        impl ArrayElement for $type {
+            const ITEM_TYPE: ItemType = $item_type;
+            const SIZE: usize = size_of::<$type>();
+
+            fn write_le(self, buf: &mut Vec<u8>) {
+                buf.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn read_le(bytes: &[u8]) -> Self {
+                <$type>::from_le_bytes(bytes.try_into().unwrap())
+            }
+        }
+    };
+}
+
+array_element!(i8, ItemType::I8);
+array_element!(u16, ItemType::U16);
+array_element!(i16, ItemType::I16);
+array_element!(u32, ItemType::U32);
+array_element!(i32, ItemType::I32);
+array_element!(u64, ItemType::U64);
+array_element!(i64, ItemType::I64);
+
+/// Serialize the slice into the blob layout described in the module documentation.
+pub(crate) fn encode<E: ArrayElement>(values: &[E]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(1 + values.len() * E::SIZE);
+    buf.push(E::ITEM_TYPE as u8);
+    for value in values {
+        value.write_le(&mut buf);
+    }
+    buf
+}
+
+/// Parse a blob written by [`encode`]. A different element type is reported as
+/// [`Error::ItemTypeMismatch`], a truncated blob as [`Error::CorruptedData`].
+pub(crate) fn decode<E: ArrayElement>(blob: &[u8]) -> Result<Vec<E>, Error> {
+    let (&tag, data) = blob.split_first().ok_or(Error::CorruptedData)?;
+    if tag != E::ITEM_TYPE as u8 {
+        let type_ = ItemType::from_repr(tag).ok_or(Error::CorruptedData)?;
+        return Err(Error::ItemTypeMismatch(type_));
+    }
+
+    if !data.len().is_multiple_of(E::SIZE) {
+        return Err(Error::CorruptedData);
+    }
+
+    Ok(data.chunks_exact(E::SIZE).map(E::read_le).collect())
+}
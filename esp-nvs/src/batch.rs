@@ -0,0 +1,197 @@
+//! Buffered updates of multiple keys.
+//!
+//! This module contains the [`Batch`] returned by [`Nvs::batch`] which collects `set` and
+//! `delete` operations in memory and applies them on commit.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::error::Error;
+use crate::platform::Platform;
+use crate::raw::{
+    ItemType,
+    MAX_BLOB_DATA_PER_PAGE,
+};
+use crate::set::Set;
+use crate::{
+    Key,
+    Nvs,
+};
+
+/// An owned copy of a value passed to [`Batch::set`].
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    U64(u64),
+    I64(i64),
+    Str(String),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    /// The item type and the raw value of primitives, like [`Nvs::set`] writes them.
+    fn primitive(&self) -> Option<(ItemType, u64)> {
+        Some(match *self {
+            Value::U8(value) => (ItemType::U8, value as u64),
+            Value::I8(value) => (ItemType::I8, value.cast_unsigned() as u64),
+            Value::U16(value) => (ItemType::U16, value as u64),
+            Value::I16(value) => (ItemType::I16, value.cast_unsigned() as u64),
+            Value::U32(value) => (ItemType::U32, value as u64),
+            Value::I32(value) => (ItemType::I32, value.cast_unsigned() as u64),
+            Value::U64(value) => (ItemType::U64, value),
+            Value::I64(value) => (ItemType::I64, value.cast_unsigned()),
+            Value::Str(_) | Value::Blob(_) => return None,
+        })
+    }
+}
+
+/// Buffered `set` and `delete` operations, see [`Nvs::batch`].
+///
+/// Nothing is written until [`Batch::commit`] is called, dropping the batch discards all
+/// operations. If the same key is modified multiple times only the last operation is kept.
+/// Primitives are packed into consecutive entries, which takes fewer flash writes than calling
+/// [`Nvs::set`] for every value.
+///
+/// Keys and the length of values are checked when an operation is added, so they don't fail
+/// halfway through the commit.
+pub struct Batch<'a, T: Platform> {
+    nvs: &'a mut Nvs<T>,
+    // `None` deletes the key
+    operations: Vec<(Key, Key, Option<Value>)>,
+}
+
+impl<'a, T: Platform> Batch<'a, T> {
+    pub(crate) fn new(nvs: &'a mut Nvs<T>) -> Self {
+        Self {
+            nvs,
+            operations: Vec::new(),
+        }
+    }
+
+    /// Buffer setting a value. See [`Nvs::set`] for the supported types.
+    pub fn set<R>(&mut self, namespace: &Key, key: &Key, value: R) -> Result<(), Error>
+    where
+        Self: Set<R>,
+    {
+        Set::set(self, namespace, key, value)
+    }
+
+    /// Buffer deleting a key. Like [`Nvs::delete`] missing keys are ignored on commit.
+    pub fn delete(&mut self, namespace: &Key, key: &Key) -> Result<(), Error> {
+        self.push(namespace, key, None)
+    }
+
+    /// Returns the number of buffered operations.
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Returns `true` if no operation is buffered.
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Apply all buffered operations in the order their keys were last modified.
+    ///
+    /// Consecutive primitives are written to the active page as consecutive entries with a
+    /// single write per page, and a single update of the entry state bitmap marks them as
+    /// written. Afterwards the old values are erased one by one. Strings, blobs and deletions are
+    /// applied by [`Nvs::set`] and [`Nvs::delete`]. Values that didn't change are skipped.
+    ///
+    /// The batch is not atomic: if an operation fails, e.g. with [`Error::FlashFull`], the
+    /// operations before it stay applied and the error is returned.
+    pub fn commit(self) -> Result<(), Error> {
+        let mut primitives = Vec::new();
+        for (namespace, key, value) in self.operations {
+            if let Some((type_, value)) = value.as_ref().and_then(Value::primitive) {
+                primitives.push((namespace, key, type_, value));
+                continue;
+            }
+
+            set_primitives(self.nvs, &mut primitives)?;
+            match value {
+                None => self.nvs.delete(&namespace, &key),
+                Some(Value::Str(value)) => self.nvs.set(&namespace, &key, value.as_str()),
+                Some(Value::Blob(value)) => self.nvs.set(&namespace, &key, value.as_slice()),
+                Some(_) => unreachable!(),
+            }?;
+        }
+
+        set_primitives(self.nvs, &mut primitives)
+    }
+
+    fn push(&mut self, namespace: &Key, key: &Key, value: Option<Value>) -> Result<(), Error> {
+        if self.nvs.read_only {
+            return Err(Error::ReadOnly);
+        }
+        if !key.is_well_formed() {
+            return Err(Error::KeyMalformed);
+        }
+        if !namespace.is_well_formed() {
+            return Err(Error::NamespaceMalformed);
+        }
+
+        // the same limits as on commit, including the null terminator of strings
+        let too_long = match &value {
+            Some(Value::Str(value)) => value.len() + 1 > MAX_BLOB_DATA_PER_PAGE,
+            Some(Value::Blob(value)) => value.len() + 1 > self.nvs.max_blob_size,
+            _ => false,
+        };
+        if too_long {
+            return Err(Error::ValueTooLong);
+        }
+
+        self.operations.retain(|(ns, k, _)| ns != namespace || k != key);
+        self.operations.push((*namespace, *key, value));
+        Ok(())
+    }
+}
+
+/// Write and clear the collected primitives, faulting the instance on flash errors like
+/// [`Nvs::set`].
+fn set_primitives<T: Platform>(nvs: &mut Nvs<T>, primitives: &mut Vec<(Key, Key, ItemType, u64)>) -> Result<(), Error> {
+    if primitives.is_empty() {
+        return Ok(());
+    }
+    if nvs.faulted {
+        return Err(Error::FlashError);
+    }
+
+    let result = nvs.set_primitives(primitives);
+    primitives.clear();
+    if result == Err(Error::FlashError) {
+        nvs.faulted = true;
+    }
+    result
+}
+
+macro_rules! impl_set {
+    ($($type:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl<T: Platform> Set<$type> for Batch<'_, T> {
+                fn set(&mut self, namespace: &Key, key: &Key, value: $type) -> Result<(), Error> {
+                    self.push(namespace, key, Some(Value::$variant(value.into())))
+                }
+            }
+        )*
+    };
+}
+
+impl_set!(
+    bool => U8,
+    u8 => U8,
+    i8 => I8,
+    u16 => U16,
+    i16 => I16,
+    u32 => U32,
+    i32 => I32,
+    u64 => U64,
+    i64 => I64,
+    &str => Str,
+    &[u8] => Blob,
+);
@@ -44,3 +44,11 @@ pub(crate) struct BlobIndexEntryBlobIndexData {
     pub(crate) size: u32,
     pub(crate) chunk_count: u8,
 }
+
+/// A blob written in the legacy single-page format (type `0x41`) used before ESP-IDF v4.0.
+///
+/// Only needed for images read by old firmware, regular blobs are written by
+/// `Set<&[u8]>`. The data is limited to [`crate::MAX_BLOB_DATA_PER_PAGE`] bytes. Reading works
+/// with `Get<Vec<u8>>` like for regular blobs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LegacyBlob<'a>(pub &'a [u8]);
@@ -3,6 +3,8 @@
 //! This module contains defragmentation, cleanup, and page reclamation logic.
 
 use alloc::collections::BTreeMap;
+#[cfg(feature = "convert-legacy-blobs")]
+use alloc::collections::BTreeSet;
 use alloc::vec::Vec;
 
 #[cfg(feature = "defmt")]
@@ -11,14 +13,18 @@ use defmt::{
     warn,
 };
 
-use crate::Nvs;
-use crate::blob::BlobIndex;
+use crate::blob::{
+    BlobIndex,
+    BlobIndexEntryBlobIndexData,
+};
 use crate::error::Error;
 use crate::page::{
     ThinPage,
     ThinPageState,
 };
 use crate::platform::Platform;
+#[cfg(feature = "convert-legacy-blobs")]
+use crate::raw::Item;
 #[cfg(feature = "debug-logs")]
 use crate::raw::slice_with_nullbytes_to_str;
 use crate::raw::{
@@ -26,6 +32,7 @@ use crate::raw::{
     EntryMapState,
     FLASH_SECTOR_SIZE,
     ItemType,
+    MAX_BLOB_DATA_PER_PAGE,
     PageState,
     write_aligned,
 };
@@ -35,8 +42,21 @@ use crate::types::{
     NamespaceIndex,
     PageIndex,
     PageSequence,
+    VersionOffset,
 };
 use crate::u24::u24;
+use crate::{
+    Key,
+    Nvs,
+};
+
+/// Blobs up to this size are checked against the CRCs of their chunks when the partition is
+/// loaded, larger blobs only on `get`.
+const MAX_VERIFIED_BLOB_SIZE: u32 = MAX_BLOB_DATA_PER_PAGE as u32;
+
+/// Full pages with at least this many erased entries are reclaimed before the last free page
+/// besides the reserved ones is used, see `reclaim_early`.
+const EARLY_RECLAIM_ERASED_ENTRIES: usize = ENTRIES_PER_PAGE / 2;
 
 impl<T> Nvs<T>
 where
@@ -46,6 +66,8 @@ where
         #[cfg(feature = "defmt")]
         trace!("cleanup_dirty_blobs");
 
+        self.drop_corrupted_blobs(&mut blob_index)?;
+
         while let Some(((namespace_index, chunk_start, key), (index, observed))) = blob_index.pop_first() {
             if let Some(index) = index {
                 // Calculate total chunks and data size from all observed chunks
@@ -107,6 +129,78 @@ where
         Ok(())
     }
 
+    /// Erase the index of blobs whose chunks don't match their CRC. Their chunks are then
+    /// removed as orphaned data by [`Nvs::cleanup_dirty_blobs`], so if the blob was being
+    /// overwritten, the other version is kept.
+    fn drop_corrupted_blobs(&mut self, blob_index: &mut BlobIndex) -> Result<(), Error> {
+        #[cfg(feature = "defmt")]
+        trace!("drop_corrupted_blobs");
+
+        for ((namespace_index, chunk_start, key), (index, _)) in blob_index.iter_mut() {
+            let Some(data) = index else {
+                continue;
+            };
+            if data.size > MAX_VERIFIED_BLOB_SIZE
+                || self.blob_chunks_valid(namespace_index.0, key, chunk_start.clone() as u8, data.chunk_count)?
+            {
+                continue;
+            }
+
+            #[cfg(feature = "debug-logs")]
+            println!(
+                "internal: load_sectors: blob data doesn't match its CRC. key: '{}', chunk_start: {}",
+                slice_with_nullbytes_to_str(&key.0),
+                chunk_start.clone() as u8
+            );
+
+            self.erase_blob_index(data)?;
+            *index = None;
+        }
+
+        Ok(())
+    }
+
+    /// Returns `false` if a chunk is missing or its data doesn't match its CRC.
+    fn blob_chunks_valid(
+        &mut self,
+        namespace_index: u8,
+        key: &Key,
+        chunk_start: u8,
+        chunk_count: u8,
+    ) -> Result<bool, Error> {
+        for chunk in chunk_start..chunk_start.saturating_add(chunk_count) {
+            let (page_index, item_index, item) = match self.load_item(namespace_index, ChunkIndex::BlobData(chunk), key)
+            {
+                Ok(found) => found,
+                Err(Error::KeyNotFound) => return Ok(false),
+                Err(e) => return Err(e),
+            };
+            if item.type_ != ItemType::BlobData {
+                return Ok(false);
+            }
+
+            let data = self.pages[page_index.0].load_referenced_data(&mut self.hal, item_index.0, &item)?;
+            if unsafe { item.data.sized.crc } != T::crc32(u32::MAX, &data) {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Erase exactly the given blob index, unlike [`Nvs::delete_key`] which erases the first blob
+    /// index of the key it finds.
+    fn erase_blob_index(&mut self, index: &BlobIndexEntryBlobIndexData) -> Result<(), Error> {
+        let Some(page) = self
+            .pages
+            .iter_mut()
+            .find(|page| page.header.sequence == index.page_sequence)
+        else {
+            return Ok(());
+        };
+        page.erase_item::<T>(&mut self.hal, &mut self.hash_index, index.item_index, 1)
+    }
+
     /// The active page has to be the last page in `self.pages` as we use `pop_if` to fetch it.
     /// We also clean up any duplicate active pages that might have been created in the past
     /// due to the borked order.
@@ -138,7 +232,7 @@ where
             }
 
             // Mark duplicate active pages as Full
-            if active_page_count > 1 {
+            if active_page_count > 1 && !self.read_only {
                 // We actively ignore the last page as it is the correct active one
                 for idx in 0..last_page_idx {
                     let page = &mut self.pages[idx];
@@ -199,7 +293,13 @@ where
     /// Clean up duplicate primitive/string entries by marking older versions as erased.
     /// This handles the write-before-delete scenario where deletion failed after successful write.
     /// IMPORTANT: This does NOT touch blob entries - they have their own cleanup logic.
-    pub(crate) fn cleanup_duplicate_entries(&mut self) -> Result<(), Error> {
+    /// Erase all but the newest item of every key, except for blob data and blob indices which
+    /// are left to [`Nvs::cleanup_dirty_blobs`].
+    ///
+    /// A key whose type changed has the new item written before the old one is erased, so a blob
+    /// index can be a duplicate of another item as well. If it is the older one, its chunks are
+    /// turned into orphaned data in `blob_index` for [`Nvs::cleanup_dirty_blobs`] to remove.
+    pub(crate) fn cleanup_duplicate_entries(&mut self, blob_index: &mut BlobIndex) -> Result<(), Error> {
         #[cfg(feature = "defmt")]
         trace!("cleanup_duplicate_entries");
 
@@ -231,16 +331,16 @@ where
                 let page = &self.pages[page_idx.0];
                 let item = page.load_item(&mut self.hal, item_index.0)?;
 
-                // Skip namespace entries (namespace_index == 0) and blob entries
+                // Skip namespace entries (namespace_index == 0) and blob data
                 // Namespace entries are special and should not be cleaned up
-                // Blob entries have their own cleanup logic
-                if item.namespace_index == 0 || item.type_ == ItemType::BlobIndex || item.type_ == ItemType::BlobData {
+                // Blob data has its own cleanup logic
+                if item.namespace_index == 0 || item.type_ == ItemType::BlobData {
                     continue;
                 }
 
                 items.push((
                     (NamespaceIndex(item.namespace_index), item.key),
-                    (page_idx, item_index, page_seq, item.span),
+                    (page_idx, item_index, page_seq, item),
                 ));
             }
 
@@ -252,7 +352,8 @@ where
 
             // Erase older duplicates
             for (_key, mut group) in key_groups {
-                if group.len() <= 1 {
+                // two versions of a blob are resolved by cleanup_dirty_blobs
+                if group.iter().all(|(.., item)| item.type_ == ItemType::BlobIndex) {
                     continue;
                 }
 
@@ -261,16 +362,125 @@ where
 
                 // Keep the newest (last after sort), erase older ones
                 let keep_count = group.len() - 1;
-                for (PageIndex(page_index), ItemIndex(item_index), _, span) in group.into_iter().take(keep_count) {
+                for (PageIndex(page_index), ItemIndex(item_index), _, item) in group.into_iter().take(keep_count) {
                     let page = self.pages.get_mut(page_index).unwrap();
-                    page.erase_item::<T>(&mut self.hal, item_index, span)?;
+                    page.erase_item::<T>(&mut self.hal, &mut self.hash_index, item_index, item.span)?;
+
+                    if item.type_ == ItemType::BlobIndex {
+                        let chunk_start = VersionOffset::from(unsafe { item.data.blob_index.chunk_start });
+                        if let Some((index, _)) =
+                            blob_index.get_mut(&(NamespaceIndex(item.namespace_index), chunk_start, item.key))
+                        {
+                            *index = None;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rewrite the given legacy single page blobs (type 0x41) as a BLOB_INDEX with BLOB_DATA
+    /// chunks.
+    ///
+    /// The new blob is written before the legacy item is erased, so an interrupted conversion is
+    /// finished on the next load. If there is not enough space left the remaining legacy blobs are
+    /// kept, they can still be read.
+    #[cfg(feature = "convert-legacy-blobs")]
+    pub(crate) fn convert_legacy_blobs(&mut self, legacy_blobs: BTreeSet<(u8, Key)>) -> Result<(), Error> {
+        #[cfg(feature = "defmt")]
+        trace!("convert_legacy_blobs");
+
+        #[cfg(feature = "debug-logs")]
+        println!("internal: convert_legacy_blobs");
+
+        for (namespace_index, key) in legacy_blobs {
+            let Some(namespace) = self
+                .namespaces
+                .iter()
+                .find(|(_, index)| **index == namespace_index)
+                .map(|(name, _)| *name)
+            else {
+                continue;
+            };
+
+            // duplicates were already cleaned up, so there is at most one legacy blob left
+            let Some((page_index, item_index, item)) = self.find_item_of_type(namespace_index, &key, ItemType::Blob)?
+            else {
+                continue;
+            };
+            let position = (self.pages[page_index.0].header.sequence, item_index.0);
+
+            if let Some((index_page, index_item, index)) =
+                self.find_item_of_type(namespace_index, &key, ItemType::BlobIndex)?
+            {
+                let page = self.pages.get_mut(index_page.0).unwrap();
+                if (page.header.sequence, index_item.0) > position {
+                    // the blob was overwritten or a previous conversion was interrupted
+                    self.erase_legacy_blob(namespace_index, &key)?;
+                    continue;
                 }
+
+                // both formats share the chunk index of the blob index, so the outdated blob index
+                // is removed to not confuse the lookup of the current blob version
+                page.erase_item::<T>(&mut self.hal, &mut self.hash_index, index_item.0, 1)?;
+                self.delete_blob_data(namespace_index, &key, unsafe {
+                    VersionOffset::from(index.data.blob_index.chunk_start)
+                })?;
+            }
+
+            let data = self.pages[page_index.0].load_referenced_data(&mut self.hal, item_index.0, &item)?;
+            if unsafe { item.data.sized.crc } != T::crc32(u32::MAX, &data) {
+                // leave it to the reader to report the corruption
+                continue;
+            }
+
+            #[cfg(feature = "debug-logs")]
+            println!(
+                "internal: convert_legacy_blobs: converting '{}'",
+                slice_with_nullbytes_to_str(&key.0)
+            );
+
+            match self.set_blob(&namespace, key, &data) {
+                Ok(()) => {}
+                Err(Error::FlashFull) => return Ok(()),
+                Err(e) => return Err(e),
             }
+            self.erase_legacy_blob(namespace_index, &key)?;
         }
 
         Ok(())
     }
 
+    #[cfg(feature = "convert-legacy-blobs")]
+    fn erase_legacy_blob(&mut self, namespace_index: u8, key: &Key) -> Result<(), Error> {
+        if let Some((page_index, item_index, item)) = self.find_item_of_type(namespace_index, key, ItemType::Blob)? {
+            let page = self.pages.get_mut(page_index.0).unwrap();
+            page.erase_item::<T>(&mut self.hal, &mut self.hash_index, item_index.0, item.span)?;
+        }
+        Ok(())
+    }
+
+    /// Like [`Nvs::load_item`] for the chunk index shared by legacy blobs and blob indices, but
+    /// only matching items of the given type.
+    #[cfg(feature = "convert-legacy-blobs")]
+    fn find_item_of_type(
+        &mut self,
+        namespace_index: u8,
+        key: &Key,
+        type_: ItemType,
+    ) -> Result<Option<(PageIndex, ItemIndex, Item)>, Error> {
+        let hash = Item::calculate_hash_ref(T::crc32, namespace_index, key, u8::MAX);
+        for (page_index, item_index) in self.hash_candidates(hash) {
+            let item = self.pages[page_index].load_item(&mut self.hal, item_index)?;
+            if item.namespace_index == namespace_index && item.key == *key && item.type_ == type_ {
+                return Ok(Some((PageIndex(page_index), ItemIndex(item_index), item)));
+            }
+        }
+        Ok(None)
+    }
+
     /// Try to find and reclaim pages that can be recycled
     pub(crate) fn defragment(&mut self) -> Result<(), Error> {
         #[cfg(feature = "defmt")]
@@ -281,41 +491,69 @@ where
 
         let next_sequence = self.get_next_sequence();
 
-        // Find the next page to reclaim
-        // By incorporating the sequence number, we will also reclaim older pages even if they are
-        // pretty full. This helps with more even wear leveling.
-        let next_page = self
-            .pages
-            .iter()
-            .enumerate()
-            .map(|(idx, page)| {
-                let points = if page.erased_entry_count == 0 {
-                    0
-                } else {
-                    page.erased_entry_count as u32 * 10 + (next_sequence - page.header.sequence)
-                };
-                (points, idx)
-            })
-            .max_by_key(|(points, _idx)| *points)
-            .map(|(_, idx)| idx)
-            .ok_or(Error::FlashFull)?;
+        let next_page = self.next_reclaim_candidate(next_sequence).ok_or(Error::FlashFull)?;
 
-        let page = self.pages.swap_remove(next_page);
+        // the copy of a full page without erased entries would be full again
+        let page = &self.pages[next_page];
+        if page.header.state == ThinPageState::Full && page.erased_entry_count == 0 {
+            return Err(Error::FlashFull);
+        }
+
+        self.reclaim_page(next_page, next_sequence)
+    }
+
+    /// Reclaim the page `defragment` would pick next if it is full and mostly erased, while a free
+    /// page besides the reserved ones is still left.
+    ///
+    /// The remaining entries are copied to a free page, which becomes the new active page, and the
+    /// reclaimed page is added to the free pages. So the last free page is kept instead of being
+    /// used up, which would leave every following full page to a reclamation.
+    ///
+    /// With a single full page the copy would go back and forth between the same two pages, so
+    /// nothing is reclaimed then. Returns `false` if no page was reclaimed.
+    pub(crate) fn reclaim_early(&mut self) -> Result<bool, Error> {
+        #[cfg(feature = "defmt")]
+        trace!("reclaim_early");
+
+        if self.pages.len() < 2 {
+            return Ok(false);
+        }
+
+        let next_sequence = self.get_next_sequence();
+        let Some(candidate) = self.next_reclaim_candidate(next_sequence) else {
+            return Ok(false);
+        };
+
+        let page = &self.pages[candidate];
+        let mostly_erased = page.erased_entry_count as usize >= EARLY_RECLAIM_ERASED_ENTRIES;
+        if page.header.state != ThinPageState::Full || !mostly_erased {
+            return Ok(false);
+        }
+
+        self.reclaim_page(candidate, next_sequence)?;
+
+        Ok(true)
+    }
+
+    fn reclaim_page(&mut self, idx: usize, next_sequence: u32) -> Result<(), Error> {
+        let page = self.pages.swap_remove(idx);
 
         #[cfg(feature = "debug-logs")]
         println!("internal: defragment: next_page: {page:?}");
 
         match page.header.state {
-            ThinPageState::Uninitialized => unreachable!(),
+            ThinPageState::Uninitialized | ThinPageState::Blank => unreachable!(),
             ThinPageState::Active => unreachable!(),
-            ThinPageState::Full => {
+            // only one freeing page is continued on init, any other one still holds all of its
+            // entries and is reclaimed like a full page. Entries that were already copied before
+            // the reclamation was interrupted become duplicates, which are cleaned up on init.
+            ThinPageState::Full | ThinPageState::Freeing => {
                 if page.erased_entry_count != ENTRIES_PER_PAGE as _ {
                     self.free_page(&page, next_sequence)?;
                 }
 
                 self.erase_page(page)?;
             }
-            ThinPageState::Freeing => unreachable!(), // TODO cleanup freeing pages on init
             ThinPageState::Corrupt => {
                 self.erase_page(page)?;
             }
@@ -327,6 +565,88 @@ where
         Ok(())
     }
 
+    /// Find the next page to reclaim
+    /// By incorporating the sequence number, we will also reclaim older pages even if they are
+    /// pretty full. This helps with more even wear leveling.
+    fn next_reclaim_candidate(&self, next_sequence: u32) -> Option<usize> {
+        self.pages
+            .iter()
+            .enumerate()
+            .map(|(idx, page)| {
+                let points = if page.erased_entry_count == 0 {
+                    0
+                } else {
+                    page.erased_entry_count as u32 * 10 + (next_sequence - page.header.sequence)
+                };
+                (points, idx)
+            })
+            .max_by_key(|(points, _idx)| *points)
+            .map(|(_, idx)| idx)
+    }
+
+    /// Proactively reclaim the page `defragment` would pick next and return the number of
+    /// entries that became available.
+    ///
+    /// The copy always goes to a fresh page as that is what `continue_free_page` expects after a
+    /// power loss. If there is an active page besides the reclaimed one, it has to be marked as
+    /// full first, so its free entries are only given up if more entries are reclaimed.
+    pub(crate) fn compact_page(&mut self) -> Result<usize, Error> {
+        #[cfg(feature = "defmt")]
+        trace!("compact_page");
+
+        #[cfg(feature = "debug-logs")]
+        println!("internal: compact_page");
+
+        let next_sequence = self.get_next_sequence();
+
+        let Some(candidate) = self.next_reclaim_candidate(next_sequence) else {
+            return Ok(0);
+        };
+
+        let page = &self.pages[candidate];
+        if page.erased_entry_count == 0 || !matches!(page.header.state, ThinPageState::Full | ThinPageState::Active) {
+            return Ok(0);
+        }
+
+        let erased = page.erased_entry_count as usize;
+        let needs_copy = erased != ENTRIES_PER_PAGE;
+
+        if needs_copy {
+            // we need the reserve page as copy target
+            if self.free_pages.is_empty() {
+                return Ok(0);
+            }
+
+            let other_active = self
+                .pages
+                .iter()
+                .position(|it| it.header.state == ThinPageState::Active)
+                .filter(|&idx| idx != candidate);
+
+            let mut lost = 0;
+            if let Some(active) = other_active {
+                lost = self.pages[active].get_free_entry_count();
+                if erased <= lost {
+                    return Ok(0);
+                }
+
+                self.pages[active].mark_as_full(&mut self.hal)?;
+            }
+
+            // keep the order as the active page has to stay the last one
+            let page = self.pages.remove(candidate);
+            self.free_page(&page, next_sequence)?;
+            self.erase_page(page)?;
+
+            Ok(erased - lost)
+        } else {
+            let page = self.pages.remove(candidate);
+            self.erase_page(page)?;
+
+            Ok(erased)
+        }
+    }
+
     /// Quickly reclaim a page that has no valid entries
     pub(crate) fn erase_page(&mut self, page: ThinPage) -> Result<(), Error> {
         #[cfg(feature = "defmt")]
@@ -336,15 +656,78 @@ where
         println!("internal: erase_page");
 
         // Erase the page and add it to free_pages
-        self.hal
-            .erase(page.address as _, (page.address + FLASH_SECTOR_SIZE) as _)
-            .map_err(|_| Error::FlashError)?;
+        self.erase_sector(page.address)?;
 
+        self.hash_index.remove_page(&page);
         self.free_pages.push(ThinPage::uninitialized(page.address));
 
         Ok(())
     }
 
+    /// Erase a single sector and count it for [`Nvs::erase_counts`].
+    pub(crate) fn erase_sector(&mut self, address: usize) -> Result<(), Error> {
+        // a failed erase might still have worn the sector, so it is counted as well
+        self.erase_counts[(address - self.base_address) / FLASH_SECTOR_SIZE] += 1;
+
+        self.hal
+            .erase(address as _, (address + FLASH_SECTOR_SIZE) as _)
+            .map_err(|_| Error::FlashError)
+    }
+
+    /// Erase all free pages that aren't erased yet and return how many were erased.
+    pub(crate) fn erase_unerased_free_pages(&mut self) -> Result<usize, Error> {
+        #[cfg(feature = "defmt")]
+        trace!("erase_unerased_free_pages");
+
+        #[cfg(feature = "debug-logs")]
+        println!("internal: erase_unerased_free_pages");
+
+        // the heap is rebuilt afterwards as the order of the pages depends on their state
+        let mut pages = core::mem::take(&mut self.free_pages).into_vec();
+        let mut result = Ok(0);
+        for page in pages
+            .iter_mut()
+            .filter(|page| page.header.state != ThinPageState::Uninitialized)
+        {
+            if let Err(e) = self.erase_sector(page.address) {
+                result = Err(e);
+                break;
+            }
+            *page = ThinPage::uninitialized(page.address);
+            result = result.map(|count| count + 1);
+        }
+        self.free_pages = pages.into();
+
+        result
+    }
+
+    /// Erase every sector of the partition and initialize the first one as the active page
+    pub(crate) fn erase_all_pages(&mut self) -> Result<(), Error> {
+        #[cfg(feature = "defmt")]
+        trace!("erase_all_pages");
+
+        #[cfg(feature = "debug-logs")]
+        println!("internal: erase_all_pages");
+
+        self.pages.clear();
+        self.free_pages.clear();
+        self.namespaces.clear();
+        self.hash_index.clear();
+
+        for sector_idx in 0..self.sectors as usize {
+            let address = self.base_address + sector_idx * FLASH_SECTOR_SIZE;
+            self.erase_page(ThinPage::uninitialized(address))?;
+        }
+
+        // a partition with a single sector can't have an active page next to the reserve page
+        if self.free_pages.len() > self.reserved_pages as usize {
+            let page = self.get_active_page()?;
+            self.pages.push(page);
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn free_page(&mut self, source: &ThinPage, next_sequence: u32) -> Result<(), Error> {
         #[cfg(feature = "defmt")]
         trace!("free_page");
@@ -363,9 +746,7 @@ where
         // When free_page is called, we should always we have on page in reserve.
         let mut target = self.free_pages.pop().ok_or(Error::FlashFull)?;
         if target.header.state != ThinPageState::Uninitialized {
-            self.hal
-                .erase(target.address as _, (target.address + FLASH_SECTOR_SIZE) as _)
-                .map_err(|_| Error::FlashError)?;
+            self.erase_sector(target.address)?;
         }
         target.initialize(&mut self.hal, next_sequence)?;
 
@@ -389,6 +770,22 @@ where
             None => None,
         };
 
+        // the target might not hold a copy at all, e.g. if more than one page was left in the
+        // freeing state, in which case nothing must be skipped
+        if let Some(last) = &last_copied_entry {
+            let hash = last.calculate_hash(T::crc32);
+            let mut found = false;
+            for hash_entry in source.item_hash_list.iter().filter(|it| it.hash == hash) {
+                if source.load_item(&mut self.hal, hash_entry.index)? == *last {
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                last_copied_entry = None;
+            }
+        }
+
         let mut item_index = 0u8;
         while item_index < ENTRIES_PER_PAGE as u8 {
             if source.get_entry_state(item_index) != EntryMapState::Written {
@@ -424,6 +821,7 @@ where
                 | ItemType::BlobIndex => {
                     target.write_item::<T>(
                         &mut self.hal,
+                        &mut self.hash_index,
                         item.namespace_index,
                         item.key,
                         item.type_,
@@ -436,10 +834,11 @@ where
                         item.data,
                     )?;
                 }
-                ItemType::Sized | ItemType::BlobData => {
+                ItemType::Sized | ItemType::BlobData | ItemType::Blob => {
                     let data = source.load_referenced_data(&mut self.hal, item_index, &item)?;
                     target.write_variable_sized_item::<T>(
                         &mut self.hal,
+                        &mut self.hash_index,
                         item.namespace_index,
                         item.key,
                         item.type_,
@@ -451,9 +850,6 @@ where
                         &data,
                     )?;
                 }
-                ItemType::Blob => {
-                    // Old BLOB type - not supported, skip
-                }
                 ItemType::Any => {
                     // Should not happen
                 }
@@ -0,0 +1,231 @@
+//! Support for encrypted NVS partitions.
+//!
+//! ESP-IDF encrypts every 32 byte entry of a page with XTS-AES-256, using the entry's offset
+//! relative to the partition start as tweak. The page header and the entry state bitmap stay
+//! unencrypted, as do entries that were never written. The keys are stored in a separate
+//! `nvs_keys` partition, see [`NvsKeys::from_partition`].
+//!
+//! [`EncryptedPlatform`] wraps any [`Platform`] and transparently decrypts reads and encrypts
+//! writes, so the rest of the driver works exactly as for plain partitions.
+
+use alloc::vec::Vec;
+
+use embedded_storage::nor_flash::{
+    ErrorType,
+    NorFlash,
+    NorFlashError,
+    NorFlashErrorKind,
+    ReadNorFlash,
+};
+
+use crate::error::Error;
+use crate::platform::{
+    Crc,
+    Platform,
+    software_crc32,
+};
+use crate::xts::Xts;
+use crate::{
+    ENTRY_STATE_BITMAP_SIZE,
+    FLASH_SECTOR_SIZE,
+    ITEM_SIZE,
+    Nvs,
+    PAGE_HEADER_SIZE,
+};
+
+/// Size of a single XTS key, the `nvs_keys` partition contains two of them.
+pub const NVS_KEY_SIZE: usize = 32;
+
+/// Offset of the first entry within a page, everything before is stored unencrypted.
+const FIRST_ENTRY_OFFSET: usize = PAGE_HEADER_SIZE + ENTRY_STATE_BITMAP_SIZE;
+
+/// The XTS keyset used to encrypt an NVS partition, equivalent to ESP-IDF's `nvs_sec_cfg_t`.
+#[derive(Clone)]
+pub struct NvsKeys {
+    /// Key used to encrypt the data
+    pub eky: [u8; NVS_KEY_SIZE],
+    /// Key used to encrypt the tweak
+    pub tky: [u8; NVS_KEY_SIZE],
+}
+
+impl NvsKeys {
+    /// Create a keyset from the data encryption key and the tweak encryption key.
+    pub fn new(eky: [u8; NVS_KEY_SIZE], tky: [u8; NVS_KEY_SIZE]) -> Self {
+        Self { eky, tky }
+    }
+
+    /// Parse the content of an `nvs_keys` partition as written by ESP-IDF or the
+    /// `nvs_partition_gen.py` tool: both keys followed by a CRC32 over them.
+    ///
+    /// Returns [`Error::InvalidKeyPartition`] if the partition is too small, still erased or the
+    /// CRC doesn't match.
+    pub fn from_partition(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < 2 * NVS_KEY_SIZE + 4 {
+            return Err(Error::InvalidKeyPartition);
+        }
+
+        if data[..2 * NVS_KEY_SIZE].iter().all(|&b| b == 0xFF) {
+            return Err(Error::InvalidKeyPartition);
+        }
+
+        let crc = u32::from_le_bytes(data[2 * NVS_KEY_SIZE..2 * NVS_KEY_SIZE + 4].try_into().unwrap());
+        if crc != software_crc32(u32::MAX, &data[..2 * NVS_KEY_SIZE]) {
+            return Err(Error::InvalidKeyPartition);
+        }
+
+        Ok(Self {
+            eky: data[..NVS_KEY_SIZE].try_into().unwrap(),
+            tky: data[NVS_KEY_SIZE..2 * NVS_KEY_SIZE].try_into().unwrap(),
+        })
+    }
+
+    /// Serialize the keys in the `nvs_keys` partition format, e.g. to provision a device.
+    pub fn to_partition(&self) -> [u8; 2 * NVS_KEY_SIZE + 4] {
+        let mut buf = [0u8; 2 * NVS_KEY_SIZE + 4];
+        buf[..NVS_KEY_SIZE].copy_from_slice(&self.eky);
+        buf[NVS_KEY_SIZE..2 * NVS_KEY_SIZE].copy_from_slice(&self.tky);
+        let crc = software_crc32(u32::MAX, &buf[..2 * NVS_KEY_SIZE]);
+        buf[2 * NVS_KEY_SIZE..].copy_from_slice(&crc.to_le_bytes());
+        buf
+    }
+}
+
+/// Errors of the [`EncryptedPlatform`].
+#[derive(Debug)]
+pub enum EncryptedFlashError<E> {
+    /// Error of the wrapped flash
+    Flash(E),
+    /// Entries can only be encrypted as a whole, so all accesses have to be entry aligned
+    NotAligned,
+}
+
+impl<E: NorFlashError> NorFlashError for EncryptedFlashError<E> {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            EncryptedFlashError::Flash(e) => e.kind(),
+            EncryptedFlashError::NotAligned => NorFlashErrorKind::NotAligned,
+        }
+    }
+}
+
+/// [`Platform`] adapter that encrypts and decrypts entries like ESP-IDF's encrypted NVS.
+///
+/// Reads and writes have to be aligned to whole entries which is guaranteed by announcing
+/// [`ITEM_SIZE`] as `READ_SIZE` and `WRITE_SIZE`. The wrapped platform's sizes must divide it.
+pub struct EncryptedPlatform<T> {
+    hal: T,
+    partition_offset: usize,
+    xts: Xts,
+}
+
+impl<T: Platform> EncryptedPlatform<T> {
+    /// Wrap the given platform. `partition_offset` has to match the offset passed to
+    /// [`Nvs::new`] as ESP-IDF derives the tweak from the partition relative address.
+    pub fn new(hal: T, partition_offset: usize, keys: &NvsKeys) -> Self {
+        Self {
+            hal,
+            partition_offset,
+            xts: Xts::new(&keys.eky, &keys.tky),
+        }
+    }
+
+    /// Consume the adapter and return the wrapped platform.
+    pub fn into_inner(self) -> T {
+        self.hal
+    }
+
+    /// Calls `f` for every encrypted entry in `buf` that was read from or is written to `offset`
+    fn for_each_entry(
+        &self,
+        offset: u32,
+        buf: &mut [u8],
+        f: impl Fn(&Xts, &mut [u8], [u8; 16]),
+    ) -> Result<(), EncryptedFlashError<T::Error>> {
+        let relative = (offset as usize)
+            .checked_sub(self.partition_offset)
+            .ok_or(EncryptedFlashError::NotAligned)?;
+        if !relative.is_multiple_of(ITEM_SIZE) || !buf.len().is_multiple_of(ITEM_SIZE) {
+            return Err(EncryptedFlashError::NotAligned);
+        }
+
+        for (idx, entry) in buf.chunks_exact_mut(ITEM_SIZE).enumerate() {
+            let address = relative + idx * ITEM_SIZE;
+            if address % FLASH_SECTOR_SIZE < FIRST_ENTRY_OFFSET {
+                continue;
+            }
+
+            let mut tweak = [0u8; 16];
+            tweak[..4].copy_from_slice(&(address as u32).to_le_bytes());
+            f(&self.xts, entry, tweak);
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: Platform> ErrorType for EncryptedPlatform<T> {
+    type Error = EncryptedFlashError<T::Error>;
+}
+
+impl<T: Platform> ReadNorFlash for EncryptedPlatform<T> {
+    const READ_SIZE: usize = ITEM_SIZE;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        self.hal.read(offset, bytes).map_err(EncryptedFlashError::Flash)?;
+
+        self.for_each_entry(offset, bytes, |xts, entry, tweak| {
+            // unwritten entries are not encrypted
+            if entry.iter().any(|&b| b != 0xFF) {
+                xts.decrypt(entry, tweak);
+            }
+        })
+    }
+
+    fn capacity(&self) -> usize {
+        self.hal.capacity()
+    }
+}
+
+impl<T: Platform> NorFlash for EncryptedPlatform<T> {
+    const WRITE_SIZE: usize = ITEM_SIZE;
+    const ERASE_SIZE: usize = T::ERASE_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        self.hal.erase(from, to).map_err(EncryptedFlashError::Flash)
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        let mut buf = Vec::from(bytes);
+        self.for_each_entry(offset, &mut buf, |xts, entry, tweak| xts.encrypt(entry, tweak))?;
+
+        self.hal.write(offset, &buf).map_err(EncryptedFlashError::Flash)
+    }
+}
+
+impl<T: Crc> Crc for EncryptedPlatform<T> {
+    fn crc32(init: u32, data: &[u8]) -> u32 {
+        T::crc32(init, data)
+    }
+}
+
+/// An [`Nvs`] instance on top of an encrypted partition.
+pub type EncryptedNvs<T> = Nvs<EncryptedPlatform<T>>;
+
+impl<T: Platform> Nvs<EncryptedPlatform<T>> {
+    /// Same as [`Nvs::new`] for a partition encrypted with the given keys.
+    ///
+    /// With the wrong keys all entries fail their CRC check and are erased during the cleanup.
+    /// Use [`Nvs::new_read_only`] together with [`EncryptedPlatform`] to check the keys first.
+    pub fn new_encrypted(
+        partition_offset: usize,
+        partition_size: usize,
+        hal: T,
+        keys: &NvsKeys,
+    ) -> Result<EncryptedNvs<T>, Error> {
+        Nvs::new(
+            partition_offset,
+            partition_size,
+            EncryptedPlatform::new(hal, partition_offset, keys),
+        )
+    }
+}
@@ -19,6 +19,15 @@ pub enum Error {
     #[error("invalid partition size")]
     InvalidPartitionSize,
 
+    /// The partition has more than `u16::MAX` sectors, i.e. the supported maximum is 65535 * 4k
+    /// bytes (just under 256 MiB)
+    #[error("partition too large")]
+    PartitionTooLarge,
+
+    /// The partition ends behind the capacity reported by the flash
+    #[error("partition out of bounds")]
+    PartitionOutOfBounds,
+
     /// The internal error value is returned from the provided `&mut impl flash::Flash`
     #[error("internal flash error")]
     FlashError,
@@ -36,8 +45,18 @@ pub enum Error {
     #[error("namespace malformed")]
     NamespaceMalformed,
 
-    /// Strings are limited to `MAX_BLOB_DATA_PER_PAGE` while blobs can be up to `MAX_BLOB_SIZE`
-    /// bytes
+    /// The namespace index is 0, already used by another namespace or the namespace already exists
+    /// with a different index.
+    #[error("invalid namespace index")]
+    InvalidNamespaceIndex,
+
+    /// The chunk start of a blob has to be 0 or 128 and differ from the one of the blob it
+    /// replaces.
+    #[error("invalid chunk start")]
+    InvalidChunkStart,
+
+    /// Strings must be shorter than `MAX_BLOB_DATA_PER_PAGE` and blobs smaller than `MAX_BLOB_SIZE`
+    /// bytes, or less if the partition is too small, see `Nvs::max_blob_size`
     #[error("value too long")]
     ValueTooLong,
 
@@ -58,14 +77,33 @@ pub enum Error {
     #[error("item type mismatch: {0}")]
     ItemTypeMismatch(ItemType),
 
+    /// The buffer passed to [`crate::Nvs::get_str_into`] is too small, the required length is
+    /// reported.
+    #[error("buffer too small, {0} bytes required")]
+    BufferTooSmall(usize),
+
     /// Blob data is corrupted or inconsistent
     #[error("corrupted data")]
     CorruptedData,
 
-    /// Flash is full and defragmentation doesn't help.
+    /// Flash is full and defragmentation doesn't help. Blobs are rejected with this up front if
+    /// they don't fit next to the stored values, even if all erased entries were reclaimed.
     #[error("flash full")]
     FlashFull,
 
+    /// The partition was opened with [`crate::Nvs::new_read_only`] and can't be modified.
+    #[error("read only")]
+    ReadOnly,
+
+    /// At least one page has to stay in reserve for the page reclamation and one page has to be
+    /// left for the data, see [`crate::Nvs::set_reserved_pages`].
+    #[error("invalid number of reserved pages")]
+    InvalidReservedPages,
+
+    /// The content of the `nvs_keys` partition is erased or its CRC doesn't match.
+    #[error("invalid key partition")]
+    InvalidKeyPartition,
+
     /// Used internally to indicate that we have to allocate a new page.
     #[error("page full")]
     PageFull,
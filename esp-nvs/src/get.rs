@@ -4,11 +4,13 @@
 use alloc::string::String;
 use alloc::vec::Vec;
 
+use crate::array::ArrayElement;
 use crate::error::Error;
 use crate::platform::Platform;
 use crate::{
     Key,
     Nvs,
+    array,
     raw,
 };
 
@@ -96,3 +98,11 @@ impl<T: Platform> Get<Vec<u8>> for Nvs<T> {
         self.get_blob(namespace, key)
     }
 }
+
+/// Reads a typed array written by [`Set<&[E]>`], see [`ArrayElement`] for the layout.
+impl<T: Platform, E: ArrayElement> Get<Vec<E>> for Nvs<T> {
+    fn get(&mut self, namespace: &Key, key: &Key) -> Result<Vec<E>, Error> {
+        let blob = self.get_blob(namespace, key)?;
+        array::decode(&blob)
+    }
+}
@@ -0,0 +1,53 @@
+//! Global index of the item hashes of all pages.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::page::ThinPage;
+use crate::u24::u24;
+
+/// Maps item hashes to the items with that hash, given by the address of their page and their
+/// index within it, so lookups only load those items instead of scanning the hash lists of all
+/// pages.
+///
+/// The hash lists of the pages stay the source of truth. Addresses are used instead of indices
+/// into `Nvs::pages` as the pages are reordered all the time. An item is added whenever it is
+/// loaded or written and removed once it is erased.
+#[derive(Default)]
+pub(crate) struct HashIndex {
+    items: BTreeMap<u24, Vec<(usize, u8)>>,
+}
+
+impl HashIndex {
+    pub(crate) fn insert(&mut self, hash: u24, address: usize, index: u8) {
+        let items = self.items.entry(hash).or_default();
+        if !items.contains(&(address, index)) {
+            items.push((address, index));
+        }
+    }
+
+    pub(crate) fn remove(&mut self, hash: u24, address: usize, index: u8) {
+        if let Some(items) = self.items.get_mut(&hash) {
+            items.retain(|it| *it != (address, index));
+            if items.is_empty() {
+                self.items.remove(&hash);
+            }
+        }
+    }
+
+    /// Remove all items of a page that is about to be erased.
+    pub(crate) fn remove_page(&mut self, page: &ThinPage) {
+        for entry in &page.item_hash_list {
+            self.remove(entry.hash, page.address, entry.index);
+        }
+    }
+
+    /// The page addresses and indices of the items with the given hash.
+    pub(crate) fn items(&self, hash: u24) -> &[(usize, u8)] {
+        self.items.get(&hash).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.items.clear();
+    }
+}
@@ -3,10 +3,14 @@
 //! This module contains the logic for reading flash sectors and initializing
 //! the in-memory page structures during [`Nvs`](crate::Nvs) startup.
 
+use alloc::collections::BTreeSet;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::mem::size_of;
-use core::ops::Not;
+use core::ops::{
+    Not,
+    Range,
+};
 
 #[cfg(feature = "defmt")]
 use defmt::trace;
@@ -27,11 +31,10 @@ use crate::page::{
     ThinPageState,
 };
 use crate::platform::Platform;
-#[cfg(feature = "debug-logs")]
-use crate::raw::slice_with_nullbytes_to_str;
 use crate::raw::{
     EntryMapState,
     FLASH_SECTOR_SIZE,
+    Item,
     ItemType,
     PageHeader,
     RawPage,
@@ -53,13 +56,15 @@ where
         println!("internal: load_sectors");
 
         let mut blob_index = BlobIndex::new();
+        let mut legacy_blobs = BTreeSet::new();
         let sectors = self.sectors as usize;
         for sector_idx in 0..sectors {
             let sector_addr = self.base_address + sector_idx * FLASH_SECTOR_SIZE;
             match self.load_sector(sector_addr)? {
                 LoadPageResult::Empty(page) => self.free_pages.push(page),
-                LoadPageResult::Used(page, new_namespaces, new_blob_index) => {
+                LoadPageResult::Used(page, new_namespaces, new_blob_index, new_legacy_blobs) => {
                     self.pages.push(page);
+                    legacy_blobs.extend(new_legacy_blobs);
                     new_namespaces.into_iter().for_each(|ns| {
                         self.namespaces.insert(ns.name, ns.index);
                     });
@@ -86,14 +91,23 @@ where
 
         self.ensure_active_page_order()?;
 
+        // Everything below modifies the flash. In read-only mode duplicates and leftovers are
+        // resolved during lookups instead.
+        if self.read_only {
+            return Ok(());
+        }
+
         self.continue_free_page()?;
 
         // After loading all pages, check for duplicate primitive/string entries and mark older ones
         // as erased This handles cases where deletion failed after a successful write
-        self.cleanup_duplicate_entries()?;
+        self.cleanup_duplicate_entries(&mut blob_index)?;
 
         self.cleanup_dirty_blobs(blob_index)?;
 
+        #[cfg(feature = "convert-legacy-blobs")]
+        self.convert_legacy_blobs(legacy_blobs)?;
+
         Ok(())
     }
 
@@ -116,6 +130,13 @@ where
             return Ok(LoadPageResult::Empty(ThinPage::uninitialized(sector_address)));
         }
 
+        if buf.iter().all(|&b| b == 0x00) {
+            #[cfg(feature = "debug-logs")]
+            println!("  raw: load page: 0x{sector_address:04X} -> blank");
+
+            return Ok(LoadPageResult::Empty(ThinPage::blank(sector_address)));
+        }
+
         // Safety: either we return directly CORRUPT/INVALID/EMPTY page or we check the crc
         // afterwards
         let raw_page: RawPage = unsafe { core::mem::transmute(buf) };
@@ -136,7 +157,7 @@ where
         };
 
         match page.header.state {
-            ThinPageState::Corrupt | ThinPageState::Invalid => {
+            ThinPageState::Corrupt | ThinPageState::Invalid | ThinPageState::Blank => {
                 return Ok(LoadPageResult::Empty(page));
             }
             ThinPageState::Uninitialized => {
@@ -161,13 +182,21 @@ where
 
         // Needed due to the desugaring below
         let mut namespaces: Vec<Namespace> = vec![];
+        let mut legacy_blobs = vec![];
         // This iterator desugaring is necessary to be able to skip entries, e.g. a BLOB or STR
         // entries are followed by entries containing their raw value.
         let items = &raw_page.items;
-        let mut item_iter = unsafe { items.entries.iter().zip(u8::MIN..u8::MAX) };
-        'item_iter: while let Some((item, item_index)) = item_iter.next() {
+        let mut item_iter = unsafe { items.raw.chunks_exact(size_of::<Item>()).zip(u8::MIN..u8::MAX) };
+        'item_iter: while let Some((raw_item, item_index)) = item_iter.next() {
+            // entries of an unknown type or with a span that doesn't fit the page are treated
+            // like entries with a CRC mismatch
+            let parse_item = || {
+                Item::from_raw(raw_item.try_into().unwrap())
+                    .filter(|item| item.crc == item.calculate_crc32(T::crc32) && item.span_is_valid(item_index))
+            };
+
             let state = page.get_entry_state(item_index);
-            match state {
+            let item = match state {
                 EntryMapState::Illegal => {
                     page.erased_entry_count += 1;
                     continue 'item_iter;
@@ -178,78 +207,70 @@ where
                 }
                 EntryMapState::Empty => {
                     // maybe data was written but the map was not updated yet
-                    let calculated_crc = item.calculate_crc32(T::crc32);
-                    if item.crc == calculated_crc && item.type_ != ItemType::Any && item.span != u8::MAX {
-                        match item.type_ {
-                            ItemType::U8
-                            | ItemType::I8
-                            | ItemType::U16
-                            | ItemType::I16
-                            | ItemType::U32
-                            | ItemType::I32
-                            | ItemType::U64
-                            | ItemType::I64
-                            | ItemType::BlobIndex => {
-                                #[cfg(feature = "debug-logs")]
-                                println!("encountered valid but empty scalar item at {item_index}");
-                                page.set_entry_state(&mut self.hal, item_index as _, EntryMapState::Written)?;
-                                page.used_entry_count += 1;
-                            }
-                            ItemType::Blob => {
-                                // TODO: should we just ignore this value or mark page corrupt?
-                                //  Alternatively, we could add support for BLOB_V1 and convert it
-                                // here
-                                page.used_entry_count += 1;
-                                continue 'item_iter;
-                            }
-                            ItemType::Sized | ItemType::BlobData => {
-                                #[cfg(feature = "debug-logs")]
-                                println!("encountered valid but EMPTY variable sized item at {item_index}");
-                                let data = page.load_referenced_data(&mut self.hal, item_index, item)?;
-                                let data_crc = T::crc32(u32::MAX, &data);
-                                if data_crc != unsafe { item.data.sized.crc } {
-                                    page.set_entry_state_range(
-                                        &mut self.hal,
-                                        item_index..item_index + item.span,
-                                        EntryMapState::Erased,
-                                    )?;
-                                    page.erased_entry_count += item.span;
-                                    continue 'item_iter;
-                                }
-                                page.set_entry_state_range(
-                                    &mut self.hal,
+                    let Some(item) = parse_item().filter(|item| item.type_ != ItemType::Any) else {
+                        continue 'item_iter;
+                    };
+                    match item.type_ {
+                        ItemType::U8
+                        | ItemType::I8
+                        | ItemType::U16
+                        | ItemType::I16
+                        | ItemType::U32
+                        | ItemType::I32
+                        | ItemType::U64
+                        | ItemType::I64
+                        | ItemType::BlobIndex => {
+                            #[cfg(feature = "debug-logs")]
+                            println!("encountered valid but empty scalar item at {item_index}");
+                            self.restore_entry_state(&mut page, item_index..item_index + 1, EntryMapState::Written)?;
+                            page.used_entry_count += 1;
+                        }
+                        ItemType::Blob => {
+                            // TODO: should we just ignore this value or mark page corrupt?
+                            page.used_entry_count += 1;
+                            continue 'item_iter;
+                        }
+                        ItemType::Sized | ItemType::BlobData => {
+                            #[cfg(feature = "debug-logs")]
+                            println!("encountered valid but EMPTY variable sized item at {item_index}");
+                            let data = page.load_referenced_data(&mut self.hal, item_index, &item)?;
+                            let data_crc = T::crc32(u32::MAX, &data);
+                            if data_crc != unsafe { item.data.sized.crc } {
+                                self.restore_entry_state(
+                                    &mut page,
                                     item_index..item_index + item.span,
-                                    EntryMapState::Written,
+                                    EntryMapState::Erased,
                                 )?;
-                                page.used_entry_count += item.span;
-                            }
-                            ItemType::Any => {
+                                page.erased_entry_count += item.span;
                                 continue 'item_iter;
                             }
+                            self.restore_entry_state(
+                                &mut page,
+                                item_index..item_index + item.span,
+                                EntryMapState::Written,
+                            )?;
+                            page.used_entry_count += item.span;
+                        }
+                        ItemType::Any => {
+                            continue 'item_iter;
                         }
-                    } else {
-                        continue 'item_iter;
                     }
+                    item
                 }
                 EntryMapState::Written => {
-                    let calculated_crc = item.calculate_crc32(T::crc32);
-                    if item.crc != calculated_crc {
+                    let Some(item) = parse_item() else {
                         #[cfg(feature = "debug-logs")]
-                        println!(
-                            "CRC mismatch for item '{}', marking as erased",
-                            slice_with_nullbytes_to_str(&item.key.0)
-                        );
-                        page.set_entry_state_range(
-                            &mut self.hal,
-                            item_index..(item_index + item.span),
-                            EntryMapState::Erased,
-                        )?;
-                        page.erased_entry_count += item.span;
+                        println!("corrupted item at {item_index}, marking as erased");
+                        // the span can't be trusted either, so like the C++ driver only this
+                        // entry is erased. Its data entries fail the CRC check on their own.
+                        self.restore_entry_state(&mut page, item_index..item_index + 1, EntryMapState::Erased)?;
+                        page.erased_entry_count += 1;
                         continue 'item_iter;
-                    }
+                    };
                     page.used_entry_count += item.span;
+                    item
                 }
-            }
+            };
 
             // Continue for valid WRITTEN and formerly EMPTY entries
             #[cfg(feature = "debug-logs")]
@@ -263,6 +284,10 @@ where
                 continue 'item_iter;
             }
 
+            if item.type_ == ItemType::Blob {
+                legacy_blobs.push((item.namespace_index, item.key));
+            }
+
             if item.type_ == ItemType::BlobIndex || item.type_ == ItemType::BlobData {
                 let chunk_start = if item.type_ == ItemType::BlobIndex {
                     unsafe { VersionOffset::from(item.data.blob_index.chunk_start) }
@@ -332,10 +357,12 @@ where
                 }
             }
 
+            let hash = item.calculate_hash(T::crc32);
             page.item_hash_list.push(ItemHashListEntry {
-                hash: item.calculate_hash(T::crc32),
+                hash,
                 index: item_index,
             });
+            self.hash_index.insert(hash, page.address, item_index);
 
             // skip following items containing raw data
             if item.span >= 2 {
@@ -346,6 +373,22 @@ where
         #[cfg(feature = "debug-logs")]
         println!("PGE {page:?}");
 
-        Ok(LoadPageResult::Used(page, namespaces, blob_index))
+        Ok(LoadPageResult::Used(page, namespaces, blob_index, legacy_blobs))
+    }
+
+    /// Fixes up the state of entries whose bitmap doesn't match their content. In read-only mode
+    /// only the in-memory copy of the bitmap is updated.
+    fn restore_entry_state(
+        &mut self,
+        page: &mut ThinPage,
+        indices: Range<u8>,
+        state: EntryMapState,
+    ) -> Result<(), Error> {
+        if self.read_only {
+            page.update_entry_state_range(indices, state);
+            Ok(())
+        } else {
+            page.set_entry_state_range(&mut self.hal, indices, state)
+        }
     }
 }
@@ -26,6 +26,7 @@ use crate::page::{
 };
 use crate::platform::Platform;
 use crate::raw::{
+    ENTRIES_PER_PAGE,
     Item,
     ItemData,
     ItemDataBlobIndex,
@@ -39,9 +40,10 @@ use crate::types::{
     PageIndex,
     VersionOffset,
 };
+use crate::u24::u24;
 use crate::{
+    EntryType,
     Key,
-    MAX_KEY_LENGTH,
     Nvs,
     raw,
 };
@@ -57,10 +59,10 @@ where
         #[cfg(feature = "debug-logs")]
         println!("internal: get_primitive");
 
-        if key.0[MAX_KEY_LENGTH] != b'\0' {
+        if !key.is_well_formed() {
             return Err(Error::KeyMalformed);
         }
-        if namespace.0[MAX_KEY_LENGTH] != b'\0' {
+        if !namespace.is_well_formed() {
             return Err(Error::NamespaceMalformed);
         }
 
@@ -81,10 +83,10 @@ where
         #[cfg(feature = "debug-logs")]
         println!("internal: get_string");
 
-        if key.0[MAX_KEY_LENGTH] != b'\0' {
+        if !key.is_well_formed() {
             return Err(Error::KeyMalformed);
         }
-        if namespace.0[MAX_KEY_LENGTH] != b'\0' {
+        if !namespace.is_well_formed() {
             return Err(Error::NamespaceMalformed);
         }
 
@@ -108,6 +110,69 @@ where
         Ok(str.to_string())
     }
 
+    pub(crate) fn load_str_into<'b>(
+        &mut self,
+        namespace: &Key,
+        key: &Key,
+        buf: &'b mut [u8],
+    ) -> Result<&'b str, Error> {
+        #[cfg(feature = "defmt")]
+        trace!("load_str_into");
+
+        #[cfg(feature = "debug-logs")]
+        println!("internal: load_str_into");
+
+        if !key.is_well_formed() {
+            return Err(Error::KeyMalformed);
+        }
+        if !namespace.is_well_formed() {
+            return Err(Error::NamespaceMalformed);
+        }
+
+        let namespace_index = *self.namespaces.get(namespace).ok_or(Error::NamespaceNotFound)?;
+
+        let (page_index, item_index, item) = self.load_item(namespace_index, ChunkIndex::Any, key)?;
+
+        if item.type_ != ItemType::Sized {
+            return Err(ItemTypeMismatch(item.type_));
+        }
+
+        let page = &self.pages[page_index.0];
+        let data = page.load_referenced_data_into(&mut self.hal, item_index.0, &item, buf)?;
+
+        let crc = unsafe { item.data.sized.crc };
+        if crc != T::crc32(u32::MAX, data) {
+            return Err(Error::KeyNotFound);
+        }
+
+        // we don't want the null terminator
+        let Some((_, str)) = data.split_last() else {
+            return Err(Error::CorruptedData);
+        };
+        core::str::from_utf8(str).map_err(|_| Error::CorruptedData)
+    }
+
+    /// Reads the string into `self.read_buffer` and returns its length without the terminator.
+    pub(crate) fn load_str_to_buffer(&mut self, namespace: &Key, key: &Key) -> Result<usize, Error> {
+        #[cfg(feature = "defmt")]
+        trace!("load_str_to_buffer");
+
+        #[cfg(feature = "debug-logs")]
+        println!("internal: load_str_to_buffer");
+
+        // the buffer is taken out so it can be filled while `self` is borrowed mutably
+        let mut buf = core::mem::take(&mut self.read_buffer);
+        let len = match self.load_str_into(namespace, key, &mut buf) {
+            Err(Error::BufferTooSmall(size)) => {
+                buf.resize(size, 0);
+                self.load_str_into(namespace, key, &mut buf).map(str::len)
+            }
+            result => result.map(str::len),
+        };
+        self.read_buffer = buf;
+        len
+    }
+
     pub(crate) fn get_blob(&mut self, namespace: &Key, key: &Key) -> Result<Vec<u8>, Error> {
         #[cfg(feature = "defmt")]
         trace!("get_blob");
@@ -115,10 +180,10 @@ where
         #[cfg(feature = "debug-logs")]
         println!("internal: get_blob");
 
-        if key.0[MAX_KEY_LENGTH] != b'\0' {
+        if !key.is_well_formed() {
             return Err(Error::KeyMalformed);
         }
-        if namespace.0[MAX_KEY_LENGTH] != b'\0' {
+        if !namespace.is_well_formed() {
             return Err(Error::NamespaceMalformed);
         }
 
@@ -182,6 +247,89 @@ where
         }
     }
 
+    pub(crate) fn load_raw(&mut self, namespace: &Key, key: &Key) -> Result<(EntryType, Vec<u8>), Error> {
+        #[cfg(feature = "defmt")]
+        trace!("load_raw");
+
+        #[cfg(feature = "debug-logs")]
+        println!("internal: load_raw");
+
+        if !key.is_well_formed() {
+            return Err(Error::KeyMalformed);
+        }
+        if !namespace.is_well_formed() {
+            return Err(Error::NamespaceMalformed);
+        }
+
+        let namespace_index = *self.namespaces.get(namespace).ok_or(Error::NamespaceNotFound)?;
+
+        let (page_index, item_index, item) = self.load_item(namespace_index, ChunkIndex::Any, key)?;
+        let entry_type = EntryType::try_from(item.type_)?;
+
+        match item.type_ {
+            ItemType::Sized => {
+                let page = &self.pages[page_index.0];
+                let data = page.load_referenced_data(&mut self.hal, item_index.0, &item)?;
+
+                let crc = unsafe { item.data.sized.crc };
+                if crc != T::crc32(u32::MAX, &data) {
+                    return Err(Error::KeyNotFound);
+                }
+                Ok((entry_type, data))
+            }
+            ItemType::BlobIndex | ItemType::Blob => Ok((entry_type, self.get_blob(namespace, key)?)),
+            _ => Ok((entry_type, unsafe { item.data.raw }.to_vec())),
+        }
+    }
+
+    pub(crate) fn load_blob_chunk_start(&mut self, namespace: &Key, key: &Key) -> Result<u8, Error> {
+        #[cfg(feature = "defmt")]
+        trace!("load_blob_chunk_start");
+
+        #[cfg(feature = "debug-logs")]
+        println!("internal: load_blob_chunk_start");
+
+        if !key.is_well_formed() {
+            return Err(Error::KeyMalformed);
+        }
+        if !namespace.is_well_formed() {
+            return Err(Error::NamespaceMalformed);
+        }
+
+        let namespace_index = *self.namespaces.get(namespace).ok_or(Error::NamespaceNotFound)?;
+
+        let (_, _, item) = self.load_item(namespace_index, ChunkIndex::Any, key)?;
+        match item.type_ {
+            ItemType::BlobIndex => Ok(unsafe { item.data.blob_index.chunk_start }),
+            type_ => Err(Error::ItemTypeMismatch(type_)),
+        }
+    }
+
+    pub(crate) fn contains_key(&mut self, namespace: &Key, key: &Key) -> Result<bool, Error> {
+        #[cfg(feature = "defmt")]
+        trace!("contains_key");
+
+        #[cfg(feature = "debug-logs")]
+        println!("internal: contains_key");
+
+        if !key.is_well_formed() {
+            return Err(Error::KeyMalformed);
+        }
+        if !namespace.is_well_formed() {
+            return Err(Error::NamespaceMalformed);
+        }
+
+        let Some(&namespace_index) = self.namespaces.get(namespace) else {
+            return Ok(false);
+        };
+
+        match self.load_item(namespace_index, ChunkIndex::Any, key) {
+            Ok(_) => Ok(true),
+            Err(Error::KeyNotFound) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
     pub(crate) fn delete_key(&mut self, namespace_index: u8, key: &Key, chunk_index: ChunkIndex) -> Result<(), Error> {
         #[cfg(feature = "defmt")]
         trace!("delete_key");
@@ -193,7 +341,7 @@ where
 
         let page = self.pages.get_mut(page_index.0).unwrap();
 
-        page.erase_item::<T>(&mut self.hal, item_index.0, item.span)?;
+        page.erase_item::<T>(&mut self.hal, &mut self.hash_index, item_index.0, item.span)?;
 
         // If we deleted a BLOB_IDX we need to delete all associated BLOB_DATA entries
         if item.type_ == ItemType::BlobIndex {
@@ -324,10 +472,10 @@ where
         #[cfg(feature = "debug-logs")]
         println!("internal: set_primitive");
 
-        if key.0[MAX_KEY_LENGTH] != b'\0' {
+        if !key.is_well_formed() {
             return Err(Error::KeyMalformed);
         }
-        if namespace.0[MAX_KEY_LENGTH] != b'\0' {
+        if !namespace.is_well_formed() {
             return Err(Error::NamespaceMalformed);
         }
 
@@ -347,8 +495,15 @@ where
         // the active page needs to be in the vec for it to be considered by load_item()
         self.pages.push(page);
 
-        let old_entry_location =
-            if let Ok((page_index, item_index, item)) = self.load_item(namespace_index, ChunkIndex::Any, &key) {
+        let mut replaces_other_type = false;
+        let old_entry_location = match self.load_item(namespace_index, ChunkIndex::Any, &key) {
+            // an item of another type might span multiple entries or be a blob, it is deleted
+            // with all of its data once the new item is written
+            Ok((_, _, item)) if item.type_ != type_ => {
+                replaces_other_type = true;
+                None
+            }
+            Ok((page_index, item_index, item)) => {
                 if unsafe { item.data.raw } == raw_value {
                     #[cfg(feature = "debug-logs")]
                     println!("internal: set_primitive: entry already exists and matches");
@@ -359,15 +514,16 @@ where
                 println!("internal: set_primitive: entry already exists and needs to be removed");
 
                 Some((page_index, item_index))
-            } else {
-                None
-            };
+            }
+            Err(_) => None,
+        };
 
         // safe since we just pushed before
         page = self.pages.pop().unwrap();
 
         page.write_item::<T>(
             &mut self.hal,
+            &mut self.hash_index,
             namespace_index,
             key,
             type_,
@@ -384,7 +540,105 @@ where
             // page_index might only change on defragmentation when load_active_page()
             // is called after we got it
             let old_page = self.pages.get_mut(page_index.0).unwrap();
-            old_page.erase_item(&mut self.hal, item_index.0, 1)?;
+            old_page.erase_item(&mut self.hal, &mut self.hash_index, item_index.0, 1)?;
+        }
+
+        // the old item comes first, as it is either on an older page or before the new one
+        if replaces_other_type {
+            self.delete_key(namespace_index, &key, ChunkIndex::Any)?;
+        }
+
+        Ok(())
+    }
+
+    /// Set multiple primitives like [`Nvs::set_primitive`], but write as many of them as fit into
+    /// the active page as consecutive entries with a single write, followed by a single update of
+    /// the entry state bitmap. The old items are erased after each page.
+    ///
+    /// Keys must be unique and well-formed.
+    pub(crate) fn set_primitives(&mut self, values: &[(Key, Key, ItemType, u64)]) -> Result<(), Error> {
+        #[cfg(feature = "defmt")]
+        trace!("set_primitives");
+
+        #[cfg(feature = "debug-logs")]
+        println!("internal: set_primitives");
+
+        // the namespace entries would split the runs of entries
+        for (namespace, ..) in values {
+            if !self.namespaces.contains_key(namespace) {
+                let mut page = self.get_active_page()?;
+                let result = self.get_or_create_namespace(namespace, &mut page);
+                let result = result.and_then(|_| match page.is_full() {
+                    true => page.mark_as_full(&mut self.hal),
+                    false => Ok(()),
+                });
+                self.pages.push(page);
+                result?;
+            }
+        }
+
+        let mut values = values;
+        while !values.is_empty() {
+            // the active page needs to be in the vec for it to be considered by load_item(), the
+            // found page indices stay valid until the run is written
+            let page = self.get_active_page()?;
+            let free_entries = page.get_free_entry_count();
+            self.pages.push(page);
+
+            let mut items = Vec::new();
+            let mut old_entry_locations = Vec::new();
+            let mut replaced_other_types = Vec::new();
+            let mut consumed = 0;
+            for (namespace, key, type_, value) in values {
+                if items.len() == free_entries {
+                    break;
+                }
+                consumed += 1;
+
+                let width = type_.get_primitive_bytes_width()?;
+                let mut raw_value = [0xFF; 8];
+                raw_value[..width].copy_from_slice(&value.to_le_bytes()[..width]);
+
+                let namespace_index = self.namespaces[namespace];
+                match self.load_item(namespace_index, ChunkIndex::Any, key) {
+                    Ok((_, _, item)) if item.type_ != *type_ => replaced_other_types.push((namespace_index, *key)),
+                    Ok((_, _, item)) if unsafe { item.data.raw } == raw_value => continue,
+                    Ok((page_index, item_index, _)) => old_entry_locations.push((page_index, item_index)),
+                    Err(_) => {}
+                }
+
+                let mut item = Item {
+                    namespace_index,
+                    type_: *type_,
+                    span: 1,
+                    chunk_index: u8::MAX,
+                    crc: 0,
+                    key: *key,
+                    data: ItemData { raw: raw_value },
+                };
+                item.crc = item.calculate_crc32(T::crc32);
+                items.push(item);
+            }
+            values = &values[consumed..];
+
+            // safe since we just pushed before
+            let mut page = self.pages.pop().unwrap();
+            let result = match items.is_empty() {
+                true => Ok(()),
+                false => page.write_items(&mut self.hal, &mut self.hash_index, &items),
+            };
+            self.pages.push(page);
+            result?;
+
+            for (page_index, item_index) in old_entry_locations {
+                let old_page = self.pages.get_mut(page_index.0).unwrap();
+                old_page.erase_item(&mut self.hal, &mut self.hash_index, item_index.0, 1)?;
+            }
+
+            // the old items come first, as they are either on older pages or before the new ones
+            for (namespace_index, key) in replaced_other_types {
+                self.delete_key(namespace_index, &key, ChunkIndex::Any)?;
+            }
         }
 
         Ok(())
@@ -397,38 +651,51 @@ where
         #[cfg(feature = "debug-logs")]
         println!("internal: set_str");
 
-        if key.0[MAX_KEY_LENGTH] != b'\0' {
+        let mut buf = Vec::with_capacity(value.len() + 1);
+        buf.extend_from_slice(value.as_bytes());
+        buf.push(b'\0');
+
+        self.set_sized(namespace, key, ItemType::Sized, buf)
+    }
+
+    pub(crate) fn set_legacy_blob(&mut self, namespace: &Key, key: Key, data: &[u8]) -> Result<(), Error> {
+        #[cfg(feature = "defmt")]
+        trace!("set_legacy_blob");
+
+        #[cfg(feature = "debug-logs")]
+        println!("internal: set_legacy_blob");
+
+        self.set_sized(namespace, key, ItemType::Blob, data.to_vec())
+    }
+
+    /// Write `buf` as a single variable sized item, used for strings and legacy blobs.
+    fn set_sized(&mut self, namespace: &Key, key: Key, type_: ItemType, buf: Vec<u8>) -> Result<(), Error> {
+        if !key.is_well_formed() {
             return Err(Error::KeyMalformed);
         }
-        if namespace.0[MAX_KEY_LENGTH] != b'\0' {
+        if !namespace.is_well_formed() {
             return Err(Error::NamespaceMalformed);
         }
 
-        if value.len() + 1 > MAX_BLOB_DATA_PER_PAGE {
+        if buf.len() > MAX_BLOB_DATA_PER_PAGE {
             return Err(Error::ValueTooLong);
         }
 
-        let mut buf = Vec::with_capacity(value.len() + 1);
-        buf.extend_from_slice(value.as_bytes());
-        buf.push(b'\0');
-
         // Check if the value already exists and matches (only if namespace exists)
         let old_entry_location = if let Some(&namespace_index) = self.namespaces.get(namespace) {
             match self.load_item(namespace_index, ChunkIndex::Any, &key) {
+                // an item of another type is deleted below as well, including the chunks of a blob
+                Ok((page_index, item_index, item)) if item.type_ != type_ => Some((page_index, item_index)),
                 Ok((page_index, item_index, item)) => {
-                    if item.type_ != ItemType::Sized {
-                        Some((page_index, item_index))
-                    } else {
-                        // Check if the data matches
-                        let page = &self.pages[page_index.0];
-                        let data = page.load_referenced_data(&mut self.hal, item_index.0, &item)?;
-
-                        let crc = unsafe { item.data.sized.crc };
-                        if crc == T::crc32(u32::MAX, &buf) && data == buf {
-                            return Ok(());
-                        }
-                        Some((page_index, item_index))
+                    // Check if the data matches
+                    let page = &self.pages[page_index.0];
+                    let data = page.load_referenced_data(&mut self.hal, item_index.0, &item)?;
+
+                    let crc = unsafe { item.data.sized.crc };
+                    if crc == T::crc32(u32::MAX, &buf) && data == buf {
+                        return Ok(());
                     }
+                    Some((page_index, item_index))
                 }
                 Err(Error::FlashError) => return Err(Error::FlashError),
                 Err(_) => None,
@@ -441,21 +708,51 @@ where
         let mut page = self.get_active_page()?;
         let namespace_index = self.get_or_create_namespace(namespace, &mut page)?;
 
-        match page.write_variable_sized_item::<T>(&mut self.hal, namespace_index, key, ItemType::Sized, None, &buf) {
+        // Only the active page is written to, never the free entries of an older page: the page
+        // sequence decides which of two items with the same key is the current one, e.g. after a
+        // power loss between writing the new item and erasing the old one. An item placed on an
+        // older page would lose against the value it replaced.
+        match page.write_variable_sized_item::<T>(
+            &mut self.hal,
+            &mut self.hash_index,
+            namespace_index,
+            key,
+            type_,
+            None,
+            &buf,
+        ) {
             Ok(_) => {}
             Err(Error::PageFull) => {
                 page.mark_as_full::<T>(&mut self.hal)?;
                 self.pages.push(page);
 
+                // the next active page might be a partially filled one left over by defragmentation
                 page = self.get_active_page()?;
-                page.write_variable_sized_item::<T>(&mut self.hal, namespace_index, key, ItemType::Sized, None, &buf)?;
+                let result = page.write_variable_sized_item::<T>(
+                    &mut self.hal,
+                    &mut self.hash_index,
+                    namespace_index,
+                    key,
+                    type_,
+                    None,
+                    &buf,
+                );
+                if let Err(e) = result {
+                    // keep track of the page, it is still the active one
+                    self.pages.push(page);
+                    return Err(match e {
+                        Error::PageFull => Error::FlashFull,
+                        e => e,
+                    });
+                }
             }
             Err(e) => return Err(e),
         }
 
         self.pages.push(page);
 
-        // Now delete the old entry if it exists
+        // Now delete the old entry if it exists, it comes first as it is either on an older page or
+        // before the new one
         if let Some((_page_index, _item_index)) = old_entry_location {
             self.delete_key(namespace_index, &key, ChunkIndex::Any)?;
         }
@@ -470,30 +767,59 @@ where
         #[cfg(feature = "debug-logs")]
         println!("internal: set_blob");
 
-        if key.0[MAX_KEY_LENGTH] != b'\0' {
+        self.write_blob(namespace, key, data, None)
+    }
+
+    /// Write a blob with the given version, or the version following the existing blob if `None`.
+    ///
+    /// With an explicit version the blob is always written, even if its content didn't change.
+    pub(crate) fn write_blob(
+        &mut self,
+        namespace: &Key,
+        key: Key,
+        data: &[u8],
+        version: Option<VersionOffset>,
+    ) -> Result<(), Error> {
+        #[cfg(feature = "defmt")]
+        trace!("write_blob");
+
+        #[cfg(feature = "debug-logs")]
+        println!("internal: write_blob");
+
+        if !key.is_well_formed() {
             return Err(Error::KeyMalformed);
         }
-        if namespace.0[MAX_KEY_LENGTH] != b'\0' {
+        if !namespace.is_well_formed() {
             return Err(Error::NamespaceMalformed);
         }
 
-        if data.len() + 1 > MAX_BLOB_SIZE {
+        if data.len() + 1 > self.max_blob_size {
             return Err(Error::ValueTooLong);
         }
 
+        // the entries holding the data, the blob index and a new namespace need one entry each
+        let data_entries = data.len().div_ceil(size_of::<Item>());
+        let other_entries = 1 + usize::from(!self.namespaces.contains_key(namespace));
+
         // Check if we're overwriting an existing blob to determine version offset
         let old_blob_version = self.find_existing_blob_version(namespace, &key);
 
+        // the chunks of both versions would collide
+        if version.is_some() && old_blob_version == version {
+            return Err(Error::InvalidChunkStart);
+        }
+
         // Check if the value already exists and matches (only if namespace exists)
+        let mut replaces_other_type = false;
         let should_write = if let Some(&namespace_index) = self.namespaces.get(namespace) {
             match self.load_item(namespace_index, ChunkIndex::Any, &key) {
-                Ok((_page_index, _item_index, item)) => {
-                    if item.type_ != ItemType::BlobIndex {
-                        true // Type differs, need to write
-                    } else {
-                        !self.blob_is_equal(namespace_index, &key, &item, data)?
-                    }
+                // a primitive, string or legacy blob is deleted once the new blob is written
+                Ok((_page_index, _item_index, item)) if item.type_ != ItemType::BlobIndex => {
+                    replaces_other_type = true;
+                    true
                 }
+                Ok(_) if version.is_some() => true,
+                Ok((_page_index, _item_index, item)) => !self.blob_is_equal(namespace_index, &key, &item, data)?,
                 Err(_) => true, // Key doesn't exist, need to write
             }
         } else {
@@ -504,15 +830,22 @@ where
             return Ok(());
         }
 
+        // fail before writing any chunk instead of running out of space halfway through, the old
+        // version of the blob stays in place until the new one is written
+        if !self.blob_fits(data_entries, other_entries) {
+            return Err(Error::FlashFull);
+        }
+
         // Get namespace index
         let mut page = self.get_active_page()?;
         let namespace_index = self.get_or_create_namespace(namespace, &mut page)?;
         self.pages.push(page);
 
         // Determine the version offset for the new blob
-        let new_version_offset = match &old_blob_version {
-            Some(old_offset) => old_offset.invert(),
-            None => VersionOffset::V0,
+        let new_version_offset = match (version, &old_blob_version) {
+            (Some(version), _) => version,
+            (None, Some(old_offset)) => old_offset.invert(),
+            (None, None) => VersionOffset::V0,
         };
 
         let version_base = new_version_offset.clone() as u8;
@@ -533,6 +866,7 @@ where
 
             match page.write_variable_sized_item::<T>(
                 &mut self.hal,
+                &mut self.hash_index,
                 namespace_index,
                 key,
                 ItemType::BlobData,
@@ -556,14 +890,11 @@ where
         // Write the blob index
         let mut page = self.get_active_page()?;
         let item_data = raw::ItemData {
-            blob_index: ItemDataBlobIndex {
-                size: data.len() as u32,
-                chunk_count,
-                chunk_start: version_base,
-            },
+            blob_index: ItemDataBlobIndex::new(data.len() as u32, chunk_count, version_base),
         };
         page.write_item::<T>(
             &mut self.hal,
+            &mut self.hash_index,
             namespace_index,
             key,
             ItemType::BlobIndex,
@@ -579,10 +910,34 @@ where
         if let Some(_old_version) = old_blob_version {
             self.delete_key(namespace_index, &key, ChunkIndex::BlobIndex)?;
         }
+        if replaces_other_type {
+            self.delete_key(namespace_index, &key, ChunkIndex::Any)?;
+        }
 
         Ok(())
     }
 
+    /// Returns `true` if a blob with `data_entries` entries of data and `other_entries` entries
+    /// without data fits, once all erased entries are reclaimed.
+    ///
+    /// Reclaiming a page leaves its free entries at its end. Every chunk needs a header next to its
+    /// data, so a page takes a chunk only if at least two entries are free. A single free entry can
+    /// still hold the blob index or the namespace.
+    fn blob_fits(&self, data_entries: usize, other_entries: usize) -> bool {
+        let free_pages = self.free_pages.len().saturating_sub(self.reserved_pages as usize);
+        let mut chunk_entries = free_pages * (ENTRIES_PER_PAGE - 1);
+        let mut single_entries = 0;
+        for page in &self.pages {
+            match ENTRIES_PER_PAGE - page.used_entry_count as usize {
+                0 => {}
+                1 => single_entries += 1,
+                free => chunk_entries += free - 1,
+            }
+        }
+
+        data_entries <= chunk_entries && data_entries + other_entries <= chunk_entries + single_entries
+    }
+
     pub(crate) fn get_active_page(&mut self) -> Result<ThinPage, Error> {
         #[cfg(feature = "defmt")]
         trace!("get_active_page");
@@ -592,9 +947,12 @@ where
             return Ok(page);
         }
 
-        // Only try reclamation if we have no free pages left
-        if self.free_pages.len() == 1 {
+        // Only try reclamation if we have no free pages left, or before using the last one if a
+        // page is mostly erased
+        if self.free_pages.len() <= self.reserved_pages as usize {
             self.defragment()?;
+        } else if self.free_pages.len() == self.reserved_pages as usize + 1 {
+            self.reclaim_early()?;
         }
 
         let page = self.pages.pop_if(|page| page.header.state == ThinPageState::Active);
@@ -603,17 +961,15 @@ where
         }
 
         // After reclamation, check if we have free pages available
-        if self.free_pages.len() == 1 {
+        if self.free_pages.len() <= self.reserved_pages as usize {
             return Err(Error::FlashFull);
         }
 
-        // at this point we have at least 2 free pages
+        // at this point we have at least one free page besides the reserved ones
         let mut page = self.free_pages.pop().unwrap();
 
         if page.header.state != ThinPageState::Uninitialized {
-            self.hal
-                .erase(page.address as _, (page.address + raw::FLASH_SECTOR_SIZE) as _)
-                .map_err(|_| Error::FlashError)?;
+            self.erase_sector(page.address)?;
         }
 
         let next_sequence = self.get_next_sequence();
@@ -644,7 +1000,7 @@ where
                     None => 1,
                 };
 
-                page.write_namespace(&mut self.hal, *namespace, namespace_index)?;
+                page.write_namespace(&mut self.hal, &mut self.hash_index, *namespace, namespace_index)?;
 
                 self.namespaces.insert(*namespace, namespace_index);
 
@@ -655,6 +1011,52 @@ where
         Ok(namespace_index)
     }
 
+    pub(crate) fn write_new_namespace(&mut self, namespace: &Key, namespace_index: u8) -> Result<(), Error> {
+        #[cfg(feature = "defmt")]
+        trace!("write_new_namespace");
+
+        #[cfg(feature = "debug-logs")]
+        println!("internal: write_new_namespace {namespace_index}");
+
+        match self.namespaces.get(namespace) {
+            Some(&idx) if idx == namespace_index => return Ok(()),
+            Some(_) => return Err(Error::InvalidNamespaceIndex),
+            None => {}
+        }
+        if namespace_index == 0 || self.namespaces.values().any(|&idx| idx == namespace_index) {
+            return Err(Error::InvalidNamespaceIndex);
+        }
+
+        let mut page = self.get_active_page()?;
+        let result = page.write_namespace(&mut self.hal, &mut self.hash_index, *namespace, namespace_index);
+        let result = result.and_then(|_| match page.is_full() {
+            true => page.mark_as_full(&mut self.hal),
+            false => Ok(()),
+        });
+        self.pages.push(page);
+        result?;
+
+        self.namespaces.insert(*namespace, namespace_index);
+
+        Ok(())
+    }
+
+    /// Returns the page and item indices of the loaded items with the given hash, in the order of
+    /// the pages and of the items within them.
+    pub(crate) fn hash_candidates(&self, hash: u24) -> Vec<(usize, u8)> {
+        let mut candidates: Vec<_> = self
+            .hash_index
+            .items(hash)
+            .iter()
+            .filter_map(|&(address, item_index)| {
+                let page_index = self.pages.iter().position(|page| page.address == address)?;
+                Some((page_index, item_index))
+            })
+            .collect();
+        candidates.sort_unstable();
+        candidates
+    }
+
     pub(crate) fn load_item(
         &mut self,
         namespace_index: u8,
@@ -678,23 +1080,35 @@ where
         #[cfg(feature = "debug-logs")]
         println!("looking for hash {hash:?}");
 
-        for (page_index, page) in self.pages.iter().enumerate() {
-            for cache_entry in &page.item_hash_list {
-                if cache_entry.hash == hash {
-                    let item: Item = page.load_item(&mut self.hal, cache_entry.index)?;
+        // in read-only mode duplicates were not cleaned up, so the newest one has to be found
+        let mut newest: Option<(u32, PageIndex, ItemIndex, Item)> = None;
 
-                    if item.namespace_index != namespace_index
-                        || item.key != *key
-                        || item.chunk_index != item_chunk_index
-                    {
-                        continue;
-                    }
+        for (page_index, item_index) in self.hash_candidates(hash) {
+            let page = &self.pages[page_index];
+            let item: Item = page.load_item(&mut self.hal, item_index)?;
+
+            if item.namespace_index != namespace_index || item.key != *key || item.chunk_index != item_chunk_index {
+                continue;
+            }
+
+            if !self.read_only {
+                return Ok((page_index.into(), item_index.into(), item));
+            }
 
-                    return Ok((page_index.into(), cache_entry.index.into(), item));
+            let is_newer = match &newest {
+                None => true,
+                Some((sequence, _, newest_index, _)) => {
+                    (page.header.sequence, item_index) > (*sequence, newest_index.0)
                 }
+            };
+            if is_newer {
+                newest = Some((page.header.sequence, page_index.into(), item_index.into(), item));
             }
         }
 
-        Err(KeyNotFound)
+        match newest {
+            Some((_, page_index, item_index, item)) => Ok((page_index, item_index, item)),
+            None => Err(KeyNotFound),
+        }
     }
 }
@@ -3,24 +3,36 @@
 
 extern crate alloc;
 
+#[cfg(feature = "encryption")]
+pub mod encryption;
 pub mod error;
 pub mod mem_flash;
 pub mod platform;
 pub mod raw;
 
+mod array;
+mod batch;
 mod blob;
 mod compaction;
 mod get;
+mod hash_index;
 mod init;
 mod items;
+mod namespace;
 mod nvs;
 mod page;
 mod set;
 mod statistics;
 mod types;
 mod u24;
+#[cfg(feature = "encryption")]
+mod xts;
 
+pub use array::ArrayElement;
+pub use batch::Batch;
+pub use blob::LegacyBlob;
 pub use get::Get;
+pub use namespace::Namespace;
 pub use nvs::Nvs;
 pub use raw::{
     ENTRIES_PER_PAGE,
@@ -36,10 +48,14 @@ pub use raw::{
 pub use set::Set;
 pub use statistics::{
     EntryStatistics,
+    NamespaceStatistics,
     NvsStatistics,
     PageStatistics,
+    StatisticsSummary,
 };
 pub use types::{
+    DecodedValue,
+    EntryType,
     Key,
     MAX_KEY_LENGTH,
 };
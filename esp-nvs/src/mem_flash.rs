@@ -4,6 +4,10 @@
 //! [`crate::platform::Crc`], making it a fully functional [`crate::platform::Platform`]
 //! that can be used with [`crate::Nvs`] on any host platform without hardware
 //! dependencies.
+//!
+//! With the `test-util` feature it supports injecting faults after a given number of operations,
+//! which allows testing how an application copes with power loss at any point, and recording all
+//! operations. The crate's own tests use it as well.
 
 use alloc::vec;
 use alloc::vec::Vec;
@@ -16,10 +20,11 @@ use embedded_storage::nor_flash::{
     ReadNorFlash,
 };
 
-use crate::FLASH_SECTOR_SIZE;
-use crate::platform::{
-    Crc,
-    software_crc32,
+use crate::error::Error;
+use crate::platform::Crc;
+use crate::{
+    FLASH_SECTOR_SIZE,
+    Nvs,
 };
 
 const WORD_SIZE: usize = 4;
@@ -31,16 +36,43 @@ const WORD_SIZE: usize = 4;
 /// - Erases restore a full sector to `0xFF`.
 /// - Read/write alignment is 4 bytes (word size).
 /// - Erase granularity is 4096 bytes (sector size).
+///
+/// With the `test-util` feature all reads, writes and erases fail with [`MemFlashError`] once
+/// `fail_after_operation` operations succeeded, see `MemFlash::new_with_fault`.
 pub struct MemFlash {
     buf: Vec<u8>,
+    #[cfg(feature = "test-util")]
+    fail_after_operation: usize,
+    #[cfg(feature = "test-util")]
+    transient_faults: Option<usize>,
+    #[cfg(feature = "test-util")]
+    operation_count: usize,
+    #[cfg(feature = "test-util")]
+    operations: Option<Vec<Operation>>,
+}
+
+/// A successful flash operation recorded by [`MemFlash`], see [`MemFlash::enable_operation_log`].
+#[cfg(feature = "test-util")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    Read { offset: u32, len: usize },
+    Write { offset: u32, len: usize },
+    Erase { offset: u32, len: usize },
 }
 
 impl MemFlash {
     /// Create a fresh flash of the given number of pages, filled with `0xFF`.
     pub fn new(pages: usize) -> Self {
-        Self {
-            buf: vec![0xFF; FLASH_SECTOR_SIZE * pages],
-        }
+        Self::from_bytes(vec![0xFF; FLASH_SECTOR_SIZE * pages])
+    }
+
+    /// Create a fresh flash which fails every operation after `fail_after_operation` operations
+    /// succeeded, simulating a power loss at that point.
+    #[cfg(feature = "test-util")]
+    pub fn new_with_fault(pages: usize, fail_after_operation: usize) -> Self {
+        let mut flash = Self::new(pages);
+        flash.fail_after_operation = fail_after_operation;
+        flash
     }
 
     /// Wrap existing binary data as a flash image.
@@ -56,7 +88,94 @@ impl MemFlash {
             data.len(),
             FLASH_SECTOR_SIZE
         );
-        Self { buf: data }
+        Self {
+            buf: data,
+            #[cfg(feature = "test-util")]
+            fail_after_operation: usize::MAX,
+            #[cfg(feature = "test-util")]
+            transient_faults: None,
+            #[cfg(feature = "test-util")]
+            operation_count: 0,
+            #[cfg(feature = "test-util")]
+            operations: None,
+        }
+    }
+
+    /// Fail every operation after `fail_after_operation` operations succeeded in total, including
+    /// the ones done before calling this.
+    #[cfg(feature = "test-util")]
+    pub fn set_fail_after_operation(&mut self, fail_after_operation: usize) {
+        self.fail_after_operation = fail_after_operation;
+    }
+
+    /// Only fail the next `count` operations once the fault is reached and work again afterwards,
+    /// like a flash recovering from a brown-out.
+    #[cfg(feature = "test-util")]
+    pub fn set_transient_faults(&mut self, count: usize) {
+        self.transient_faults = Some(count);
+    }
+
+    /// Stop injecting faults, e.g. to reload the flash after a simulated power loss.
+    #[cfg(feature = "test-util")]
+    pub fn disable_faults(&mut self) {
+        self.fail_after_operation = usize::MAX;
+        self.transient_faults = None;
+    }
+
+    /// Number of successful operations so far.
+    #[cfg(feature = "test-util")]
+    pub fn operation_count(&self) -> usize {
+        self.operation_count
+    }
+
+    /// Start recording all successful operations, see [`MemFlash::operations`]. Disabled by
+    /// default as the log grows with every access.
+    #[cfg(feature = "test-util")]
+    pub fn enable_operation_log(&mut self) {
+        self.operations.get_or_insert_with(Vec::new);
+    }
+
+    /// The operations recorded since [`MemFlash::enable_operation_log`] was called.
+    #[cfg(feature = "test-util")]
+    pub fn operations(&self) -> &[Operation] {
+        self.operations.as_deref().unwrap_or_default()
+    }
+
+    /// Forget the operations recorded so far, the log stays enabled.
+    #[cfg(feature = "test-util")]
+    pub fn clear_operations(&mut self) {
+        if let Some(operations) = &mut self.operations {
+            operations.clear();
+        }
+    }
+
+    /// Counts the operation or fails it if the configured fault was reached.
+    #[cfg(feature = "test-util")]
+    fn operation(&mut self, operation: Operation) -> Result<(), MemFlashError> {
+        if self.operation_count >= self.fail_after_operation {
+            match &mut self.transient_faults {
+                None => {}
+                Some(0 | 1) => self.disable_faults(),
+                Some(remaining) => *remaining -= 1,
+            }
+            return Err(MemFlashError);
+        }
+
+        self.operation_count += 1;
+        if let Some(operations) = &mut self.operations {
+            operations.push(operation);
+        }
+        Ok(())
+    }
+
+    /// The contents of the flash.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// The contents of the flash, e.g. to corrupt them on purpose.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.buf
     }
 
     /// Consume the flash and return the underlying buffer.
@@ -92,6 +211,14 @@ impl ReadNorFlash for MemFlash {
     const READ_SIZE: usize = WORD_SIZE;
 
     fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        assert!(offset.is_multiple_of(Self::READ_SIZE as _));
+
+        #[cfg(feature = "test-util")]
+        self.operation(Operation::Read {
+            offset,
+            len: bytes.len(),
+        })?;
+
         let offset = offset as usize;
         bytes.copy_from_slice(&self.buf[offset..offset + bytes.len()]);
         Ok(())
@@ -107,6 +234,15 @@ impl NorFlash for MemFlash {
     const ERASE_SIZE: usize = FLASH_SECTOR_SIZE;
 
     fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        assert!(from.is_multiple_of(Self::ERASE_SIZE as _));
+        assert!(to.is_multiple_of(Self::ERASE_SIZE as _));
+
+        #[cfg(feature = "test-util")]
+        self.operation(Operation::Erase {
+            offset: from,
+            len: (to - from) as usize,
+        })?;
+
         for addr in from..to {
             self.buf[addr as usize] = 0xFF;
         }
@@ -114,6 +250,15 @@ impl NorFlash for MemFlash {
     }
 
     fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        assert!(offset.is_multiple_of(Self::WRITE_SIZE as _));
+        assert!(bytes.len().is_multiple_of(Self::WRITE_SIZE));
+
+        #[cfg(feature = "test-util")]
+        self.operation(Operation::Write {
+            offset,
+            len: bytes.len(),
+        })?;
+
         let offset = offset as usize;
         for (i, &val) in bytes.iter().enumerate() {
             // Real NOR flash can only flip bits from 1 to 0
@@ -123,8 +268,20 @@ impl NorFlash for MemFlash {
     }
 }
 
-impl Crc for MemFlash {
-    fn crc32(init: u32, data: &[u8]) -> u32 {
-        software_crc32(init, data)
+impl Crc for MemFlash {}
+
+impl Nvs<MemFlash> {
+    /// Load a partition image, e.g. one generated by `esp-nvs-partition-tool` or read back from
+    /// a device, into RAM. Useful to test code relying on provisioned data on the host.
+    ///
+    /// Returns [`Error::InvalidPartitionSize`] if the image isn't a multiple of
+    /// [`FLASH_SECTOR_SIZE`]. Like [`Nvs::new`] this cleans up the image, use
+    /// [`Nvs::into_inner`] and [`MemFlash::into_inner`] to get the resulting bytes.
+    pub fn from_image(image: &[u8]) -> Result<Nvs<MemFlash>, Error> {
+        if !image.len().is_multiple_of(FLASH_SECTOR_SIZE) {
+            return Err(Error::InvalidPartitionSize);
+        }
+
+        Nvs::new(0, image.len(), MemFlash::from_bytes(image.to_vec()))
     }
 }
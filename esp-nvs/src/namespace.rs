@@ -0,0 +1,90 @@
+//! Namespace-scoped handle to the NVS storage.
+//!
+//! This module contains the [`Namespace`] handle returned by [`Nvs::open`] which mirrors
+//! ESP-IDF's `nvs::Handle` and saves passing the namespace to every call.
+
+use crate::error::Error;
+use crate::get::Get;
+use crate::platform::Platform;
+use crate::set::Set;
+use crate::{
+    Key,
+    Nvs,
+};
+
+/// A handle to a single namespace of an [`Nvs`] instance.
+///
+/// The handle borrows the [`Nvs`] instance mutably, so there can only be one live handle at a
+/// time. The namespace is only created on flash once the first value is written to it.
+pub struct Namespace<'a, T: Platform> {
+    nvs: &'a mut Nvs<T>,
+    namespace: Key,
+}
+
+impl<'a, T: Platform> Namespace<'a, T> {
+    pub(crate) fn new(nvs: &'a mut Nvs<T>, namespace: Key) -> Self {
+        Self { nvs, namespace }
+    }
+
+    /// Returns the namespace this handle is scoped to.
+    pub fn name(&self) -> &Key {
+        &self.namespace
+    }
+
+    /// Get a value from the namespace. See [`Nvs::get`].
+    pub fn get<R>(&mut self, key: &Key) -> Result<R, Error>
+    where
+        Nvs<T>: Get<R>,
+    {
+        self.nvs.get(&self.namespace, key)
+    }
+
+    /// Get a value from the namespace or fall back to `default`. See [`Nvs::get_or`].
+    pub fn get_or<R>(&mut self, key: &Key, default: R) -> Result<R, Error>
+    where
+        Nvs<T>: Get<R>,
+    {
+        self.nvs.get_or(&self.namespace, key, default)
+    }
+
+    /// Get a string from the namespace without allocating. See [`Nvs::get_str_into`].
+    pub fn get_str_into<'b>(&mut self, key: &Key, buf: &'b mut [u8]) -> Result<&'b str, Error> {
+        self.nvs.get_str_into(&self.namespace, key, buf)
+    }
+
+    /// Set a value in the namespace. See [`Nvs::set`].
+    pub fn set<R>(&mut self, key: &Key, value: R) -> Result<(), Error>
+    where
+        Nvs<T>: Set<R>,
+    {
+        self.nvs.set(&self.namespace, key, value)
+    }
+
+    /// Set a value in the namespace and return the previous one. See [`Nvs::replace`].
+    pub fn replace<R>(&mut self, key: &Key, value: R) -> Result<Option<R>, Error>
+    where
+        Nvs<T>: Get<R> + Set<R>,
+    {
+        self.nvs.replace(&self.namespace, key, value)
+    }
+
+    /// Delete a key from the namespace. See [`Nvs::delete`].
+    pub fn delete(&mut self, key: &Key) -> Result<(), Error> {
+        self.nvs.delete(&self.namespace, key)
+    }
+
+    /// Delete a key from the namespace and return whether it existed. See [`Nvs::remove`].
+    pub fn remove(&mut self, key: &Key) -> Result<bool, Error> {
+        self.nvs.remove(&self.namespace, key)
+    }
+
+    /// Delete every key of the namespace starting with `prefix`. See [`Nvs::delete_prefix`].
+    pub fn delete_prefix(&mut self, prefix: &[u8]) -> Result<u32, Error> {
+        self.nvs.delete_prefix(&self.namespace, prefix)
+    }
+
+    /// Check whether a value is stored under the key. See [`Nvs::contains`].
+    pub fn contains(&mut self, key: &Key) -> Result<bool, Error> {
+        self.nvs.contains(&self.namespace, key)
+    }
+}
@@ -5,12 +5,17 @@
 
 use alloc::collections::{
     BTreeMap,
+    BTreeSet,
     BinaryHeap,
 };
+use alloc::vec;
 use alloc::vec::Vec;
 
+use crate::batch::Batch;
 use crate::error::Error;
 use crate::get::Get;
+use crate::hash_index::HashIndex;
+use crate::namespace::Namespace;
 use crate::page::{
     IterPageItems,
     ThinPage,
@@ -22,6 +27,8 @@ use crate::raw::{
     FLASH_SECTOR_SIZE,
     Item,
     ItemType,
+    MAX_BLOB_DATA_PER_PAGE,
+    MAX_BLOB_SIZE,
 };
 use crate::set::Set;
 use crate::types::{
@@ -29,9 +36,11 @@ use crate::types::{
     VersionOffset,
 };
 use crate::{
+    DecodedValue,
     EntryStatistics,
+    EntryType,
     Key,
-    MAX_KEY_LENGTH,
+    NamespaceStatistics,
     NvsStatistics,
     PageStatistics,
 };
@@ -43,11 +52,18 @@ pub struct Nvs<T: Platform> {
     pub(crate) base_address: usize,
     pub(crate) sectors: u16,
     pub(crate) faulted: bool,
+    pub(crate) read_only: bool,
+    pub(crate) reserved_pages: u16,
+    pub(crate) erase_counts: Vec<u32>,
+    pub(crate) max_blob_size: usize,
+    /// Backs the strings returned by [`Nvs::get_str_ref`], grows to the longest one read so far.
+    pub(crate) read_buffer: Vec<u8>,
 
     // set after calling self.load_sectors
     pub(crate) namespaces: BTreeMap<Key, u8>,
     pub(crate) free_pages: BinaryHeap<ThinPage>,
     pub(crate) pages: Vec<ThinPage>,
+    pub(crate) hash_index: HashIndex,
 }
 
 impl<T: Platform> Nvs<T> {
@@ -55,11 +71,33 @@ impl<T: Platform> Nvs<T> {
     /// 1. Resolve all existing namespaces
     /// 2. Create a hashed key cache per page for quicker lookups
     /// 3. Cleanup duplicate entries
-    /// 4. Cleanup of duplicated blobs or orphaned blob data
+    /// 4. Cleanup of duplicated blobs or orphaned blob data, and of blobs up to
+    ///    [`MAX_BLOB_DATA_PER_PAGE`](crate::MAX_BLOB_DATA_PER_PAGE) bytes whose data doesn't match
+    ///    its CRC
     ///
     /// Pages or entries with invalid CRC32 values are marked as corrupt and are erased when
-    /// necessary
+    /// necessary. Pages that are entirely zeroed are free and erased when necessary as well, see
+    /// [`Nvs::erase_free_pages`].
     pub fn new(partition_offset: usize, partition_size: usize, hal: T) -> Result<Nvs<T>, Error> {
+        Self::open_partition(partition_offset, partition_size, hal, false)
+    }
+
+    /// Same as [`Nvs::new`] but guarantees that the flash is never written to or erased.
+    ///
+    /// None of the cleanup done by [`Nvs::new`] is performed, e.g. duplicate entries, dirty blobs
+    /// or interrupted page reclamation are left as they are. Reading values still works as
+    /// duplicates are resolved in favor of the newest page sequence. Any operation that would
+    /// modify the flash returns [`Error::ReadOnly`].
+    pub fn new_read_only(partition_offset: usize, partition_size: usize, hal: T) -> Result<Nvs<T>, Error> {
+        Self::open_partition(partition_offset, partition_size, hal, true)
+    }
+
+    fn open_partition(
+        partition_offset: usize,
+        partition_size: usize,
+        hal: T,
+        read_only: bool,
+    ) -> Result<Nvs<T>, Error> {
         if !partition_offset.is_multiple_of(FLASH_SECTOR_SIZE) {
             return Err(Error::InvalidPartitionOffset);
         }
@@ -70,7 +108,14 @@ impl<T: Platform> Nvs<T> {
 
         let sectors = partition_size / FLASH_SECTOR_SIZE;
         if sectors > u16::MAX as usize {
-            return Err(Error::InvalidPartitionSize);
+            return Err(Error::PartitionTooLarge);
+        }
+
+        if partition_offset
+            .checked_add(partition_size)
+            .is_none_or(|end| end > hal.capacity())
+        {
+            return Err(Error::PartitionOutOfBounds);
         }
 
         let mut nvs: Nvs<T> = Self {
@@ -80,7 +125,13 @@ impl<T: Platform> Nvs<T> {
             namespaces: BTreeMap::new(),
             free_pages: Default::default(),
             pages: Default::default(),
+            hash_index: Default::default(),
             faulted: false,
+            read_only,
+            reserved_pages: 1,
+            erase_counts: vec![0; sectors],
+            max_blob_size: max_blob_size(sectors as u16, 1),
+            read_buffer: Vec::new(),
         };
 
         match nvs.load_sectors() {
@@ -93,6 +144,35 @@ impl<T: Platform> Nvs<T> {
         }
     }
 
+    /// Reload the partition from scratch to clear a fault caused by a [`Error::FlashError`].
+    ///
+    /// Does the same as [`Nvs::new`], including the cleanup of anything left behind by the
+    /// operation that failed. If reloading fails as well, the instance stays faulted and the error
+    /// is returned.
+    pub fn recover(&mut self) -> Result<(), Error> {
+        self.namespaces.clear();
+        self.free_pages.clear();
+        self.pages.clear();
+        self.hash_index.clear();
+
+        match self.load_sectors() {
+            Ok(()) => {
+                self.faulted = false;
+                Ok(())
+            }
+            Err(e) => {
+                self.faulted = true;
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns `true` if an operation failed with [`Error::FlashError`] and every following one
+    /// fails with it as well until [`Nvs::recover`] succeeds.
+    pub fn is_faulted(&self) -> bool {
+        self.faulted
+    }
+
     /// Get a value from the flash.
     ///
     /// Supported types are bool, singed and unsigned integers up to 64-bit width, String and Vec.
@@ -112,6 +192,73 @@ impl<T: Platform> Nvs<T> {
         }
     }
 
+    /// Get a value from the flash or fall back to `default` if the key or its namespace doesn't
+    /// exist yet.
+    ///
+    /// All other errors, e.g. [`Error::FlashError`] or [`Error::ItemTypeMismatch`], are still
+    /// returned.
+    pub fn get_or<R>(&mut self, namespace: &Key, key: &Key, default: R) -> Result<R, Error>
+    where
+        Nvs<T>: Get<R>,
+    {
+        match self.get(namespace, key) {
+            Ok(val) => Ok(val),
+            Err(Error::KeyNotFound | Error::NamespaceNotFound) => Ok(default),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get the raw bytes stored under the key together with the type they were written as.
+    ///
+    /// Primitives return their whole 8 byte data field in little endian, strings include their
+    /// null terminator and blobs are reassembled from their chunks.
+    pub fn get_raw(&mut self, namespace: &Key, key: &Key) -> Result<(EntryType, Vec<u8>), Error> {
+        match self.load_raw(namespace, key) {
+            Ok(val) => Ok(val),
+            Err(Error::FlashError) => {
+                self.faulted = true;
+                Err(Error::FlashError)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get a string without allocating, by reading it into `buf`.
+    ///
+    /// Like ESP-IDF's `nvs_get_str`, `buf` has to have room for the null terminator of the
+    /// stored string. If it is too small, [`Error::BufferTooSmall`] reports the required length.
+    /// The returned string borrows from `buf` and doesn't include the terminator.
+    pub fn get_str_into<'b>(&mut self, namespace: &Key, key: &Key, buf: &'b mut [u8]) -> Result<&'b str, Error> {
+        match self.load_str_into(namespace, key, buf) {
+            Ok(val) => Ok(val),
+            Err(Error::FlashError) => {
+                self.faulted = true;
+                Err(Error::FlashError)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get a string without allocating for every read, by reading it into a buffer owned by the
+    /// [`Nvs`].
+    ///
+    /// The buffer is only allocated, or grown, when a string longer than any read before is
+    /// requested. The returned string borrows the [`Nvs`], so it has to be dropped before the next
+    /// call to [`Nvs::get`], [`Nvs::set`] or any other method.
+    pub fn get_str_ref(&mut self, namespace: &Key, key: &Key) -> Result<&str, Error> {
+        let len = match self.load_str_to_buffer(namespace, key) {
+            Ok(len) => len,
+            Err(Error::FlashError) => {
+                self.faulted = true;
+                return Err(Error::FlashError);
+            }
+            Err(e) => return Err(e),
+        };
+
+        // already validated while loading
+        core::str::from_utf8(&self.read_buffer[..len]).map_err(|_| Error::CorruptedData)
+    }
+
     /// Set a value and write it to the flash
     ///
     /// Type support:
@@ -127,6 +274,10 @@ impl<T: Platform> Nvs<T> {
             return Err(Error::FlashError);
         }
 
+        if self.read_only {
+            return Err(Error::ReadOnly);
+        }
+
         match Set::set(self, namespace, key, value) {
             Ok(()) => Ok(()),
             Err(Error::FlashError) => {
@@ -137,11 +288,144 @@ impl<T: Platform> Nvs<T> {
         }
     }
 
+    /// Set a blob with the given chunk start and write it to the flash.
+    ///
+    /// Blobs alternate between the chunk starts 0 and 128 so the old version stays intact until
+    /// the new one is written. [`Nvs::set`] picks the chunk start automatically, this allows
+    /// reproducing the layout of another partition. The blob is written even if its content
+    /// didn't change, the chunk start has to differ from the one of an existing blob.
+    pub fn set_blob_with_chunk_start(
+        &mut self,
+        namespace: &Key,
+        key: &Key,
+        data: &[u8],
+        chunk_start: u8,
+    ) -> Result<(), Error> {
+        if self.faulted {
+            return Err(Error::FlashError);
+        }
+
+        if self.read_only {
+            return Err(Error::ReadOnly);
+        }
+
+        let version = match chunk_start {
+            0x00 => VersionOffset::V0,
+            0x80 => VersionOffset::V1,
+            _ => return Err(Error::InvalidChunkStart),
+        };
+
+        match self.write_blob(namespace, *key, data, Some(version)) {
+            Ok(()) => Ok(()),
+            Err(Error::FlashError) => {
+                self.faulted = true;
+                Err(Error::FlashError)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get the chunk start of a blob, 0 or 128 depending on which version it is stored as.
+    pub fn blob_chunk_start(&mut self, namespace: &Key, key: &Key) -> Result<u8, Error> {
+        match self.load_blob_chunk_start(namespace, key) {
+            Ok(val) => Ok(val),
+            Err(Error::FlashError) => {
+                self.faulted = true;
+                Err(Error::FlashError)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Check whether a value is stored under the given key.
+    ///
+    /// A missing namespace is reported as `Ok(false)`.
+    pub fn contains(&mut self, namespace: &Key, key: &Key) -> Result<bool, Error> {
+        if self.faulted {
+            return Err(Error::FlashError);
+        }
+
+        match self.contains_key(namespace, key) {
+            Ok(val) => Ok(val),
+            Err(Error::FlashError) => {
+                self.faulted = true;
+                Err(Error::FlashError)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Set a value and return the previous one, or `None` if the key or its namespace didn't exist
+    /// yet.
+    ///
+    /// If the previous value can't be read as `R`, e.g. [`Error::ItemTypeMismatch`], the error is
+    /// returned and nothing is written.
+    pub fn replace<R>(&mut self, namespace: &Key, key: &Key, value: R) -> Result<Option<R>, Error>
+    where
+        Nvs<T>: Get<R> + Set<R>,
+    {
+        let previous = match self.get(namespace, key) {
+            Ok(val) => Some(val),
+            Err(Error::KeyNotFound | Error::NamespaceNotFound) => None,
+            Err(e) => return Err(e),
+        };
+
+        self.set(namespace, key, value)?;
+
+        Ok(previous)
+    }
+
+    /// Open a handle scoped to a single namespace, similar to ESP-IDF's `nvs::Handle`.
+    ///
+    /// The namespace is created lazily once the first value is written through the handle.
+    pub fn open(&mut self, namespace: &Key) -> Namespace<'_, T> {
+        Namespace::new(self, *namespace)
+    }
+
+    /// Start buffering multiple `set` and `delete` operations, which are only written on
+    /// [`Batch::commit`].
+    pub fn batch(&mut self) -> Batch<'_, T> {
+        Batch::new(self)
+    }
+
+    /// Create a namespace with the given index without writing a value to it.
+    ///
+    /// Namespaces are usually created by the first write to them and get the next unused index.
+    /// This allows reproducing the index assignment of another partition. Nothing is written if
+    /// the namespace already exists with this index.
+    pub fn create_namespace(&mut self, namespace: &Key, index: u8) -> Result<(), Error> {
+        if self.faulted {
+            return Err(Error::FlashError);
+        }
+
+        if self.read_only {
+            return Err(Error::ReadOnly);
+        }
+
+        if !namespace.is_well_formed() {
+            return Err(Error::NamespaceMalformed);
+        }
+
+        match self.write_new_namespace(namespace, index) {
+            Ok(()) => Ok(()),
+            Err(Error::FlashError) => {
+                self.faulted = true;
+                Err(Error::FlashError)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     /// Returns an iterator over all known namespaces.
     pub fn namespaces(&self) -> impl Iterator<Item = &Key> {
         self.namespaces.keys()
     }
 
+    /// Returns the index the given namespace is stored with, or `None` if it doesn't exist.
+    pub fn namespace_index(&self, namespace: &Key) -> Option<u8> {
+        self.namespaces.get(namespace).copied()
+    }
+
     /// Returns an iterator over all keys in all namespaces.
     ///
     /// # Errors
@@ -165,33 +449,215 @@ impl<T: Platform> Nvs<T> {
         IterTypedEntries::new(&self.pages, &mut self.hal, &self.namespaces)
     }
 
+    /// Returns an iterator over the values of a namespace, decoded according to their type.
+    ///
+    /// The keys are collected up front, their values are only read when the iterator gets to
+    /// them. Reading a value can fail, e.g. for a corrupted entry, so every value comes with its
+    /// own `Result` and the iterator continues with the next key afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::NamespaceNotFound`] if the namespace doesn't exist. The iterator yields
+    /// an error if there is a flash read error or a value can't be read.
+    pub fn iter_namespace(
+        &mut self,
+        namespace: &Key,
+    ) -> Result<impl Iterator<Item = Result<(Key, DecodedValue), Error>> + '_, Error> {
+        if !namespace.is_well_formed() {
+            return Err(Error::NamespaceMalformed);
+        }
+        if !self.namespaces.contains_key(namespace) {
+            return Err(Error::NamespaceNotFound);
+        }
+
+        // in read-only mode an outdated item might still be around next to the current one
+        let mut seen = BTreeSet::new();
+        let entries: Vec<_> = self
+            .typed_entries()
+            .filter(|entry| match entry {
+                Ok((ns, key, _)) => ns == namespace && seen.insert(*key),
+                Err(_) => true,
+            })
+            .collect();
+
+        let namespace = *namespace;
+        Ok(entries.into_iter().map(move |entry| {
+            let (_, key, item_type) = entry?;
+            let value = match EntryType::try_from(item_type)? {
+                EntryType::U8 => DecodedValue::U8(self.get(&namespace, &key)?),
+                EntryType::I8 => DecodedValue::I8(self.get(&namespace, &key)?),
+                EntryType::U16 => DecodedValue::U16(self.get(&namespace, &key)?),
+                EntryType::I16 => DecodedValue::I16(self.get(&namespace, &key)?),
+                EntryType::U32 => DecodedValue::U32(self.get(&namespace, &key)?),
+                EntryType::I32 => DecodedValue::I32(self.get(&namespace, &key)?),
+                EntryType::U64 => DecodedValue::U64(self.get(&namespace, &key)?),
+                EntryType::I64 => DecodedValue::I64(self.get(&namespace, &key)?),
+                EntryType::Str => DecodedValue::Str(self.get(&namespace, &key)?),
+                EntryType::Blob => DecodedValue::Blob(self.get(&namespace, &key)?),
+            };
+            Ok((key, value))
+        }))
+    }
+
     /// Delete a key
     ///
-    /// Ignores missing keys or the namespaces
+    /// Ignores missing keys or the namespaces, see [`Nvs::remove`] to find out whether the key
+    /// existed.
     pub fn delete(&mut self, namespace: &Key, key: &Key) -> Result<(), Error> {
+        self.remove(namespace, key).map(|_| ())
+    }
+
+    /// Delete a key and return whether it existed.
+    ///
+    /// Returns `Ok(false)` if either the key or the namespace doesn't exist.
+    pub fn remove(&mut self, namespace: &Key, key: &Key) -> Result<bool, Error> {
         if self.faulted {
             return Err(Error::FlashError);
         }
 
-        if key.0[MAX_KEY_LENGTH] != b'\0' {
+        if self.read_only {
+            return Err(Error::ReadOnly);
+        }
+
+        if !key.is_well_formed() {
             return Err(Error::KeyMalformed);
         }
-        if namespace.0[MAX_KEY_LENGTH] != b'\0' {
+        if !namespace.is_well_formed() {
             return Err(Error::NamespaceMalformed);
         }
 
         let namespace_index = match self.namespaces.get(namespace) {
             Some(&idx) => idx,
-            None => return Ok(()), // Namespace doesn't exist, that's fine
+            None => return Ok(false), // Namespace doesn't exist, that's fine
         };
         let result = self.delete_key(namespace_index, key, ChunkIndex::Any);
         match result {
-            Err(Error::KeyNotFound) => Ok(()),
+            Ok(()) => Ok(true),
+            Err(Error::KeyNotFound) => Ok(false),
             Err(Error::FlashError) => {
                 self.faulted = true;
                 Err(Error::FlashError)
             }
-            other => other,
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Delete every key of the namespace that starts with `prefix` and return how many keys were
+    /// deleted.
+    ///
+    /// Blobs are deleted with all their data chunks. An empty prefix deletes all keys of the
+    /// namespace, a missing namespace deletes nothing.
+    pub fn delete_prefix(&mut self, namespace: &Key, prefix: &[u8]) -> Result<u32, Error> {
+        if self.faulted {
+            return Err(Error::FlashError);
+        }
+
+        if self.read_only {
+            return Err(Error::ReadOnly);
+        }
+
+        if !namespace.is_well_formed() {
+            return Err(Error::NamespaceMalformed);
+        }
+
+        // blobs are reported once by their index, legacy blobs by their single item
+        let keys = self
+            .typed_entries()
+            .filter_map(|entry| match entry {
+                Ok((ns, key, _)) if ns == *namespace && key.trimmed().starts_with(prefix) => Some(Ok(key)),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            })
+            .collect::<Result<BTreeSet<_>, _>>();
+        let keys = match keys {
+            Ok(keys) => keys,
+            Err(Error::FlashError) => {
+                self.faulted = true;
+                return Err(Error::FlashError);
+            }
+            Err(e) => return Err(e),
+        };
+
+        let mut deleted = 0;
+        for key in keys {
+            if self.remove(namespace, &key)? {
+                deleted += 1;
+            }
+        }
+
+        Ok(deleted)
+    }
+
+    /// Erase all free pages that aren't erased yet and return how many were erased.
+    ///
+    /// Such pages, e.g. of flash that was shipped zeroed or pages that were found corrupt, are
+    /// otherwise erased one by one once they are needed for writing. Calling this right after
+    /// [`Nvs::new`] moves that cost up front. Corrupt pages are lost for diagnostics afterwards.
+    pub fn erase_free_pages(&mut self) -> Result<usize, Error> {
+        if self.faulted {
+            return Err(Error::FlashError);
+        }
+
+        if self.read_only {
+            return Err(Error::ReadOnly);
+        }
+
+        match self.erase_unerased_free_pages() {
+            Ok(count) => Ok(count),
+            Err(Error::FlashError) => {
+                self.faulted = true;
+                Err(Error::FlashError)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Erase the whole partition and start over with a single, freshly initialized active page.
+    ///
+    /// All namespaces and values are gone afterwards. Sectors are erased one after another, so a
+    /// power loss in the middle leaves some pages untouched while partially erased pages show up
+    /// as corrupt on the next [`Nvs::new`] and are reclaimed once space is needed.
+    pub fn erase_all(&mut self) -> Result<(), Error> {
+        if self.faulted {
+            return Err(Error::FlashError);
+        }
+
+        if self.read_only {
+            return Err(Error::ReadOnly);
+        }
+
+        match self.erase_all_pages() {
+            Ok(()) => Ok(()),
+            Err(Error::FlashError) => {
+                self.faulted = true;
+                Err(Error::FlashError)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Reclaim the page with the most erased entries now instead of when the next write runs
+    /// out of space, e.g. to move the expensive copy into an idle period.
+    ///
+    /// Returns the number of entries that became available, `Ok(0)` if there was nothing worth
+    /// reclaiming. A power loss in the middle is recovered by [`Nvs::new`] just like for the
+    /// reclamation done during writes.
+    pub fn compact(&mut self) -> Result<usize, Error> {
+        if self.faulted {
+            return Err(Error::FlashError);
+        }
+
+        if self.read_only {
+            return Err(Error::ReadOnly);
+        }
+
+        match self.compact_page() {
+            Ok(freed) => Ok(freed),
+            Err(Error::FlashError) => {
+                self.faulted = true;
+                Err(Error::FlashError)
+            }
+            Err(e) => Err(e),
         }
     }
 
@@ -203,6 +669,131 @@ impl<T: Platform> Nvs<T> {
         self.hal
     }
 
+    /// Returns the number of entries that can still be allocated before [`Error::FlashFull`] is
+    /// returned, e.g. the number of primitive values that still fit.
+    ///
+    /// Counts the free tail of all active pages plus all free pages except the ones that
+    /// are kept in reserve for defragmentation, see [`Nvs::set_reserved_pages`]. Erased entries on
+    /// full pages are not included as they only become usable after the page got reclaimed.
+    pub fn free_entries(&self) -> u32 {
+        let active: usize = self
+            .pages
+            .iter()
+            .filter(|page| *page.get_state() == ThinPageState::Active)
+            .map(|page| page.get_free_entry_count())
+            .sum();
+
+        // corrupt free pages are erased before they are used, so they count like empty ones
+        let free = self.free_pages.len().saturating_sub(self.reserved_pages as usize);
+
+        (active + free * ENTRIES_PER_PAGE) as u32
+    }
+
+    /// Returns the number of written entries, from the counts kept in memory.
+    ///
+    /// Unlike [`Nvs::statistics`] no page is scanned. Strings and blobs count with all entries
+    /// holding their data.
+    pub fn used_entries(&self) -> u32 {
+        self.pages.iter().map(|page| page.used_entry_count as u32).sum()
+    }
+
+    /// Returns the number of entries of all pages except the ones kept in reserve for
+    /// defragmentation, see [`Nvs::set_reserved_pages`].
+    pub fn capacity_entries(&self) -> u32 {
+        ((self.sectors as usize).saturating_sub(self.reserved_pages as usize) * ENTRIES_PER_PAGE) as u32
+    }
+
+    /// Returns [`Nvs::used_entries`] relative to [`Nvs::capacity_entries`], between 0 and 1.
+    pub fn usage_ratio(&self) -> f32 {
+        match self.capacity_entries() {
+            0 => 0.0,
+            capacity => self.used_entries() as f32 / capacity as f32,
+        }
+    }
+
+    /// Returns the address and the number of erases of every sector of the partition.
+    ///
+    /// The erases are only counted since this instance was created, they are not persisted.
+    pub fn erase_counts(&self) -> Vec<(usize, u32)> {
+        self.erase_counts
+            .iter()
+            .enumerate()
+            .map(|(sector, count)| (self.base_address + sector * FLASH_SECTOR_SIZE, *count))
+            .collect()
+    }
+
+    /// Returns the address and the sequence number of every sector of the partition.
+    ///
+    /// Free sectors, including erased or corrupt ones, have no sequence. The sequences of the
+    /// other sectors are expected to be unique, the highest one belonging to the active page.
+    pub fn page_sequences(&self) -> Vec<(usize, Option<u32>)> {
+        let mut sequences = self
+            .pages
+            .iter()
+            .map(|page| (page.address, Some(page.header.sequence)))
+            .chain(self.free_pages.iter().map(|page| (page.address, None)))
+            .collect::<Vec<_>>();
+        sequences.sort_unstable_by_key(|(address, _)| *address);
+        sequences
+    }
+
+    /// Returns the sequence number the next initialized page gets, one more than the highest
+    /// sequence in use or zero if all pages are free.
+    pub fn next_sequence(&self) -> u32 {
+        self.get_next_sequence()
+    }
+
+    /// Returns the number of free pages kept in reserve, see [`Nvs::set_reserved_pages`].
+    pub fn reserved_pages(&self) -> u16 {
+        self.reserved_pages
+    }
+
+    /// Returns the limit for the size of blobs in this partition. Like
+    /// [`MAX_BLOB_SIZE`](crate::MAX_BLOB_SIZE) the limit is exclusive, blobs must be smaller.
+    ///
+    /// This is [`MAX_BLOB_SIZE`](crate::MAX_BLOB_SIZE) or less for partitions with too few pages
+    /// to hold that many chunks, as the reserved pages are always kept free, see
+    /// [`Nvs::set_reserved_pages`]. Blobs of this size or larger are rejected with
+    /// [`Error::ValueTooLong`] before anything is written.
+    pub fn max_blob_size(&self) -> usize {
+        self.max_blob_size
+    }
+
+    /// Set the number of free pages kept in reserve, one by default like the C++ driver.
+    ///
+    /// A page is reclaimed once no other free page than the reserved ones is left, so at least
+    /// one page is needed to copy the remaining entries of the reclaimed page to. Before the last
+    /// other free page is used, a full page with at least half of its entries erased is reclaimed
+    /// instead, which keeps that page free for longer. Every additional reserved page is not
+    /// available for data, i.e. [`Error::FlashFull`] is returned
+    /// [`ENTRIES_PER_PAGE`](crate::ENTRIES_PER_PAGE) entries earlier and pages are reclaimed
+    /// earlier as well.
+    ///
+    /// Returns [`Error::InvalidReservedPages`] if `pages` is zero or doesn't leave at least one
+    /// page for the data.
+    pub fn set_reserved_pages(&mut self, pages: u16) -> Result<(), Error> {
+        if pages == 0 || pages >= self.sectors {
+            return Err(Error::InvalidReservedPages);
+        }
+
+        self.reserved_pages = pages;
+        self.max_blob_size = max_blob_size(self.sectors, pages);
+        Ok(())
+    }
+
+    /// Returns the largest number of contiguous free entries on any active page.
+    ///
+    /// Strings and single page blobs can't be split across pages, so this is an upper bound of
+    /// what can be written without starting a new page.
+    pub fn largest_free_run(&self) -> usize {
+        self.pages
+            .iter()
+            .filter(|page| *page.get_state() == ThinPageState::Active)
+            .map(|page| page.get_free_entry_count())
+            .max()
+            .unwrap_or(0)
+    }
+
     /// Returns detailed statistics about the NVS partition usage
     pub fn statistics(&mut self) -> Result<NvsStatistics, Error> {
         if self.faulted {
@@ -232,7 +823,7 @@ impl<T: Platform> Nvs<T> {
                     ThinPageState::Freeing => page_stats.erasing += 1,
                     ThinPageState::Corrupt => page_stats.corrupted += 1,
                     ThinPageState::Invalid => page_stats.corrupted += 1,
-                    ThinPageState::Uninitialized => page_stats.empty += 1,
+                    ThinPageState::Uninitialized | ThinPageState::Blank => page_stats.empty += 1,
                 }
 
                 if *page.get_state() == ThinPageState::Corrupt {
@@ -275,6 +866,53 @@ impl<T: Platform> Nvs<T> {
             entries_overall,
         })
     }
+
+    /// Returns the usage of every namespace, including namespaces without any entries.
+    ///
+    /// The entry defining the namespace itself is not attributed to it. Blobs count their index
+    /// and all data chunks.
+    pub fn namespace_statistics(&mut self) -> Result<BTreeMap<Key, NamespaceStatistics>, Error> {
+        if self.faulted {
+            return Err(Error::FlashError);
+        }
+
+        let mut statistics: BTreeMap<u8, NamespaceStatistics> = self
+            .namespaces
+            .values()
+            .map(|index| (*index, NamespaceStatistics::default()))
+            .collect();
+
+        let result = IterLoadedItems::new(&self.pages, &mut self.hal).try_for_each(|item| {
+            let item = item?;
+            if let Some(stats) = statistics.get_mut(&item.namespace_index) {
+                stats.entries += item.span as u32;
+                if matches!(item.type_, ItemType::Sized | ItemType::BlobData | ItemType::Blob) {
+                    stats.data_size += unsafe { item.data.sized.size } as u32;
+                }
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            if e == Error::FlashError {
+                self.faulted = true;
+            }
+            return Err(e);
+        }
+
+        Ok(self
+            .namespaces
+            .iter()
+            .map(|(name, index)| (*name, statistics.remove(index).unwrap_or_default()))
+            .collect())
+    }
+}
+
+/// The chunks of a blob can use every page but the reserved ones. The first chunk shares its page
+/// with the namespace entry and the blob index needs an entry of its own. The size is exclusive
+/// like [`MAX_BLOB_SIZE`].
+fn max_blob_size(sectors: u16, reserved_pages: u16) -> usize {
+    let pages = sectors.saturating_sub(reserved_pages) as usize;
+    MAX_BLOB_SIZE.min((pages * MAX_BLOB_DATA_PER_PAGE).saturating_sub(2 * size_of::<Item>()))
 }
 
 /// Iterator over items across all loaded pages.
@@ -327,15 +965,11 @@ impl<'a, T: Platform> IterKeys<'a, T> {
         }
     }
 
-    fn item_to_keys(&self, item: Item) -> (Key, Key) {
-        let (namespace_key, _) = self
-            .namespaces
-            .iter()
-            .find(|(_, idx)| **idx == item.namespace_index)
-            // a key should always have a namespace
-            .unwrap();
+    /// Returns `None` for items whose namespace entry was lost, e.g. on a corrupted page.
+    fn item_to_keys(&self, item: Item) -> Option<(Key, Key)> {
+        let (namespace_key, _) = self.namespaces.iter().find(|(_, idx)| **idx == item.namespace_index)?;
 
-        (*namespace_key, item.key)
+        Some((*namespace_key, item.key))
     }
 }
 
@@ -359,7 +993,10 @@ impl<'a, T: Platform> Iterator for IterKeys<'a, T> {
                         continue;
                     }
 
-                    Some(Ok(self.item_to_keys(item)))
+                    match self.item_to_keys(item) {
+                        Some(keys) => Some(Ok(keys)),
+                        None => continue,
+                    }
                 }
                 Err(err) => Some(Err(err)),
             };
@@ -381,14 +1018,11 @@ impl<'a, T: Platform> IterTypedEntries<'a, T> {
         }
     }
 
-    fn item_to_entry(&self, item: Item) -> (Key, Key, ItemType) {
-        let (namespace_key, _) = self
-            .namespaces
-            .iter()
-            .find(|(_, idx)| **idx == item.namespace_index)
-            .unwrap();
+    /// Returns `None` for items whose namespace entry was lost, e.g. on a corrupted page.
+    fn item_to_entry(&self, item: Item) -> Option<(Key, Key, ItemType)> {
+        let (namespace_key, _) = self.namespaces.iter().find(|(_, idx)| **idx == item.namespace_index)?;
 
-        (*namespace_key, item.key, item.type_)
+        Some((*namespace_key, item.key, item.type_))
     }
 }
 
@@ -410,7 +1044,10 @@ impl<'a, T: Platform> Iterator for IterTypedEntries<'a, T> {
                     }
 
                     // Include BlobIndex, legacy Blob (0x41), primitives, and Sized
-                    Some(Ok(self.item_to_entry(item)))
+                    match self.item_to_entry(item) {
+                        Some(entry) => Some(Ok(entry)),
+                        None => continue,
+                    }
                 }
                 Err(err) => Some(Err(err)),
             };
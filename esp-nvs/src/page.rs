@@ -27,6 +27,7 @@ use crate::error::Error::{
     KeyNotFound,
     PageFull,
 };
+use crate::hash_index::HashIndex;
 use crate::platform::{
     AlignedOps,
     Platform,
@@ -35,6 +36,7 @@ use crate::raw::{
     ENTRIES_PER_PAGE,
     ENTRY_STATE_BITMAP_SIZE,
     EntryMapState,
+    ITEM_SIZE,
     Item,
     ItemData,
     ItemType,
@@ -69,6 +71,12 @@ impl ThinPage {
         }
     }
 
+    pub(crate) fn blank(address: usize) -> Self {
+        let mut page = Self::uninitialized(address);
+        page.header.state = ThinPageState::Blank;
+        page
+    }
+
     pub(crate) fn initialize<T: Platform>(&mut self, hal: &mut T, next_sequence: u32) -> Result<(), Error> {
         #[cfg(feature = "defmt")]
         trace!("initialize: @{:#08x}", self.address);
@@ -131,10 +139,10 @@ impl ThinPage {
             return Err(KeyNotFound);
         }
 
-        // Safety: we check the crc afterwards
-        let item = unsafe { core::mem::transmute::<[u8; 32], Item>(buf) };
+        // corrupted entries are skipped like entries with a CRC mismatch
+        let item = Item::from_raw(buf).ok_or(KeyNotFound)?;
 
-        if item.crc != item.calculate_crc32(T::crc32) {
+        if item.crc != item.calculate_crc32(T::crc32) || !item.span_is_valid(item_index) {
             return Err(KeyNotFound);
         }
 
@@ -145,6 +153,7 @@ impl ThinPage {
     pub(crate) fn write_item<T: Platform>(
         &mut self,
         hal: &mut T,
+        hash_index: &mut HashIndex,
         namespace_index: u8,
         key: Key,
         type_: ItemType,
@@ -181,10 +190,12 @@ impl ThinPage {
 
         // Add to hash list if this is not a namespace entry (namespace_index == 0)
         if namespace_index != 0 {
+            let hash = item.calculate_hash(T::crc32);
             self.item_hash_list.push(ItemHashListEntry {
-                hash: item.calculate_hash(T::crc32),
+                hash,
                 index: item_index as u8,
             });
+            hash_index.insert(hash, self.address, item_index as u8);
         }
 
         // Check if page is now full by trying to find the next free entry
@@ -195,18 +206,80 @@ impl ThinPage {
         Ok(())
     }
 
-    pub(crate) fn write_namespace<T: Platform>(&mut self, hal: &mut T, key: Key, value: u8) -> Result<(), Error> {
+    /// Write items of a single entry each to consecutive entries with a single write and mark them
+    /// as written with a single update of the entry state bitmap. The items must fit into the free
+    /// entries of the page.
+    pub(crate) fn write_items<T: Platform>(
+        &mut self,
+        hal: &mut T,
+        hash_index: &mut HashIndex,
+        items: &[Item],
+    ) -> Result<(), Error> {
+        #[cfg(feature = "debug-logs")]
+        println!("internal: write_items");
+
+        let start_index = self.get_next_free_entry();
+        if items.len() > ENTRIES_PER_PAGE - start_index {
+            return Err(PageFull);
+        }
+
+        #[cfg(feature = "defmt")]
+        trace!(
+            "write_items: @{:#08x}[{}-{}]",
+            self.address,
+            start_index,
+            start_index + items.len() - 1
+        );
+
+        let mut buf = Vec::with_capacity(size_of_val(items));
+        for item in items {
+            let raw_item = RawItem { item: *item };
+            buf.extend_from_slice(unsafe { &raw_item.raw });
+        }
+        let target_addr = self.address + offset_of!(RawPage, items) + size_of::<Item>() * start_index;
+        write_aligned(hal, target_addr as _, &buf).map_err(|_| Error::FlashError)?;
+
+        let end_index = start_index + items.len();
+        self.set_entry_state_range(hal, start_index as u8..end_index as u8, EntryMapState::Written)?;
+
+        self.used_entry_count += items.len() as u8;
+
+        for (index, item) in (start_index..end_index).zip(items) {
+            let hash = item.calculate_hash(T::crc32);
+            self.item_hash_list.push(ItemHashListEntry {
+                hash,
+                index: index as u8,
+            });
+            hash_index.insert(hash, self.address, index as u8);
+        }
+
+        if end_index == ENTRIES_PER_PAGE {
+            self.mark_as_full::<T>(hal)?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn write_namespace<T: Platform>(
+        &mut self,
+        hal: &mut T,
+        hash_index: &mut HashIndex,
+        key: Key,
+        value: u8,
+    ) -> Result<(), Error> {
         #[cfg(feature = "defmt")]
         trace!("write_namespace: @{:#08x}", self.address);
 
         let mut buf = [u8::MAX; 8];
         buf[..1].copy_from_slice(&value.to_le_bytes());
-        self.write_item::<T>(hal, 0, key, ItemType::U8, None, 1, ItemData { raw: buf })
+        self.write_item::<T>(hal, hash_index, 0, key, ItemType::U8, None, 1, ItemData { raw: buf })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn write_variable_sized_item<T: Platform>(
         &mut self,
         hal: &mut T,
+        hash_index: &mut HashIndex,
         namespace_index: u8,
         key: Key,
         type_: ItemType,
@@ -271,10 +344,12 @@ impl ThinPage {
             EntryMapState::Written,
         )?;
 
+        let hash = item.calculate_hash(T::crc32);
         self.item_hash_list.push(ItemHashListEntry {
-            hash: item.calculate_hash(T::crc32),
+            hash,
             index: start_index as u8,
         });
+        hash_index.insert(hash, self.address, start_index as u8);
         self.used_entry_count += span as u8;
 
         if start_index + span == ENTRIES_PER_PAGE {
@@ -296,7 +371,8 @@ impl ThinPage {
             "load_referenced_data: @{:#08x}[{}-{}]",
             self.address,
             item_index + 1,
-            item_index + item.span
+            // the span isn't validated yet
+            item_index as usize + item.span as usize
         );
 
         #[cfg(feature = "debug-logs")]
@@ -307,6 +383,11 @@ impl ThinPage {
             _ => return Err(ItemTypeMismatch(item.type_)),
         }
 
+        // don't read beyond the page if the item is corrupted
+        if !item.span_is_valid(item_index) {
+            return Err(Error::CorruptedData);
+        }
+
         let size = unsafe { item.data.sized.size } as usize;
         let aligned_size = T::align_read(size);
 
@@ -330,6 +411,52 @@ impl ThinPage {
         Ok(buf)
     }
 
+    /// Same as [`ThinPage::load_referenced_data`] but reads into `buf` instead of allocating.
+    ///
+    /// Returns the data, or [`Error::BufferTooSmall`] if `buf` is shorter than the data.
+    pub(crate) fn load_referenced_data_into<'b, T: Platform>(
+        &self,
+        hal: &mut T,
+        // this is the index of the given &Item, not the start of the data which is +1
+        item_index: u8,
+        item: &Item,
+        buf: &'b mut [u8],
+    ) -> Result<&'b [u8], Error> {
+        #[cfg(feature = "defmt")]
+        trace!("load_referenced_data_into: @{:#08x}[{}]", self.address, item_index + 1);
+
+        match item.type_ {
+            ItemType::Sized | ItemType::BlobData | ItemType::Blob => {}
+            _ => return Err(ItemTypeMismatch(item.type_)),
+        }
+
+        // don't read beyond the page if the item is corrupted
+        if !item.span_is_valid(item_index) {
+            return Err(Error::CorruptedData);
+        }
+
+        let size = unsafe { item.data.sized.size } as usize;
+        if buf.len() < size {
+            return Err(Error::BufferTooSmall(size));
+        }
+
+        // whole entries are read directly into `buf`, the last partial one through a copy, so
+        // reads stay aligned to entries like they are for encrypted partitions
+        let address = self.address + offset_of!(RawPage, items) + size_of::<Item>() * (item_index as usize + 1);
+        let direct = size / ITEM_SIZE * ITEM_SIZE;
+        hal.read(address as _, &mut buf[..direct])
+            .map_err(|_| Error::FlashError)?;
+
+        if direct < size {
+            let mut tail = [0u8; ITEM_SIZE];
+            hal.read((address + direct) as _, &mut tail)
+                .map_err(|_| Error::FlashError)?;
+            buf[direct..size].copy_from_slice(&tail[..size - direct]);
+        }
+
+        Ok(&buf[..size])
+    }
+
     pub(crate) fn set_entry_state<T: Platform>(
         &mut self,
         hal: &mut T,
@@ -376,15 +503,7 @@ impl ThinPage {
             self.address, indices.start, indices.end, state
         );
 
-        let raw_state = state as u8;
-        for item_index in indices.clone() {
-            let mask = 0b11u8 << ((item_index % 4) * 2);
-            let bits = raw_state << ((item_index % 4) * 2);
-            let masked_bits = bits | !mask;
-
-            let offset_in_map = item_index / 4;
-            self.entry_state_bitmap[offset_in_map as usize] &= masked_bits;
-        }
+        self.update_entry_state_range(indices.clone(), state);
 
         let start_byte = (indices.start / 4) as usize;
         let end_byte = ((indices.end - 1) / 4) as usize;
@@ -409,6 +528,19 @@ impl ThinPage {
         .map_err(|_| Error::FlashError)
     }
 
+    /// Only updates the in-memory copy of the entry state bitmap without touching the flash
+    pub(crate) fn update_entry_state_range(&mut self, indices: Range<u8>, state: EntryMapState) {
+        let raw_state = state as u8;
+        for item_index in indices {
+            let mask = 0b11u8 << ((item_index % 4) * 2);
+            let bits = raw_state << ((item_index % 4) * 2);
+            let masked_bits = bits | !mask;
+
+            let offset_in_map = item_index / 4;
+            self.entry_state_bitmap[offset_in_map as usize] &= masked_bits;
+        }
+    }
+
     pub(crate) fn get_next_free_entry(&self) -> usize {
         self.used_entry_count as usize + self.erased_entry_count as usize
     }
@@ -443,7 +575,13 @@ impl ThinPage {
         (empty, written, erased, illegal)
     }
 
-    pub(crate) fn erase_item<T: Platform>(&mut self, hal: &mut T, item_index: u8, span: u8) -> Result<(), Error> {
+    pub(crate) fn erase_item<T: Platform>(
+        &mut self,
+        hal: &mut T,
+        hash_index: &mut HashIndex,
+        item_index: u8,
+        span: u8,
+    ) -> Result<(), Error> {
         #[cfg(feature = "defmt")]
         trace!(
             "erase_item: @{:#08x}[{}-{}]",
@@ -459,7 +597,10 @@ impl ThinPage {
 
         self.erased_entry_count += span;
         self.used_entry_count -= span;
-        self.item_hash_list.retain(|entry| entry.index != item_index);
+        if let Some(pos) = self.item_hash_list.iter().position(|entry| entry.index == item_index) {
+            let hash = self.item_hash_list.remove(pos).hash;
+            hash_index.remove(hash, self.address, item_index);
+        }
 
         Ok(())
     }
@@ -591,6 +732,9 @@ impl Debug for ThinPageHeader {
 #[derive(strum::Display, PartialEq)]
 pub(crate) enum ThinPageState {
     Uninitialized,
+    /// Free page without any NVS data that wasn't erased, e.g. flash that was shipped zeroed. It
+    /// is erased before it is used.
+    Blank,
     Active,
     Full,
     Freeing,
@@ -613,5 +757,7 @@ pub(crate) struct Namespace {
 /// Result of loading a page from flash.
 pub(crate) enum LoadPageResult {
     Empty(ThinPage),
-    Used(ThinPage, Vec<Namespace>, crate::blob::BlobIndex),
+    /// The page, its namespaces, its blob chunks and the `(namespace_index, key)` of its legacy
+    /// blobs.
+    Used(ThinPage, Vec<Namespace>, crate::blob::BlobIndex, Vec<(u8, Key)>),
 }
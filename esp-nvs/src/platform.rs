@@ -12,8 +12,14 @@ impl<T: Crc + NorFlash> Platform for T {}
 
 pub type FnCrc32 = fn(init: u32, data: &[u8]) -> u32;
 
+/// The CRC32 used for all checksums of the NVS format.
+///
+/// Defaults to [`software_crc32`], which matches the ESP-IDF ROM `crc32_le`. Override it to use a
+/// hardware implementation.
 pub trait Crc {
-    fn crc32(init: u32, data: &[u8]) -> u32;
+    fn crc32(init: u32, data: &[u8]) -> u32 {
+        software_crc32(init, data)
+    }
 }
 
 impl<T: Crc> Crc for &mut T {
@@ -86,6 +92,79 @@ const fn align_floor(size: usize, alignment: usize) -> usize {
 
 impl<T: Platform> AlignedOps for T {}
 
+#[cfg(feature = "embedded-storage")]
+pub use nor_flash::NorFlashPlatform;
+
+#[cfg(feature = "embedded-storage")]
+mod nor_flash {
+    use embedded_storage::nor_flash::{
+        ErrorType,
+        NorFlash,
+        ReadNorFlash,
+    };
+
+    use crate::platform::Crc;
+
+    /// Adapter that turns any [`NorFlash`] into a [`crate::platform::Platform`].
+    ///
+    /// Reads, writes and erases are forwarded to the wrapped flash, including its `READ_SIZE` and
+    /// `WRITE_SIZE` alignment. The CRC32 is calculated in software using
+    /// [`crate::platform::software_crc32`].
+    ///
+    /// ```
+    /// use esp_nvs::mem_flash::MemFlash;
+    /// use esp_nvs::platform::NorFlashPlatform;
+    ///
+    /// let nvs = esp_nvs::Nvs::new(0, 3 * 4096, NorFlashPlatform::new(MemFlash::new(3))).unwrap();
+    /// ```
+    pub struct NorFlashPlatform<F> {
+        flash: F,
+    }
+
+    impl<F: NorFlash> NorFlashPlatform<F> {
+        /// Wrap the given flash.
+        pub fn new(flash: F) -> Self {
+            Self { flash }
+        }
+
+        /// Consume the adapter and return the wrapped flash.
+        pub fn into_inner(self) -> F {
+            self.flash
+        }
+    }
+
+    impl<F: NorFlash> ErrorType for NorFlashPlatform<F> {
+        type Error = F::Error;
+    }
+
+    impl<F: NorFlash> ReadNorFlash for NorFlashPlatform<F> {
+        const READ_SIZE: usize = F::READ_SIZE;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            self.flash.read(offset, bytes)
+        }
+
+        fn capacity(&self) -> usize {
+            self.flash.capacity()
+        }
+    }
+
+    impl<F: NorFlash> NorFlash for NorFlashPlatform<F> {
+        const WRITE_SIZE: usize = F::WRITE_SIZE;
+        const ERASE_SIZE: usize = F::ERASE_SIZE;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            self.flash.erase(from, to)
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            self.flash.write(offset, bytes)
+        }
+    }
+
+    impl<F> Crc for NorFlashPlatform<F> {}
+}
+
 #[cfg(any(
     feature = "esp32",
     feature = "esp32s2",
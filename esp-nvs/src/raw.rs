@@ -299,6 +299,18 @@ pub(crate) struct ItemDataBlobIndex {
     pub(crate) size: u32,
     pub(crate) chunk_count: u8,
     pub(crate) chunk_start: u8,
+    _reserved: u16,
+}
+
+impl ItemDataBlobIndex {
+    pub(crate) fn new(size: u32, chunk_count: u8, chunk_start: u8) -> Self {
+        Self {
+            size,
+            chunk_count,
+            chunk_start,
+            _reserved: u16::MAX,
+        }
+    }
 }
 
 #[cfg(feature = "debug-logs")]
@@ -314,6 +326,30 @@ impl Debug for ItemDataBlobIndex {
 }
 
 impl Item {
+    /// Interpret an entry read from flash, returns `None` if the type is unknown.
+    pub(crate) fn from_raw(raw: [u8; size_of::<Item>()]) -> Option<Item> {
+        ItemType::from_repr(raw[1])?;
+
+        // Safety: all other fields are valid for any bit pattern
+        Some(unsafe { transmute::<[u8; 32], Item>(raw) })
+    }
+
+    /// Returns `true` if the entries of the item at `item_index` don't reach past the page and
+    /// hold all of its data.
+    pub(crate) fn span_is_valid(&self, item_index: u8) -> bool {
+        if self.span == 0 || item_index as usize + self.span as usize > ENTRIES_PER_PAGE {
+            return false;
+        }
+
+        match self.type_ {
+            ItemType::Sized | ItemType::BlobData | ItemType::Blob => {
+                let size = unsafe { self.data.sized.size } as usize;
+                size <= (self.span as usize - 1) * size_of::<Item>()
+            }
+            _ => true,
+        }
+    }
+
     pub(crate) fn calculate_hash(&self, crc32: FnCrc32) -> u24 {
         Self::calculate_hash_ref(crc32, self.namespace_index, &self.key, self.chunk_index)
     }
@@ -392,8 +428,9 @@ pub(crate) fn write_aligned<T: Platform>(hal: &mut T, offset: u32, bytes: &[u8])
         }
 
         // no need to write the trailer if remaining data is all ones - this the default state of
-        // the flash
-        if bytes[pivot..].iter().any(|&e| e != 0xFF) {
+        // the flash. Platforms writing whole entries (e.g. encryption) transform the data, so the
+        // trailer has to be written regardless.
+        if T::WRITE_SIZE >= ITEM_SIZE || bytes[pivot..].iter().any(|&e| e != 0xFF) {
             let mut buf = vec![0xFFu8; T::WRITE_SIZE];
             buf[..trailer.len()].copy_from_slice(trailer);
             hal.write(offset + (pivot as u32), &buf)?
@@ -1,11 +1,14 @@
 //! The `Set<T>` trait and its implementations allow providing a single generic,
 //! overloaded function `set<T>()` for all supported types of the driver.
 
+use crate::array::ArrayElement;
 use crate::error::Error;
 use crate::platform::Platform;
 use crate::{
     Key,
+    LegacyBlob,
     Nvs,
+    array,
     raw,
 };
 
@@ -84,3 +87,16 @@ impl<T: Platform> Set<&[u8]> for Nvs<T> {
         self.set_blob(namespace, *key, value)
     }
 }
+
+impl<T: Platform> Set<LegacyBlob<'_>> for Nvs<T> {
+    fn set(&mut self, namespace: &Key, key: &Key, value: LegacyBlob<'_>) -> Result<(), Error> {
+        self.set_legacy_blob(namespace, *key, value.0)
+    }
+}
+
+/// Stores the slice as typed array, see [`ArrayElement`] for the layout.
+impl<T: Platform, E: ArrayElement> Set<&[E]> for Nvs<T> {
+    fn set(&mut self, namespace: &Key, key: &Key, value: &[E]) -> Result<(), Error> {
+        self.set_blob(namespace, *key, &array::encode(value))
+    }
+}
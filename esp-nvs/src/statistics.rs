@@ -4,9 +4,11 @@
 //! an NVS partition.
 
 use alloc::vec::Vec;
+use core::fmt;
 
 /// Overall statistics for an NVS partition.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct NvsStatistics {
     pub pages: PageStatistics,
     pub entries_per_page: Vec<EntryStatistics>,
@@ -15,6 +17,7 @@ pub struct NvsStatistics {
 
 /// Statistics about page states in the partition.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct PageStatistics {
     pub empty: u16,
     pub active: u16,
@@ -25,9 +28,141 @@ pub struct PageStatistics {
 
 /// Statistics about entry states within pages.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct EntryStatistics {
     pub empty: u32,
     pub written: u32,
     pub erased: u32,
     pub illegal: u32,
 }
+
+/// Usage of a single namespace, see [`crate::Nvs::namespace_statistics`].
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NamespaceStatistics {
+    /// Written entries, including the ones holding string and blob data.
+    pub entries: u32,
+    /// Size of all string and blob values in bytes.
+    pub data_size: u32,
+}
+
+impl NvsStatistics {
+    /// Share of written entries in all entries of the partition, in percent.
+    pub fn usage_percent(&self) -> u8 {
+        self.entries_overall.usage_percent()
+    }
+
+    /// A single line summary of the page and entry totals, e.g. for logging with `defmt`.
+    pub fn summary(&self) -> StatisticsSummary<'_> {
+        StatisticsSummary(self)
+    }
+}
+
+impl PageStatistics {
+    /// Returns the number of pages in the partition.
+    pub fn total(&self) -> u16 {
+        self.empty + self.active + self.full + self.erasing + self.corrupted
+    }
+}
+
+impl EntryStatistics {
+    /// Returns the number of entries, in all states.
+    pub fn total(&self) -> u32 {
+        self.empty + self.written + self.erased + self.illegal
+    }
+
+    /// Share of written entries in all entries, in percent.
+    pub fn usage_percent(&self) -> u8 {
+        match self.total() {
+            0 => 0,
+            total => (self.written as u64 * 100 / total as u64) as u8,
+        }
+    }
+}
+
+impl fmt::Display for PageStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} pages: {} active, {} full, {} empty, {} erasing, {} corrupted",
+            self.total(),
+            self.active,
+            self.full,
+            self.empty,
+            self.erasing,
+            self.corrupted
+        )
+    }
+}
+
+impl fmt::Display for EntryStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} entries: {} written, {} erased, {} empty, {} illegal ({}% used)",
+            self.total(),
+            self.written,
+            self.erased,
+            self.empty,
+            self.illegal,
+            self.usage_percent()
+        )
+    }
+}
+
+/// Prints the page and entry totals on one line each, the statistics of the single pages are
+/// left out.
+impl fmt::Display for NvsStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.pages)?;
+        writeln!(f, "{}", self.entries_overall)
+    }
+}
+
+/// The totals of [`NvsStatistics`] on a single line, see [`NvsStatistics::summary`].
+pub struct StatisticsSummary<'a>(&'a NvsStatistics);
+
+impl fmt::Display for StatisticsSummary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pages = &self.0.pages;
+        let entries = &self.0.entries_overall;
+        write!(
+            f,
+            "pages {}/{}/{}/{}/{} (active/full/empty/erasing/corrupted), entries {}/{}/{}/{} \
+             (written/erased/empty/illegal), {}% used",
+            pages.active,
+            pages.full,
+            pages.empty,
+            pages.erasing,
+            pages.corrupted,
+            entries.written,
+            entries.erased,
+            entries.empty,
+            entries.illegal,
+            entries.usage_percent()
+        )
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for StatisticsSummary<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        let pages = &self.0.pages;
+        let entries = &self.0.entries_overall;
+        defmt::write!(
+            f,
+            "pages {}/{}/{}/{}/{} (active/full/empty/erasing/corrupted), entries {}/{}/{}/{} \
+             (written/erased/empty/illegal), {}% used",
+            pages.active,
+            pages.full,
+            pages.empty,
+            pages.erasing,
+            pages.corrupted,
+            entries.written,
+            entries.erased,
+            entries.empty,
+            entries.illegal,
+            entries.usage_percent()
+        );
+    }
+}
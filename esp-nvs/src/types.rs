@@ -3,7 +3,13 @@
 //! These types provide type safety for various index and sequence values
 //! used throughout the NVS implementation.
 
+use alloc::string::String;
+use alloc::vec::Vec;
 use core::fmt;
+use core::fmt::Write;
+
+use crate::error::Error;
+use crate::raw::ItemType;
 
 /// Maximum Key length is 15 bytes + 1 byte for the null terminator.
 pub const MAX_KEY_LENGTH: usize = 15;
@@ -54,27 +60,105 @@ impl Key {
     ///
     /// Tip: use a const context if possible to ensure that the key is transformed at compile time:
     ///   `let my_key = const { Key::from_str("my_key") };`
+    ///
+    /// Unlike [`FromStr::from_str`](core::str::FromStr::from_str), used by `"my_key".parse()`, this
+    /// panics if the key is too long.
     pub const fn from_str(s: &str) -> Self {
         let bytes = s.as_bytes();
         Self::from_slice(bytes)
     }
 
+    /// Fallible variant of [`Key::from_slice`] for keys that are only known at runtime.
+    ///
+    /// Returns [`Error::KeyTooLong`] if `src` is longer than [`MAX_KEY_LENGTH`] and
+    /// [`Error::KeyMalformed`] if it contains a null byte.
+    pub const fn try_from_slice(src: &[u8]) -> Result<Self, Error> {
+        if src.len() > MAX_KEY_LENGTH {
+            return Err(Error::KeyTooLong);
+        }
+        let mut dst = [0u8; MAX_KEY_NUL_TERMINATED_LENGTH];
+        let mut i = 0;
+        while i < src.len() {
+            if src[i] == 0 {
+                return Err(Error::KeyMalformed);
+            }
+            dst[i] = src[i];
+            i += 1;
+        }
+        Ok(Self(dst))
+    }
+
+    /// Fallible variant of [`Key::from_str`] for keys that are only known at runtime, see
+    /// [`Key::try_from_slice`].
+    pub const fn try_from_str(s: &str) -> Result<Self, Error> {
+        Self::try_from_slice(s.as_bytes())
+    }
+
+    /// Creates a key from a null-terminated C string, e.g. one passed over FFI.
+    ///
+    /// Returns [`Error::KeyTooLong`] if the string without its terminator is longer than
+    /// [`MAX_KEY_LENGTH`].
+    pub const fn from_cstr(s: &core::ffi::CStr) -> Result<Self, Error> {
+        // a CStr can't contain interior null bytes, so this only checks the length
+        Self::try_from_slice(s.to_bytes())
+    }
+
     /// Converts a key to a byte array.
     pub const fn as_bytes(&self) -> &[u8; MAX_KEY_NUL_TERMINATED_LENGTH] {
         &self.0
     }
 
-    /// Returns the key as a string slice, excluding null padding.
-    pub fn as_str(&self) -> &str {
+    /// Returns the key as a string slice, up to the first null byte.
+    ///
+    /// Keys written by other tools aren't guaranteed to be valid UTF-8, use the [`fmt::Display`]
+    /// implementation for a lossy representation.
+    pub fn as_str(&self) -> Result<&str, core::str::Utf8Error> {
+        core::str::from_utf8(self.trimmed())
+    }
+
+    /// Whether the key is null terminated and has no bytes after the first null byte, which
+    /// would be ignored when the key is read back and let different keys alias each other.
+    pub(crate) fn is_well_formed(&self) -> bool {
+        let len = self.trimmed().len();
+        len <= MAX_KEY_LENGTH && self.0[len..].iter().all(|&b| b == 0)
+    }
+
+    /// The key without the null padding
+    pub(crate) fn trimmed(&self) -> &[u8] {
         let len = self.0.iter().position(|&b| b == 0).unwrap_or(self.0.len());
-        // Safety: NVS keys are always valid ASCII/UTF-8
-        unsafe { core::str::from_utf8_unchecked(&self.0[..len]) }
+        &self.0[..len]
+    }
+}
+
+/// Same as [`Key::try_from_slice`].
+impl TryFrom<&[u8]> for Key {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> Result<Self, Error> {
+        Self::try_from_slice(value)
+    }
+}
+
+/// Same as [`Key::try_from_str`]. Note that the inherent, const [`Key::from_str`] takes precedence
+/// over this one when called as `Key::from_str`, use `s.parse::<Key>()` instead.
+impl core::str::FromStr for Key {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Self::try_from_str(s)
     }
 }
 
 impl fmt::Display for Key {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.as_str())
+        // same as String::from_utf8_lossy, which isn't available in core
+        for chunk in self.trimmed().utf8_chunks() {
+            f.write_str(chunk.valid())?;
+            if !chunk.invalid().is_empty() {
+                f.write_char(char::REPLACEMENT_CHARACTER)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -132,6 +216,126 @@ impl AsRef<[u8]> for Key {
     }
 }
 
+/// Compares the bytes up to the first null byte, so `Key::from_str("my_key") == "my_key"`.
+impl PartialEq<[u8]> for Key {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.trimmed() == other
+    }
+}
+
+impl PartialEq<&[u8]> for Key {
+    fn eq(&self, other: &&[u8]) -> bool {
+        self.trimmed() == *other
+    }
+}
+
+impl PartialEq<str> for Key {
+    fn eq(&self, other: &str) -> bool {
+        self.trimmed() == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for Key {
+    fn eq(&self, other: &&str) -> bool {
+        self.trimmed() == other.as_bytes()
+    }
+}
+
+/// The kind of value stored under a key, independent of how it is laid out on flash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EntryType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    Str,
+    /// Blobs, regardless of whether they are stored in chunks or in the legacy single page format.
+    Blob,
+}
+
+impl EntryType {
+    /// Returns `true` for the integer types.
+    pub const fn is_primitive(&self) -> bool {
+        self.byte_width().is_some()
+    }
+
+    /// Returns `true` for blobs.
+    pub const fn is_blob(&self) -> bool {
+        matches!(self, EntryType::Blob)
+    }
+
+    /// Returns the size of the value in bytes for the integer types.
+    pub const fn byte_width(&self) -> Option<usize> {
+        match self {
+            EntryType::U8 | EntryType::I8 => Some(1),
+            EntryType::U16 | EntryType::I16 => Some(2),
+            EntryType::U32 | EntryType::I32 => Some(4),
+            EntryType::U64 | EntryType::I64 => Some(8),
+            EntryType::Str | EntryType::Blob => None,
+        }
+    }
+}
+
+/// A value read back with the type it was written as, see [`crate::Nvs::iter_namespace`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodedValue {
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    U64(u64),
+    I64(i64),
+    Str(String),
+    /// Blobs, regardless of whether they are stored in chunks or in the legacy single page format.
+    Blob(Vec<u8>),
+}
+
+impl DecodedValue {
+    /// Returns the type the value was stored as.
+    pub const fn entry_type(&self) -> EntryType {
+        match self {
+            DecodedValue::U8(_) => EntryType::U8,
+            DecodedValue::I8(_) => EntryType::I8,
+            DecodedValue::U16(_) => EntryType::U16,
+            DecodedValue::I16(_) => EntryType::I16,
+            DecodedValue::U32(_) => EntryType::U32,
+            DecodedValue::I32(_) => EntryType::I32,
+            DecodedValue::U64(_) => EntryType::U64,
+            DecodedValue::I64(_) => EntryType::I64,
+            DecodedValue::Str(_) => EntryType::Str,
+            DecodedValue::Blob(_) => EntryType::Blob,
+        }
+    }
+}
+
+/// Fails with [`Error::ItemTypeMismatch`] for [`ItemType::Any`], which doesn't describe a value.
+impl TryFrom<ItemType> for EntryType {
+    type Error = Error;
+
+    fn try_from(value: ItemType) -> Result<Self, Error> {
+        Ok(match value {
+            ItemType::U8 => EntryType::U8,
+            ItemType::I8 => EntryType::I8,
+            ItemType::U16 => EntryType::U16,
+            ItemType::I16 => EntryType::I16,
+            ItemType::U32 => EntryType::U32,
+            ItemType::I32 => EntryType::I32,
+            ItemType::U64 => EntryType::U64,
+            ItemType::I64 => EntryType::I64,
+            ItemType::Sized => EntryType::Str,
+            ItemType::Blob | ItemType::BlobData | ItemType::BlobIndex => EntryType::Blob,
+            ItemType::Any => return Err(Error::ItemTypeMismatch(value)),
+        })
+    }
+}
+
 /// Index of an item within a page's entry array.
 pub(crate) struct ItemIndex(pub(crate) u8);
 
@@ -0,0 +1,211 @@
+//! Minimal AES-256 and XTS-AES implementation used for NVS encryption.
+//!
+//! ESP-IDF encrypts every 32 byte entry as a separate XTS data unit, so only full blocks are
+//! supported and ciphertext stealing is not implemented.
+
+const ROUNDS: usize = 14;
+
+#[rustfmt::skip]
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+const INV_SBOX: [u8; 256] = {
+    let mut inv = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        inv[SBOX[i] as usize] = i as u8;
+        i += 1;
+    }
+    inv
+};
+
+/// Multiplication in GF(2^8) with the AES polynomial
+const fn gmul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+pub(crate) struct Aes256 {
+    round_keys: [[u8; 16]; ROUNDS + 1],
+}
+
+impl Aes256 {
+    pub(crate) fn new(key: &[u8; 32]) -> Self {
+        let mut words = [[0u8; 4]; 4 * (ROUNDS + 1)];
+        for (idx, word) in words.iter_mut().take(8).enumerate() {
+            word.copy_from_slice(&key[idx * 4..idx * 4 + 4]);
+        }
+
+        let mut rcon = 1u8;
+        for idx in 8..words.len() {
+            let mut temp = words[idx - 1];
+            if idx % 8 == 0 {
+                temp.rotate_left(1);
+                temp = temp.map(|b| SBOX[b as usize]);
+                temp[0] ^= rcon;
+                rcon = gmul(rcon, 2);
+            } else if idx % 8 == 4 {
+                temp = temp.map(|b| SBOX[b as usize]);
+            }
+            for byte in 0..4 {
+                words[idx][byte] = words[idx - 8][byte] ^ temp[byte];
+            }
+        }
+
+        let mut round_keys = [[0u8; 16]; ROUNDS + 1];
+        for (round, round_key) in round_keys.iter_mut().enumerate() {
+            for word in 0..4 {
+                round_key[word * 4..word * 4 + 4].copy_from_slice(&words[round * 4 + word]);
+            }
+        }
+
+        Self { round_keys }
+    }
+
+    pub(crate) fn encrypt_block(&self, block: &mut [u8; 16]) {
+        add_round_key(block, &self.round_keys[0]);
+        for round in 1..ROUNDS {
+            sub_bytes(block, &SBOX);
+            shift_rows(block);
+            mix_columns(block);
+            add_round_key(block, &self.round_keys[round]);
+        }
+        sub_bytes(block, &SBOX);
+        shift_rows(block);
+        add_round_key(block, &self.round_keys[ROUNDS]);
+    }
+
+    pub(crate) fn decrypt_block(&self, block: &mut [u8; 16]) {
+        add_round_key(block, &self.round_keys[ROUNDS]);
+        for round in (1..ROUNDS).rev() {
+            inv_shift_rows(block);
+            sub_bytes(block, &INV_SBOX);
+            add_round_key(block, &self.round_keys[round]);
+            inv_mix_columns(block);
+        }
+        inv_shift_rows(block);
+        sub_bytes(block, &INV_SBOX);
+        add_round_key(block, &self.round_keys[0]);
+    }
+}
+
+fn add_round_key(block: &mut [u8; 16], round_key: &[u8; 16]) {
+    block.iter_mut().zip(round_key).for_each(|(b, k)| *b ^= k);
+}
+
+fn sub_bytes(block: &mut [u8; 16], table: &[u8; 256]) {
+    block.iter_mut().for_each(|b| *b = table[*b as usize]);
+}
+
+// the state is stored column by column, so row `r` of column `c` is at `c * 4 + r`
+fn shift_rows(block: &mut [u8; 16]) {
+    let old = *block;
+    for column in 0..4 {
+        for row in 1..4 {
+            block[column * 4 + row] = old[((column + row) % 4) * 4 + row];
+        }
+    }
+}
+
+fn inv_shift_rows(block: &mut [u8; 16]) {
+    let old = *block;
+    for column in 0..4 {
+        for row in 1..4 {
+            block[((column + row) % 4) * 4 + row] = old[column * 4 + row];
+        }
+    }
+}
+
+fn mix_columns(block: &mut [u8; 16]) {
+    for column in block.chunks_exact_mut(4) {
+        let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+        column[0] = gmul(a0, 2) ^ gmul(a1, 3) ^ a2 ^ a3;
+        column[1] = a0 ^ gmul(a1, 2) ^ gmul(a2, 3) ^ a3;
+        column[2] = a0 ^ a1 ^ gmul(a2, 2) ^ gmul(a3, 3);
+        column[3] = gmul(a0, 3) ^ a1 ^ a2 ^ gmul(a3, 2);
+    }
+}
+
+fn inv_mix_columns(block: &mut [u8; 16]) {
+    for column in block.chunks_exact_mut(4) {
+        let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+        column[0] = gmul(a0, 14) ^ gmul(a1, 11) ^ gmul(a2, 13) ^ gmul(a3, 9);
+        column[1] = gmul(a0, 9) ^ gmul(a1, 14) ^ gmul(a2, 11) ^ gmul(a3, 13);
+        column[2] = gmul(a0, 13) ^ gmul(a1, 9) ^ gmul(a2, 14) ^ gmul(a3, 11);
+        column[3] = gmul(a0, 11) ^ gmul(a1, 13) ^ gmul(a2, 9) ^ gmul(a3, 14);
+    }
+}
+
+/// XTS-AES-256 as specified in IEEE 1619, restricted to data units made of full blocks.
+pub(crate) struct Xts {
+    data: Aes256,
+    tweak: Aes256,
+}
+
+impl Xts {
+    pub(crate) fn new(data_key: &[u8; 32], tweak_key: &[u8; 32]) -> Self {
+        Self {
+            data: Aes256::new(data_key),
+            tweak: Aes256::new(tweak_key),
+        }
+    }
+
+    pub(crate) fn encrypt(&self, data_unit: &mut [u8], tweak: [u8; 16]) {
+        self.crypt(data_unit, tweak, |block| self.data.encrypt_block(block));
+    }
+
+    pub(crate) fn decrypt(&self, data_unit: &mut [u8], tweak: [u8; 16]) {
+        self.crypt(data_unit, tweak, |block| self.data.decrypt_block(block));
+    }
+
+    fn crypt(&self, data_unit: &mut [u8], mut tweak: [u8; 16], cipher: impl Fn(&mut [u8; 16])) {
+        debug_assert!(data_unit.len().is_multiple_of(16));
+
+        self.tweak.encrypt_block(&mut tweak);
+
+        for chunk in data_unit.chunks_exact_mut(16) {
+            let mut block = [0u8; 16];
+            for (idx, byte) in block.iter_mut().enumerate() {
+                *byte = chunk[idx] ^ tweak[idx];
+            }
+            cipher(&mut block);
+            for (idx, byte) in chunk.iter_mut().enumerate() {
+                *byte = block[idx] ^ tweak[idx];
+            }
+
+            // multiply the tweak by the primitive element of GF(2^128)
+            let carry = tweak[15] >> 7;
+            for idx in (1..16).rev() {
+                tweak[idx] = (tweak[idx] << 1) | (tweak[idx - 1] >> 7);
+            }
+            tweak[0] = (tweak[0] << 1) ^ (carry * 0x87);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use esp_nvs::{
+    FLASH_SECTOR_SIZE,
     ITEM_SIZE,
     Key,
 };
@@ -9,7 +10,6 @@ use crate::common::Operation::{
 };
 use crate::common::{
     ENTRY_STATE_MAP_OFFSET,
-    FLASH_SECTOR_SIZE,
     ITEM_OFFSET,
     PAGE_HEADER_SIZE,
     WORD_SIZE,
@@ -19,7 +19,7 @@ mod common;
 
 #[test]
 fn single_primitve() {
-    let mut flash = common::Flash::new(2);
+    let mut flash = common::flash(2);
 
     let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
     nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), 0xAAu8).unwrap();
@@ -49,7 +49,7 @@ fn single_primitve() {
             len: WORD_SIZE,
         },
         Write {
-            offset: (ITEM_OFFSET + 1 * ITEM_SIZE) as _,
+            offset: (ITEM_OFFSET + ITEM_SIZE) as _,
             len: ITEM_SIZE,
         },
         Write {
@@ -61,8 +61,8 @@ fn single_primitve() {
     let mut ops = ops_init.clone();
     ops.extend(ops_write);
 
-    assert_eq!(flash.operations, ops);
-    flash.operations.clear();
+    assert_eq!(flash.operations(), ops);
+    flash.clear_operations();
 
     let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
     assert_eq!(
@@ -72,11 +72,11 @@ fn single_primitve() {
 
     // namespace is already cached, so only reading the actual value is required
     let ops_read = vec![Read {
-        offset: (ITEM_OFFSET + 1 * ITEM_SIZE) as _,
+        offset: (ITEM_OFFSET + ITEM_SIZE) as _,
         len: ITEM_SIZE,
     }];
     let mut ops = ops_init.clone();
     ops.extend(ops_read);
 
-    assert_eq!(flash.operations, ops);
+    assert_eq!(flash.operations(), ops);
 }
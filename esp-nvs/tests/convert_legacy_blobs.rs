@@ -0,0 +1,54 @@
+#![cfg(feature = "convert-legacy-blobs")]
+
+use esp_nvs::{
+    ItemType,
+    Key,
+    LegacyBlob,
+};
+use pretty_assertions::assert_eq;
+
+mod common;
+
+#[test]
+fn legacy_blob_is_converted_on_load() {
+    let mut flash = common::flash(3);
+    let namespace = Key::from_str("ns");
+    let key = Key::from_str("legacy");
+    let data: Vec<u8> = (0..200).collect();
+
+    {
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        nvs.set(&namespace, &key, LegacyBlob(&data)).unwrap();
+        nvs.set(&namespace, &Key::from_str("other"), 1u8).unwrap();
+    }
+
+    let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+    assert_eq!(nvs.get::<Vec<u8>>(&namespace, &key).unwrap(), data);
+    assert_eq!(
+        nvs.typed_entries().collect::<Result<Vec<_>, _>>().unwrap(),
+        vec![
+            (namespace, Key::from_str("other"), ItemType::U8),
+            (namespace, key, ItemType::BlobIndex),
+        ]
+    );
+}
+
+#[test]
+fn newer_blob_wins_over_legacy_blob() {
+    let mut flash = common::flash(3);
+    let namespace = Key::from_str("ns");
+    let key = Key::from_str("blob");
+
+    {
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        nvs.set(&namespace, &key, LegacyBlob(&[1, 2, 3])).unwrap();
+        nvs.set(&namespace, &key, &[4u8, 5][..]).unwrap();
+    }
+
+    let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+    assert_eq!(nvs.get::<Vec<u8>>(&namespace, &key).unwrap(), vec![4, 5]);
+    assert_eq!(
+        nvs.typed_entries().collect::<Result<Vec<_>, _>>().unwrap(),
+        vec![(namespace, key, ItemType::BlobIndex)]
+    );
+}
@@ -0,0 +1,109 @@
+#![cfg(feature = "encryption")]
+
+use esp_nvs::Key;
+use esp_nvs::encryption::NvsKeys;
+use esp_nvs::error::Error;
+use pretty_assertions::assert_eq;
+
+mod common;
+
+fn keys() -> NvsKeys {
+    NvsKeys::from_partition(&std::fs::read("tests/assets/test_nvs_keys.bin").unwrap()).unwrap()
+}
+
+#[test]
+fn from_encrypted_partition() {
+    let mut flash = common::flash_from_file("tests/assets/test_nvs_data_encrypted.bin");
+
+    let mut nvs = esp_nvs::Nvs::new_encrypted(0, flash.len(), &mut flash, &keys()).unwrap();
+
+    assert_eq!(
+        nvs.get::<u8>(&Key::from_str("namespace_one"), &Key::from_str("example_u8")),
+        Ok(100)
+    );
+    assert_eq!(
+        nvs.get::<i32>(&Key::from_str("namespace_one"), &Key::from_str("example_i32")),
+        Ok(-2147480000)
+    );
+    assert_eq!(
+        nvs.get::<String>(&Key::from_str("namespace_one"), &Key::from_str("example_s_long")),
+        Ok(String::from(
+            "long string spanning multiple entries whereas each entry is 32 bytes in total"
+        ))
+    );
+    assert_eq!(
+        nvs.get::<Vec<u8>>(&Key::from_str("namespace_one"), &Key::from_str("example_b_long")),
+        Ok(std::fs::read("tests/assets/multi_page_blob.bin").unwrap())
+    );
+    assert_eq!(
+        nvs.get::<u8>(&Key::from_str("namespace_two"), &Key::from_str("only_in_two")),
+        Ok(1)
+    );
+}
+
+#[test]
+fn wrong_keys_find_nothing() {
+    let mut flash = common::flash_from_file("tests/assets/test_nvs_data_encrypted.bin");
+
+    let keys = NvsKeys::new([0x11; 32], [0x22; 32]);
+    let mut nvs = esp_nvs::Nvs::new_encrypted(0, flash.len(), &mut flash, &keys).unwrap();
+
+    assert_eq!(
+        nvs.get::<u8>(&Key::from_str("namespace_one"), &Key::from_str("example_u8")),
+        Err(Error::NamespaceNotFound)
+    );
+}
+
+#[test]
+fn write_and_read_back() {
+    let mut flash = common::flash(3);
+
+    {
+        let mut nvs = esp_nvs::Nvs::new_encrypted(0, flash.len(), &mut flash, &keys()).unwrap();
+        nvs.set(&Key::from_str("secrets"), &Key::from_str("pin"), 1234u16)
+            .unwrap();
+        nvs.set(&Key::from_str("secrets"), &Key::from_str("password"), "hunter2")
+            .unwrap();
+        // the last entry ends with 0xFF which still has to be encrypted
+        nvs.set(
+            &Key::from_str("secrets"),
+            &Key::from_str("blob"),
+            [0xFFu8; 40].as_slice(),
+        )
+        .unwrap();
+    }
+
+    assert!(!flash.as_slice().windows(7).any(|w| w == b"hunter2"));
+    assert!(!flash.as_slice().windows(7).any(|w| w == b"secrets"));
+
+    let mut nvs = esp_nvs::Nvs::new_encrypted(0, flash.len(), &mut flash, &keys()).unwrap();
+    assert_eq!(
+        nvs.get::<u16>(&Key::from_str("secrets"), &Key::from_str("pin")),
+        Ok(1234)
+    );
+    assert_eq!(
+        nvs.get::<String>(&Key::from_str("secrets"), &Key::from_str("password")),
+        Ok(String::from("hunter2"))
+    );
+    assert_eq!(
+        nvs.get::<Vec<u8>>(&Key::from_str("secrets"), &Key::from_str("blob")),
+        Ok(vec![0xFF; 40])
+    );
+}
+
+#[test]
+fn key_partition() {
+    let keys = keys();
+    assert_eq!(keys.eky, core::array::from_fn(|i| i as u8));
+    assert_eq!(keys.tky, core::array::from_fn(|i| i as u8 + 0x20));
+
+    let mut raw = keys.to_partition();
+    assert_eq!(NvsKeys::from_partition(&raw).unwrap().eky, keys.eky);
+
+    raw[0] ^= 1;
+    assert!(matches!(NvsKeys::from_partition(&raw), Err(Error::InvalidKeyPartition)));
+    assert!(matches!(
+        NvsKeys::from_partition(&[0xFF; 4096]),
+        Err(Error::InvalidKeyPartition)
+    ));
+}
@@ -0,0 +1,94 @@
+use esp_nvs::Key;
+use esp_nvs::error::Error;
+use pretty_assertions::assert_eq;
+
+mod common;
+
+#[test]
+fn try_from_str() {
+    assert_eq!(Key::try_from_str("my_key"), Ok(Key::from_str("my_key")));
+    assert_eq!(
+        Key::try_from_str("fifteen_bytes__"),
+        Ok(Key::from_str("fifteen_bytes__"))
+    );
+    assert_eq!(Key::try_from_str("sixteen_bytes___"), Err(Error::KeyTooLong));
+    assert_eq!(Key::try_from_str("my\0key"), Err(Error::KeyMalformed));
+}
+
+#[test]
+fn try_from_slice() {
+    assert_eq!(Key::try_from_slice(b"my_key"), Ok(Key::from_slice(b"my_key")));
+    assert_eq!(Key::try_from_slice(&[b'a'; 16]), Err(Error::KeyTooLong));
+    assert_eq!(Key::try_from_slice(b"my_key\0"), Err(Error::KeyMalformed));
+}
+
+#[test]
+fn std_conversions() {
+    assert_eq!("my_key".parse::<Key>(), Ok(Key::from_str("my_key")));
+    assert_eq!("sixteen_bytes___".parse::<Key>(), Err(Error::KeyTooLong));
+    assert_eq!("my\0key".parse::<Key>(), Err(Error::KeyMalformed));
+
+    assert_eq!(Key::try_from(b"my_key".as_slice()), Ok(Key::from_str("my_key")));
+    assert_eq!(Key::try_from([b'a'; 16].as_slice()), Err(Error::KeyTooLong));
+    assert_eq!(Key::try_from(b"my_key\0".as_slice()), Err(Error::KeyMalformed));
+
+    // usable with `?` in generic code
+    fn parse_all<K: core::str::FromStr>(keys: &[&str]) -> Result<Vec<K>, K::Err> {
+        keys.iter().map(|key| key.parse()).collect()
+    }
+    assert_eq!(
+        parse_all::<Key>(&["a", "b"]),
+        Ok(vec![Key::from_str("a"), Key::from_str("b")])
+    );
+}
+
+#[test]
+fn as_str_and_display() {
+    let key = Key::from_str("my_key");
+    assert_eq!(key.as_str(), Ok("my_key"));
+    assert_eq!(key.to_string(), "my_key");
+
+    let key = Key::from_slice(b"bad\xffkey");
+    assert!(key.as_str().is_err());
+    assert_eq!(key.to_string(), "bad\u{FFFD}key");
+}
+
+#[test]
+fn compare_with_str_and_slice() {
+    let key = Key::from_str("my_key");
+    assert!(key == "my_key");
+    assert!(key == *"my_key");
+    assert!(key != "my_key\0");
+    assert!(key != "my_key_2");
+    assert!(key != "my");
+    assert!(key == b"my_key".as_slice());
+    assert!(key == b"my_key"[..]);
+    assert!(key != b"my_key\0".as_slice());
+    assert!(Key::from_str("") == "");
+}
+
+#[test]
+fn from_cstr() {
+    assert_eq!(Key::from_cstr(c"my_key"), Ok(Key::from_str("my_key")));
+    assert_eq!(Key::from_cstr(c"fifteen_bytes__"), Ok(Key::from_str("fifteen_bytes__")));
+    assert_eq!(Key::from_cstr(c"sixteen_bytes___"), Err(Error::KeyTooLong));
+    assert_eq!(Key::from_cstr(c""), Ok(Key::from_str("")));
+}
+
+#[test]
+fn interior_null_is_rejected() {
+    let mut flash = common::flash(2);
+    let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+
+    let namespace = Key::from_str("ns");
+    let aliased = Key::from_slice(b"ab\0cd");
+    assert_eq!(nvs.set(&namespace, &aliased, 1u8), Err(Error::KeyMalformed));
+    assert_eq!(nvs.get::<u8>(&namespace, &aliased), Err(Error::KeyMalformed));
+    assert_eq!(nvs.delete(&namespace, &aliased), Err(Error::KeyMalformed));
+    assert_eq!(
+        nvs.set(&aliased, &Key::from_str("value"), 1u8),
+        Err(Error::NamespaceMalformed)
+    );
+
+    nvs.set(&namespace, &Key::from_str("ab"), 1u8).unwrap();
+}
@@ -0,0 +1,91 @@
+use esp_nvs::Key;
+use esp_nvs::error::Error;
+use esp_nvs::mem_flash::{
+    MemFlash,
+    Operation,
+};
+use pretty_assertions::assert_eq;
+
+#[test]
+fn operation_log() {
+    let mut flash = MemFlash::new(2);
+    flash.enable_operation_log();
+    flash.set_fail_after_operation(0);
+
+    assert_eq!(
+        esp_nvs::Nvs::new(0, flash.len(), &mut flash).err(),
+        Some(Error::FlashError)
+    );
+    assert_eq!(flash.operation_count(), 0);
+    assert!(flash.operations().is_empty());
+
+    flash.disable_faults();
+    esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+    assert_eq!(flash.operations()[0], Operation::Read { offset: 0, len: 4096 });
+    assert_eq!(flash.operations().len(), flash.operation_count());
+}
+
+#[test]
+fn power_loss_at_any_point() {
+    let namespace = Key::from_str("ns");
+    let key = Key::from_str("value");
+
+    let mut operations = 0;
+    loop {
+        let mut flash = MemFlash::new(3);
+        {
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            nvs.set(&namespace, &key, 1u32).unwrap();
+        }
+
+        flash.set_fail_after_operation(flash.operation_count() + operations);
+        let completed = esp_nvs::Nvs::new(0, flash.len(), &mut flash)
+            .and_then(|mut nvs| nvs.set(&namespace, &key, 2u32))
+            .is_ok();
+
+        flash.disable_faults();
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        let value = nvs.get::<u32>(&namespace, &key).unwrap();
+        if completed {
+            assert_eq!(value, 2);
+            break;
+        }
+        assert!(value == 1 || value == 2, "unexpected value {value}");
+
+        operations += 1;
+    }
+}
+
+#[test]
+fn partition_out_of_bounds() {
+    let mut flash = MemFlash::new(2);
+
+    assert_eq!(
+        esp_nvs::Nvs::new(0, 3 * 4096, &mut flash).err(),
+        Some(Error::PartitionOutOfBounds)
+    );
+    assert_eq!(
+        esp_nvs::Nvs::new(4096, 2 * 4096, &mut flash).err(),
+        Some(Error::PartitionOutOfBounds)
+    );
+    assert_eq!(
+        esp_nvs::Nvs::new_read_only(usize::MAX - 4095, 4096, &mut flash).err(),
+        Some(Error::PartitionOutOfBounds)
+    );
+    assert!(esp_nvs::Nvs::new(4096, 4096, &mut flash).is_ok());
+}
+
+#[test]
+fn partition_size_errors() {
+    let mut flash = MemFlash::new(2);
+
+    assert_eq!(
+        esp_nvs::Nvs::new(0, 4096 + 1, &mut flash).err(),
+        Some(Error::InvalidPartitionSize)
+    );
+    // rejected before the size is compared with the capacity of the flash
+    assert_eq!(
+        esp_nvs::Nvs::new(0, (u16::MAX as usize + 1) * 4096, &mut flash).err(),
+        Some(Error::PartitionTooLarge)
+    );
+}
@@ -0,0 +1,27 @@
+#![cfg(feature = "embedded-storage")]
+
+use esp_nvs::Key;
+use esp_nvs::mem_flash::MemFlash;
+use esp_nvs::platform::NorFlashPlatform;
+use pretty_assertions::assert_eq;
+
+#[test]
+fn roundtrip_through_adapter() {
+    let flash = MemFlash::new(3);
+    let size = flash.len();
+
+    let mut nvs = esp_nvs::Nvs::new(0, size, NorFlashPlatform::new(flash)).unwrap();
+    nvs.set(&Key::from_str("ns"), &Key::from_str("value"), 42u32).unwrap();
+    nvs.set(&Key::from_str("ns"), &Key::from_str("string"), "hello")
+        .unwrap();
+
+    let flash = nvs.into_inner().into_inner();
+
+    // the image is readable with the native MemFlash platform as both use the same CRC
+    let mut nvs = esp_nvs::Nvs::new(0, size, flash).unwrap();
+    assert_eq!(nvs.get::<u32>(&Key::from_str("ns"), &Key::from_str("value")), Ok(42));
+    assert_eq!(
+        nvs.get::<String>(&Key::from_str("ns"), &Key::from_str("string")),
+        Ok(String::from("hello"))
+    );
+}
@@ -3,7 +3,9 @@ use esp_nvs::error::{
     ItemType,
 };
 use esp_nvs::{
+    DecodedValue,
     EntryStatistics,
+    EntryType,
     Key,
     NvsStatistics,
     PageStatistics,
@@ -14,7 +16,7 @@ mod common;
 
 #[test]
 fn from_generated_partition() {
-    let mut flash = common::Flash::new_from_file("tests/assets/test_nvs_data.bin");
+    let mut flash = common::flash_from_file("tests/assets/test_nvs_data.bin");
 
     let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
 
@@ -98,9 +100,157 @@ fn from_generated_partition() {
     );
 }
 
+#[test]
+fn get_or_default() {
+    let mut flash = common::flash_from_file("tests/assets/test_nvs_data.bin");
+
+    let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+
+    assert_eq!(
+        nvs.get_or::<u8>(&Key::from_str("namespace_one"), &Key::from_str("example_u8"), 1),
+        Ok(100)
+    );
+    assert_eq!(
+        nvs.get_or::<u8>(&Key::from_str("namespace_one"), &Key::from_str("missing"), 1),
+        Ok(1)
+    );
+    assert_eq!(
+        nvs.get_or(
+            &Key::from_str("missing"),
+            &Key::from_str("example_u8"),
+            String::from("default")
+        ),
+        Ok(String::from("default"))
+    );
+    assert_eq!(
+        nvs.get_or::<u32>(&Key::from_str("namespace_one"), &Key::from_str("example_u16"), 1),
+        Err(Error::ItemTypeMismatch(ItemType::U16))
+    );
+}
+
+#[test]
+fn get_str_into_buffer() {
+    let mut flash = common::flash_from_file("tests/assets/test_nvs_data.bin");
+
+    let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+    let namespace = Key::from_str("namespace_one");
+
+    let mut buf = [0u8; 128];
+    assert_eq!(
+        nvs.get_str_into(&namespace, &Key::from_str("example_s_short"), &mut buf),
+        Ok("short string")
+    );
+    assert_eq!(
+        nvs.get_str_into(&namespace, &Key::from_str("example_s_long"), &mut buf),
+        Ok("long string spanning multiple entries whereas each entry is 32 bytes in total")
+    );
+
+    // the null terminator has to fit as well
+    assert_eq!(
+        nvs.get_str_into(&namespace, &Key::from_str("example_s_short"), &mut [0u8; 12]),
+        Err(Error::BufferTooSmall(13))
+    );
+    assert_eq!(
+        nvs.get_str_into(&namespace, &Key::from_str("example_s_short"), &mut [0u8; 13]),
+        Ok("short string")
+    );
+    assert_eq!(
+        nvs.get_str_into(&namespace, &Key::from_str("example_u8"), &mut buf),
+        Err(Error::ItemTypeMismatch(ItemType::U8))
+    );
+}
+
+#[test]
+fn get_str_ref_reuses_buffer() {
+    let mut flash = common::flash_from_file("tests/assets/test_nvs_data.bin");
+
+    let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+    let namespace = Key::from_str("namespace_one");
+
+    assert_eq!(
+        nvs.get_str_ref(&namespace, &Key::from_str("example_s_short")),
+        Ok("short string")
+    );
+    assert_eq!(
+        nvs.get_str_ref(&namespace, &Key::from_str("example_s_long")),
+        Ok("long string spanning multiple entries whereas each entry is 32 bytes in total")
+    );
+    // a shorter string after a longer one only uses part of the buffer
+    assert_eq!(
+        nvs.get_str_ref(&namespace, &Key::from_str("example_s_short")),
+        Ok("short string")
+    );
+    assert_eq!(
+        nvs.get_str_ref(&namespace, &Key::from_str("example_u8")),
+        Err(Error::ItemTypeMismatch(ItemType::U8))
+    );
+    assert_eq!(
+        nvs.get_str_ref(&namespace, &Key::from_str("missing")),
+        Err(Error::KeyNotFound)
+    );
+}
+
+#[test]
+fn entry_types() {
+    let mut flash = common::flash_from_file("tests/assets/test_nvs_data.bin");
+
+    let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+
+    let types = nvs
+        .typed_entries()
+        .map(|entry| EntryType::try_from(entry.unwrap().2).unwrap())
+        .collect::<Vec<_>>();
+    assert!(types.contains(&EntryType::U8));
+    assert!(types.contains(&EntryType::Str));
+    assert!(types.contains(&EntryType::Blob));
+
+    assert_eq!(EntryType::try_from(ItemType::BlobIndex), Ok(EntryType::Blob));
+    assert_eq!(EntryType::try_from(ItemType::Blob), Ok(EntryType::Blob));
+    assert_eq!(EntryType::try_from(ItemType::Sized), Ok(EntryType::Str));
+    assert_eq!(
+        EntryType::try_from(ItemType::Any),
+        Err(Error::ItemTypeMismatch(ItemType::Any))
+    );
+
+    assert!(EntryType::I16.is_primitive());
+    assert!(!EntryType::Str.is_primitive());
+    assert!(EntryType::Blob.is_blob());
+    assert_eq!(EntryType::I16.byte_width(), Some(2));
+    assert_eq!(EntryType::U64.byte_width(), Some(8));
+    assert_eq!(EntryType::Blob.byte_width(), None);
+}
+
+#[test]
+fn get_raw() {
+    let mut flash = common::flash_from_file("tests/assets/test_nvs_data.bin");
+
+    let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+    let namespace = Key::from_str("namespace_one");
+
+    assert_eq!(
+        nvs.get_raw(&namespace, &Key::from_str("example_i8")),
+        Ok((EntryType::I8, vec![0x9C, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]))
+    );
+    assert_eq!(
+        nvs.get_raw(&namespace, &Key::from_str("example_s_short")),
+        Ok((EntryType::Str, b"short string\0".to_vec()))
+    );
+    assert_eq!(
+        nvs.get_raw(&namespace, &Key::from_str("example_b_long")),
+        Ok((
+            EntryType::Blob,
+            std::fs::read("tests/assets/multi_page_blob.bin").unwrap()
+        ))
+    );
+    assert_eq!(
+        nvs.get_raw(&namespace, &Key::from_str("missing")),
+        Err(Error::KeyNotFound)
+    );
+}
+
 #[test]
 fn iter_namespaces() {
-    let mut flash = common::Flash::new_from_file("tests/assets/test_nvs_data.bin");
+    let mut flash = common::flash_from_file("tests/assets/test_nvs_data.bin");
 
     let nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
 
@@ -112,7 +262,7 @@ fn iter_namespaces() {
 
 #[test]
 fn iter_keys() {
-    let mut flash = common::Flash::new_from_file("tests/assets/test_nvs_data.bin");
+    let mut flash = common::flash_from_file("tests/assets/test_nvs_data.bin");
 
     let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
 
@@ -135,13 +285,73 @@ fn iter_keys() {
     );
 }
 
+#[test]
+fn iter_namespace() {
+    let mut flash = common::flash_from_file("tests/assets/test_nvs_data.bin");
+
+    let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+
+    assert_eq!(
+        nvs.iter_namespace(&Key::from_str("namespace_two"))
+            .unwrap()
+            .collect::<Vec<_>>(),
+        vec![
+            Ok((Key::from_str("example_u8"), DecodedValue::U8(123))),
+            Ok((Key::from_str("only_in_two"), DecodedValue::U8(1))),
+        ]
+    );
+
+    let values = nvs
+        .iter_namespace(&Key::from_str("namespace_one"))
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(values.len(), 10);
+    assert_eq!(values[1], (Key::from_str("example_i8"), DecodedValue::I8(-100)));
+    assert_eq!(values[6].1, DecodedValue::Str("short string".to_string()));
+    assert_eq!(
+        values[9].1,
+        DecodedValue::Blob(std::fs::read("tests/assets/multi_page_blob.bin").unwrap())
+    );
+    assert_eq!(values[9].1.entry_type(), EntryType::Blob);
+
+    assert!(matches!(
+        nvs.iter_namespace(&Key::from_str("missing")),
+        Err(Error::NamespaceNotFound)
+    ));
+}
+
+#[test]
+fn iter_namespace_continues_after_corrupt_value() {
+    let mut flash = common::flash(2);
+    {
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        nvs.set(&Key::from_str("ns"), &Key::from_str("a"), 1u8).unwrap();
+        nvs.set(&Key::from_str("ns"), &Key::from_str("b"), "hi").unwrap();
+        nvs.set(&Key::from_str("ns"), &Key::from_str("c"), 3u8).unwrap();
+    }
+
+    // break the data of "b", its header stays intact
+    flash.as_mut_slice()[common::ITEM_OFFSET + 3 * 32] = b'x';
+
+    let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+    assert_eq!(
+        nvs.iter_namespace(&Key::from_str("ns")).unwrap().collect::<Vec<_>>(),
+        vec![
+            Ok((Key::from_str("a"), DecodedValue::U8(1))),
+            Err(Error::KeyNotFound),
+            Ok((Key::from_str("c"), DecodedValue::U8(3))),
+        ]
+    );
+}
+
 #[test]
 fn corrupt_page() {
-    let mut flash = common::Flash::new_from_file("tests/assets/test_nvs_data.bin");
+    let mut flash = common::flash_from_file("tests/assets/test_nvs_data.bin");
 
     // we manually corrupt header of the first page
     // -> crc invalid
-    flash.buf[4] = 123;
+    flash.as_mut_slice()[4] = 123;
 
     let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
     let result = nvs.get::<u8>(&Key::from_str("namespace_one"), &Key::from_str("example_u8"));
@@ -191,15 +401,27 @@ fn corrupt_page() {
             },
         }
     );
+
+    let statistics = nvs.statistics().unwrap();
+    assert_eq!(
+        statistics.to_string(),
+        "4 pages: 0 active, 2 full, 1 empty, 0 erasing, 1 corrupted\n\
+         504 entries: 3 written, 144 erased, 231 empty, 126 illegal (0% used)\n"
+    );
+    assert_eq!(
+        statistics.summary().to_string(),
+        "pages 0/2/1/0/1 (active/full/empty/erasing/corrupted), entries 3/144/231/126 \
+         (written/erased/empty/illegal), 0% used"
+    );
 }
 
 #[test]
 fn corrupt_entry() {
-    let mut flash = common::Flash::new_from_file("tests/assets/test_nvs_data.bin");
+    let mut flash = common::flash_from_file("tests/assets/test_nvs_data.bin");
 
     // we manually corrupt the example_u8 entry
     // -> crc invalid
-    flash.buf[0x60] = 123;
+    flash.as_mut_slice()[0x60] = 123;
 
     let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
     let result = nvs.get::<u8>(&Key::from_str("namespace_one"), &Key::from_str("example_u8"));
@@ -253,6 +475,81 @@ fn corrupt_entry() {
     );
 }
 
+#[test]
+fn corrupt_entry_with_valid_crc() {
+    let mut flash = common::flash(2);
+    {
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        nvs.set(&Key::from_str("ns"), &Key::from_str("a"), 1u8).unwrap();
+        nvs.set(&Key::from_str("ns"), &Key::from_str("b"), "hi").unwrap();
+        nvs.set(&Key::from_str("ns"), &Key::from_str("c"), 3u8).unwrap();
+    }
+
+    // an unknown type for "a" and a span beyond the page for "b", both with a matching CRC
+    let entry = |index: usize| common::ITEM_OFFSET + index * 32;
+    flash.as_mut_slice()[entry(1) + 1] = 0x77;
+    flash.as_mut_slice()[entry(2) + 2] = 200;
+    for index in [1, 2] {
+        let raw = &flash.as_slice()[entry(index)..entry(index) + 32];
+        let crc = esp_nvs::platform::software_crc32(u32::MAX, &raw[0..4]);
+        let crc = esp_nvs::platform::software_crc32(crc, &raw[8..32]);
+        flash.as_mut_slice()[entry(index) + 4..entry(index) + 8].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+    assert_eq!(
+        nvs.get::<u8>(&Key::from_str("ns"), &Key::from_str("a")),
+        Err(Error::KeyNotFound)
+    );
+    assert_eq!(
+        nvs.get::<String>(&Key::from_str("ns"), &Key::from_str("b")),
+        Err(Error::KeyNotFound)
+    );
+    assert_eq!(nvs.get::<u8>(&Key::from_str("ns"), &Key::from_str("c")), Ok(3));
+    // the data entry of "b" has an unknown type as well
+    assert_eq!(nvs.statistics().unwrap().entries_overall.erased, 3);
+}
+
+#[test]
+fn span_crossing_page_boundary() {
+    let entry = |index: usize| common::ITEM_OFFSET + index * 32;
+    let mut flash = common::flash(2);
+    {
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        nvs.set(&Key::from_str("ns"), &Key::from_str("b"), "hi").unwrap();
+    }
+
+    // a copy of the string "b" in the last two entries of the page, claiming one entry more
+    let (header, data) = (
+        flash.as_slice()[entry(1)..entry(2)].to_vec(),
+        flash.as_slice()[entry(2)..entry(3)].to_vec(),
+    );
+    flash.as_mut_slice()[entry(124)..entry(125)].copy_from_slice(&header);
+    flash.as_mut_slice()[entry(125)..entry(126)].copy_from_slice(&data);
+    flash.as_mut_slice()[entry(124) + 2] = 3;
+    let raw = &flash.as_slice()[entry(124)..entry(125)];
+    let crc = esp_nvs::platform::software_crc32(u32::MAX, &raw[0..4]);
+    let crc = esp_nvs::platform::software_crc32(crc, &raw[8..32]);
+    flash.as_mut_slice()[entry(124) + 4..entry(124) + 8].copy_from_slice(&crc.to_le_bytes());
+
+    // with the bitmap not updated yet the entry is ignored
+    let mut nvs = esp_nvs::Nvs::new_read_only(0, flash.len(), &mut flash).unwrap();
+    assert_eq!(
+        nvs.get::<String>(&Key::from_str("ns"), &Key::from_str("b")).unwrap(),
+        "hi"
+    );
+
+    // once marked as written it is erased like any other corrupt entry
+    // entry 124 uses the lowest two bits of its byte, 0b10 means written
+    flash.as_mut_slice()[common::ENTRY_STATE_MAP_OFFSET + 124 / 4] &= !0b01;
+    let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+    assert_eq!(
+        nvs.get::<String>(&Key::from_str("ns"), &Key::from_str("b")).unwrap(),
+        "hi"
+    );
+    assert_eq!(nvs.statistics().unwrap().entries_per_page[0].erased, 1);
+}
+
 // TODO: when reading a multi-page-blob and the bounds don't match, mark the entry as corrupt
 
 // TODO: when reading a single-page-blob and the bounds don't match, mark the entry as corrupt
@@ -1,8 +1,11 @@
 mod common;
 
 mod set {
-    use esp_nvs::Key;
     use esp_nvs::error::Error;
+    use esp_nvs::{
+        EntryType,
+        Key,
+    };
     use pretty_assertions::assert_eq;
 
     use crate::common;
@@ -11,7 +14,7 @@ mod set {
 
     #[test]
     fn primitives() {
-        let mut flash = common::Flash::new(2);
+        let mut flash = common::flash(2);
 
         let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
 
@@ -103,7 +106,7 @@ mod set {
 
     #[test]
     fn string() {
-        let mut flash = common::Flash::new(2);
+        let mut flash = common::flash(2);
         let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
 
         nvs.set(&Key::from_str("hello world"), &Key::from_str("char"), "X")
@@ -136,9 +139,41 @@ mod set {
         );
     }
 
+    #[test]
+    fn empty_string_and_blob() {
+        let mut flash = common::flash(3);
+        let namespace = Key::from_str("ns");
+        {
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            nvs.set(&namespace, &Key::from_str("str"), "").unwrap();
+            nvs.set(&namespace, &Key::from_str("blob"), [0u8; 0].as_slice())
+                .unwrap();
+        }
+
+        // the loading cleans up broken blobs, an empty one has to survive it
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        assert_eq!(nvs.get::<String>(&namespace, &Key::from_str("str")), Ok(String::new()));
+        assert_eq!(nvs.get::<Vec<u8>>(&namespace, &Key::from_str("blob")), Ok(vec![]));
+        assert_eq!(
+            nvs.get_raw(&namespace, &Key::from_str("str")),
+            Ok((EntryType::Str, vec![0]))
+        );
+        assert_eq!(
+            nvs.get_raw(&namespace, &Key::from_str("blob")),
+            Ok((EntryType::Blob, vec![]))
+        );
+
+        // writing the same empty values again doesn't change anything
+        let written = nvs.statistics().unwrap().entries_overall.written;
+        nvs.set(&namespace, &Key::from_str("str"), "").unwrap();
+        nvs.set(&namespace, &Key::from_str("blob"), [0u8; 0].as_slice())
+            .unwrap();
+        assert_eq!(nvs.statistics().unwrap().entries_overall.written, written);
+    }
+
     #[test]
     fn blob() {
-        let mut flash = common::Flash::new(4);
+        let mut flash = common::flash(4);
         let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
 
         let tiny_blob: Vec<_> = (0u8..20).collect();
@@ -181,9 +216,197 @@ mod set {
         );
     }
 
+    #[test]
+    fn typed_array() {
+        let mut flash = common::flash(8);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+
+        let table: Vec<u16> = vec![0x0102, 0x0304];
+        nvs.set(&Key::from_str("calib"), &Key::from_str("table"), table.as_slice())
+            .unwrap();
+        assert_eq!(
+            nvs.get::<Vec<u16>>(&Key::from_str("calib"), &Key::from_str("table"))
+                .unwrap(),
+            table
+        );
+        // tag followed by the little endian elements
+        assert_eq!(
+            nvs.get::<Vec<u8>>(&Key::from_str("calib"), &Key::from_str("table"))
+                .unwrap(),
+            vec![0x02, 0x02, 0x01, 0x04, 0x03]
+        );
+        assert_eq!(
+            nvs.get::<Vec<u32>>(&Key::from_str("calib"), &Key::from_str("table")),
+            Err(Error::ItemTypeMismatch(esp_nvs::ItemType::U16))
+        );
+
+        let multi_page: Vec<i32> = (-1500..1500).collect();
+        nvs.set(&Key::from_str("calib"), &Key::from_str("large"), multi_page.as_slice())
+            .unwrap();
+        assert_eq!(
+            nvs.get::<Vec<i32>>(&Key::from_str("calib"), &Key::from_str("large"))
+                .unwrap(),
+            multi_page
+        );
+
+        nvs.set(
+            &Key::from_str("calib"),
+            &Key::from_str("broken"),
+            [0x04u8, 1, 2, 3].as_slice(),
+        )
+        .unwrap();
+        assert_eq!(
+            nvs.get::<Vec<u32>>(&Key::from_str("calib"), &Key::from_str("broken")),
+            Err(Error::CorruptedData)
+        );
+    }
+
+    #[test]
+    fn legacy_blob() {
+        let mut flash = common::flash(2);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+
+        let namespace = Key::from_str("ns");
+        let key = Key::from_str("legacy");
+        nvs.set(&namespace, &key, esp_nvs::LegacyBlob(&[1, 2, 3])).unwrap();
+        assert_eq!(nvs.get::<Vec<u8>>(&namespace, &key).unwrap(), vec![1, 2, 3]);
+        assert_eq!(
+            nvs.typed_entries().collect::<Result<Vec<_>, _>>().unwrap(),
+            vec![(namespace, key, esp_nvs::ItemType::Blob)]
+        );
+
+        nvs.set(&namespace, &key, esp_nvs::LegacyBlob(&[4, 5])).unwrap();
+        assert_eq!(nvs.get::<Vec<u8>>(&namespace, &key).unwrap(), vec![4, 5]);
+
+        assert_eq!(
+            nvs.set(
+                &namespace,
+                &key,
+                esp_nvs::LegacyBlob(&[0; esp_nvs::MAX_BLOB_DATA_PER_PAGE + 1])
+            ),
+            Err(Error::ValueTooLong)
+        );
+    }
+
+    #[test]
+    fn blob_larger_than_partition() {
+        let mut flash = common::flash(3);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        // the namespace entry and the blob index take an entry each
+        assert_eq!(nvs.max_blob_size(), 2 * esp_nvs::MAX_BLOB_DATA_PER_PAGE - 2 * 32);
+
+        let namespace = Key::from_str("ns");
+        let key = Key::from_str("blob");
+        assert_eq!(
+            nvs.set(&namespace, &key, vec![0u8; nvs.max_blob_size()].as_slice()),
+            Err(Error::ValueTooLong)
+        );
+        assert_eq!(nvs.statistics().unwrap().entries_overall.written, 0);
+
+        let mut flash = common::flash(300);
+        let nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        assert_eq!(nvs.max_blob_size(), esp_nvs::MAX_BLOB_SIZE);
+    }
+
+    #[test]
+    fn largest_blob_fits_into_empty_partition() {
+        for (sectors, reserved_pages) in [(2, 1), (3, 1), (5, 1), (5, 2), (5, 4)] {
+            let mut flash = common::flash(sectors);
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            nvs.set_reserved_pages(reserved_pages).unwrap();
+
+            let pages = sectors - reserved_pages as usize;
+            assert_eq!(nvs.max_blob_size(), pages * esp_nvs::MAX_BLOB_DATA_PER_PAGE - 2 * 32);
+
+            let namespace = Key::from_str("ns");
+            let key = Key::from_str("blob");
+            assert_eq!(
+                nvs.set(&namespace, &key, vec![0u8; nvs.max_blob_size()].as_slice()),
+                Err(Error::ValueTooLong)
+            );
+            let blob = vec![0xAB; nvs.max_blob_size() - 1];
+            nvs.set(&namespace, &key, blob.as_slice()).unwrap();
+            assert_eq!(nvs.get::<Vec<u8>>(&namespace, &key), Ok(blob));
+        }
+    }
+
+    #[test]
+    fn blob_larger_than_free_space() {
+        let mut flash = common::flash(2);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        let namespace = Key::from_str("ns");
+
+        // namespace + 10 values leave 115 entries, the blob needs 122 data entries, a chunk
+        // header and the blob index
+        for i in 0..10u32 {
+            nvs.set(&namespace, &Key::from_str(&format!("v{i}")), i).unwrap();
+        }
+        let blob = vec![0xABu8; 3900];
+        assert!(blob.len() < nvs.max_blob_size());
+
+        let written = nvs.statistics().unwrap().entries_overall.written;
+        assert_eq!(
+            nvs.set(&namespace, &Key::from_str("blob"), blob.as_slice()),
+            Err(Error::FlashFull)
+        );
+        assert_eq!(nvs.statistics().unwrap().entries_overall.written, written);
+
+        // replacing a value keeps it if the blob doesn't fit
+        assert_eq!(
+            nvs.set(&namespace, &Key::from_str("v0"), blob.as_slice()),
+            Err(Error::FlashFull)
+        );
+        assert_eq!(nvs.get::<u32>(&namespace, &Key::from_str("v0")), Ok(0));
+
+        // erased entries count as free, as they are reclaimed while writing the blob
+        for i in 0..50u32 {
+            nvs.set(&namespace, &Key::from_str("v1"), i).unwrap();
+        }
+        let blob = vec![0xCD; 3500];
+        nvs.set(&namespace, &Key::from_str("blob"), blob.as_slice()).unwrap();
+        assert_eq!(nvs.get::<Vec<u8>>(&namespace, &Key::from_str("blob")), Ok(blob));
+        assert_eq!(nvs.get::<u32>(&namespace, &Key::from_str("v1")), Ok(49));
+    }
+
+    #[test]
+    fn blob_into_fragmented_free_space() {
+        let mut flash = common::flash(5);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        let namespace = Key::from_str("ns");
+
+        // strings of two and three entries leave a single free entry at the end of every page
+        let mut lengths = vec![1; 62];
+        for _ in 1..4 {
+            lengths.extend([1; 61]);
+            lengths.push(40);
+        }
+        for (i, len) in lengths.into_iter().enumerate() {
+            nvs.set(&namespace, &Key::from_str(&format!("s{i}")), "x".repeat(len).as_str())
+                .unwrap();
+        }
+        assert_eq!(nvs.free_entries(), 1);
+        assert_eq!(nvs.capacity_entries() - nvs.used_entries(), 4);
+
+        // nothing can be reclaimed to make room for a chunk header next to its data
+        let erases = nvs.erase_counts();
+        let written = nvs.statistics().unwrap().entries_overall.written;
+        assert_eq!(
+            nvs.set(&namespace, &Key::from_str("blob"), [0xABu8].as_slice()),
+            Err(Error::FlashFull)
+        );
+        assert_eq!(nvs.statistics().unwrap().entries_overall.written, written);
+        assert_eq!(nvs.erase_counts(), erases);
+
+        assert_eq!(
+            nvs.set(&namespace, &Key::from_str("string"), "x"),
+            Err(Error::FlashFull)
+        );
+        assert_eq!(nvs.get::<String>(&namespace, &Key::from_str("s0")), Ok("x".to_string()));
+    }
+
     #[test]
     fn blob_replace_with_different_size() {
-        let mut flash = common::Flash::new(4);
+        let mut flash = common::flash(4);
         let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
 
         let tiny_blob: Vec<_> = (0u8..20).collect();
@@ -215,7 +438,7 @@ mod set {
 
     #[test]
     fn second_page_is_allocated() {
-        let mut flash = common::Flash::new(3);
+        let mut flash = common::flash(3);
 
         let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
 
@@ -234,7 +457,7 @@ mod set {
 
     #[test]
     fn primitive_overwrite_same_type() {
-        let mut flash = common::Flash::new(2);
+        let mut flash = common::flash(2);
 
         let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
 
@@ -252,9 +475,9 @@ mod set {
 
     #[test]
     fn primitive_no_change() {
-        let mut flash = common::Flash::new(2);
+        let mut flash = common::flash(2);
 
-        // we need to drop nvs to be able to access flash.buf again
+        // we need to drop nvs to be able to access flash.as_slice() again
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
             nvs.set(&Key::from_str("hello world"), &Key::from_str("val"), 1u8)
@@ -266,7 +489,7 @@ mod set {
             );
         }
 
-        let snapshot = flash.buf.clone();
+        let snapshot = flash.as_slice().to_vec();
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -279,16 +502,16 @@ mod set {
             );
         }
 
-        assert_eq!(snapshot, flash.buf)
+        assert_eq!(snapshot, flash.as_slice())
     }
 
     #[test]
     fn string_no_change() {
-        let mut flash = common::Flash::new(2);
+        let mut flash = common::flash(2);
 
         let value = "hello";
 
-        // we need to drop nvs to be able to access flash.buf again
+        // we need to drop nvs to be able to access flash.as_slice() again
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
             nvs.set(&Key::from_str("hello world"), &Key::from_str("val"), value)
@@ -300,7 +523,7 @@ mod set {
             );
         }
 
-        let snapshot = flash.buf.clone();
+        let snapshot = flash.as_slice().to_vec();
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -313,16 +536,16 @@ mod set {
             );
         }
 
-        assert_eq!(snapshot, flash.buf)
+        assert_eq!(snapshot, flash.as_slice())
     }
 
     #[test]
     fn blob_small_no_change() {
-        let mut flash = common::Flash::new(2);
+        let mut flash = common::flash(2);
 
         let blob = (u8::MIN..u8::MAX).cycle().take(129).collect::<Vec<_>>();
 
-        // we need to drop nvs to be able to access flash.buf again
+        // we need to drop nvs to be able to access flash.as_slice() again
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
             nvs.set(&Key::from_str("hello world"), &Key::from_str("val"), blob.as_slice())
@@ -334,7 +557,7 @@ mod set {
             );
         }
 
-        let snapshot = flash.buf.clone();
+        let snapshot = flash.as_slice().to_vec();
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -347,16 +570,16 @@ mod set {
             );
         }
 
-        assert_eq!(snapshot, flash.buf)
+        assert_eq!(snapshot, flash.as_slice())
     }
 
     #[test]
     fn blob_large_no_change() {
-        let mut flash = common::Flash::new(3);
+        let mut flash = common::flash(3);
 
         let blob = (u8::MIN..u8::MAX).cycle().take(256).collect::<Vec<_>>();
 
-        // we need to drop nvs to be able to access flash.buf again
+        // we need to drop nvs to be able to access flash.as_slice() again
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
             nvs.set(&Key::from_str("hello world"), &Key::from_str("val"), blob.as_slice())
@@ -368,7 +591,7 @@ mod set {
             );
         }
 
-        let snapshot = flash.buf.clone();
+        let snapshot = flash.as_slice().to_vec();
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -381,12 +604,12 @@ mod set {
             );
         }
 
-        assert_eq!(snapshot, flash.buf)
+        assert_eq!(snapshot, flash.as_slice())
     }
 
     #[test]
     fn namespace_still_fits_but_item_not_so_new_page_is_allocated() {
-        let mut flash = common::Flash::new(3);
+        let mut flash = common::flash(3);
 
         {
             // we fill the partition so that a only single entry still fits
@@ -398,10 +621,10 @@ mod set {
         }
 
         // last item on first page is unused
-        assert_eq!(flash.buf[4096 - 32..4096], vec![0xffu8; 32]);
+        assert_eq!(flash.as_slice()[4096 - 32..4096], vec![0xffu8; 32]);
 
         // second page is still uninitialized
-        assert_eq!(flash.buf[4096..4096 * 2], vec![0xffu8; 4096]);
+        assert_eq!(flash.as_slice()[4096..4096 * 2], vec![0xffu8; 4096]);
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -415,15 +638,15 @@ mod set {
         }
 
         // last item on first page is unused
-        assert_ne!(flash.buf[4096 - 32..4096], vec![0xffu8; 32]);
+        assert_ne!(flash.as_slice()[4096 - 32..4096], vec![0xffu8; 32]);
 
         // second page is now in use
-        assert_ne!(flash.buf[4096..4096 * 2], vec![0xffu8; 4096]);
+        assert_ne!(flash.as_slice()[4096..4096 * 2], vec![0xffu8; 4096]);
     }
 
     #[test]
     fn string_not_fitting_into_active_page() {
-        let mut flash = common::Flash::new(3);
+        let mut flash = common::flash(3);
 
         {
             // we fill the partition so that a only 4 entries still fit
@@ -434,10 +657,10 @@ mod set {
         }
 
         // last 4 item on first page are unused
-        assert_eq!(flash.buf[4096 - (32 * 4)..4096], vec![0xffu8; 32 * 4]);
+        assert_eq!(flash.as_slice()[4096 - (32 * 4)..4096], vec![0xffu8; 32 * 4]);
 
         // second page is still uninitialized
-        assert_eq!(flash.buf[4096..4096 * 2], vec![0xffu8; 4096]);
+        assert_eq!(flash.as_slice()[4096..4096 * 2], vec![0xffu8; 4096]);
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -456,15 +679,15 @@ mod set {
         }
 
         // last 4 item on first page are still unused
-        assert_eq!(flash.buf[4096 - (32 * 4)..4096], vec![0xffu8; 32 * 4]);
+        assert_eq!(flash.as_slice()[4096 - (32 * 4)..4096], vec![0xffu8; 32 * 4]);
 
         // second page is now in use
-        assert_ne!(flash.buf[4096..4096 * 2], vec![0xffu8; 4096]);
+        assert_ne!(flash.as_slice()[4096..4096 * 2], vec![0xffu8; 4096]);
     }
 
     #[test]
     fn propagate_flash_full_error() {
-        let mut flash = common::Flash::new(2);
+        let mut flash = common::flash(2);
 
         {
             // we fill the partition so that it's filled
@@ -482,6 +705,114 @@ mod set {
     }
 }
 
+mod replace {
+    use esp_nvs::Key;
+    use esp_nvs::error::Error;
+    use pretty_assertions::assert_eq;
+
+    use crate::common;
+
+    #[test]
+    fn returns_previous_value() {
+        let mut flash = common::flash(3);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        let namespace = Key::from_str("ns");
+        let key = Key::from_str("counter");
+
+        assert_eq!(nvs.replace(&namespace, &key, 1u32), Ok(None));
+        assert_eq!(nvs.replace(&namespace, &key, 2u32), Ok(Some(1)));
+        assert_eq!(nvs.open(&namespace).replace(&key, 3u32), Ok(Some(2)));
+        assert_eq!(nvs.get::<u32>(&namespace, &key), Ok(3));
+
+        assert_eq!(
+            nvs.replace(&namespace, &key, 4u8),
+            Err(Error::ItemTypeMismatch(esp_nvs::ItemType::U32))
+        );
+        assert_eq!(nvs.get::<u32>(&namespace, &key), Ok(3));
+    }
+}
+
+mod create_namespace {
+    use esp_nvs::Key;
+    use esp_nvs::error::Error;
+    use pretty_assertions::assert_eq;
+
+    use crate::common;
+
+    #[test]
+    fn uses_given_index() {
+        let mut flash = common::flash(3);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        let first = Key::from_str("first");
+        let second = Key::from_str("second");
+
+        assert_eq!(nvs.create_namespace(&first, 7), Ok(()));
+        assert_eq!(nvs.create_namespace(&first, 7), Ok(()));
+        assert_eq!(nvs.create_namespace(&first, 8), Err(Error::InvalidNamespaceIndex));
+        assert_eq!(nvs.create_namespace(&second, 7), Err(Error::InvalidNamespaceIndex));
+        assert_eq!(nvs.create_namespace(&second, 0), Err(Error::InvalidNamespaceIndex));
+
+        // namespaces created by writes continue after the highest index
+        nvs.set(&second, &Key::from_str("key"), 1u8).unwrap();
+        nvs.set(&first, &Key::from_str("key"), 2u8).unwrap();
+        assert_eq!(nvs.namespaces().collect::<Vec<_>>(), [&first, &second]);
+
+        drop(nvs);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        assert_eq!(nvs.create_namespace(&second, 8), Ok(()));
+        assert_eq!(nvs.get::<u8>(&first, &Key::from_str("key")), Ok(2));
+        assert_eq!(nvs.get::<u8>(&second, &Key::from_str("key")), Ok(1));
+    }
+}
+
+mod blob_chunk_start {
+    use esp_nvs::Key;
+    use esp_nvs::error::Error;
+    use pretty_assertions::assert_eq;
+
+    use crate::common;
+
+    #[test]
+    fn alternates_and_can_be_chosen() {
+        let mut flash = common::flash(3);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        let namespace = Key::from_str("ns");
+        let key = Key::from_str("blob");
+
+        nvs.set(&namespace, &key, [1u8, 2, 3].as_slice()).unwrap();
+        assert_eq!(nvs.blob_chunk_start(&namespace, &key), Ok(0));
+        nvs.set(&namespace, &key, [4u8, 5].as_slice()).unwrap();
+        assert_eq!(nvs.blob_chunk_start(&namespace, &key), Ok(0x80));
+
+        assert_eq!(
+            nvs.set_blob_with_chunk_start(&namespace, &key, &[6], 0x80),
+            Err(Error::InvalidChunkStart)
+        );
+        assert_eq!(
+            nvs.set_blob_with_chunk_start(&namespace, &key, &[6], 1),
+            Err(Error::InvalidChunkStart)
+        );
+        assert_eq!(nvs.set_blob_with_chunk_start(&namespace, &key, &[6], 0), Ok(()));
+        assert_eq!(nvs.blob_chunk_start(&namespace, &key), Ok(0));
+        assert_eq!(nvs.get::<Vec<u8>>(&namespace, &key), Ok(vec![6]));
+
+        let other = Key::from_str("other");
+        assert_eq!(nvs.set_blob_with_chunk_start(&namespace, &other, &[7], 0x80), Ok(()));
+        assert_eq!(nvs.get::<Vec<u8>>(&namespace, &other), Ok(vec![7]));
+
+        nvs.set(&namespace, &Key::from_str("int"), 1u8).unwrap();
+        assert_eq!(
+            nvs.blob_chunk_start(&namespace, &Key::from_str("int")),
+            Err(Error::ItemTypeMismatch(esp_nvs::ItemType::U8))
+        );
+
+        drop(nvs);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        assert_eq!(nvs.blob_chunk_start(&namespace, &other), Ok(0x80));
+        assert_eq!(nvs.get::<Vec<u8>>(&namespace, &other), Ok(vec![7]));
+    }
+}
+
 mod delete {
     use esp_nvs::error::Error;
     use esp_nvs::{
@@ -496,7 +827,7 @@ mod delete {
 
     #[test]
     fn primitive() {
-        let mut flash = common::Flash::new(2);
+        let mut flash = common::flash(2);
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -521,7 +852,7 @@ mod delete {
 
     #[test]
     fn string() {
-        let mut flash = common::Flash::new(2);
+        let mut flash = common::flash(2);
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -552,7 +883,7 @@ mod delete {
 
     #[test]
     fn blob_small() {
-        let mut flash = common::Flash::new(2);
+        let mut flash = common::flash(2);
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -578,7 +909,7 @@ mod delete {
 
     #[test]
     fn blob_large() {
-        let mut flash = common::Flash::new(4);
+        let mut flash = common::flash(4);
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -648,23 +979,463 @@ mod delete {
     }
 
     #[test]
-    fn nonexisting_key() {
-        let mut flash = common::Flash::new(1);
+    fn prefix() {
+        let mut flash = common::flash(4);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        let namespace = Key::from_str("ns");
+        let other = Key::from_str("other");
+
+        nvs.set(&namespace, &Key::from_str("sensor_0_min"), 1u8).unwrap();
+        nvs.set(&namespace, &Key::from_str("sensor_0_name"), "a").unwrap();
+        nvs.set(&namespace, &Key::from_str("sensor_0_cal"), &[0u8; 5000][..])
+            .unwrap();
+        nvs.set(&namespace, &Key::from_str("sensor_0_old"), esp_nvs::LegacyBlob(&[1]))
+            .unwrap();
+        nvs.set(&namespace, &Key::from_str("sensor_1_min"), 1u8).unwrap();
+        nvs.set(&other, &Key::from_str("sensor_0_min"), 1u8).unwrap();
+
+        assert_eq!(nvs.delete_prefix(&namespace, b"sensor_0_"), Ok(4));
+        assert_eq!(nvs.delete_prefix(&namespace, b"sensor_0_"), Ok(0));
+        assert_eq!(nvs.delete_prefix(&Key::from_str("missing"), b""), Ok(0));
+
+        assert_eq!(
+            nvs.keys().collect::<Result<Vec<_>, _>>().unwrap(),
+            vec![
+                (namespace, Key::from_str("sensor_1_min")),
+                (other, Key::from_str("sensor_0_min"))
+            ]
+        );
+        // the blob data is gone as well
+        assert_eq!(nvs.statistics().unwrap().entries_overall.written, 4);
+    }
+
+    #[test]
+    fn nonexisting_key() {
+        let mut flash = common::flash(1);
+
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        let result = nvs.delete(&Key::from_str("ns1"), &Key::from_str("my_key"));
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn remove_reports_existence() {
+        let mut flash = common::flash(2);
+
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        assert_eq!(nvs.remove(&Key::from_str("ns1"), &Key::from_str("my_key")), Ok(false));
+
+        nvs.set(&Key::from_str("ns1"), &Key::from_str("my_key"), 1u8).unwrap();
+        nvs.set(&Key::from_str("ns1"), &Key::from_str("my_blob"), [1u8; 3000].as_slice())
+            .unwrap();
+        assert_eq!(nvs.remove(&Key::from_str("ns1"), &Key::from_str("other")), Ok(false));
+        assert_eq!(nvs.remove(&Key::from_str("ns1"), &Key::from_str("my_key")), Ok(true));
+        assert_eq!(nvs.remove(&Key::from_str("ns1"), &Key::from_str("my_key")), Ok(false));
+        assert_eq!(nvs.remove(&Key::from_str("ns1"), &Key::from_str("my_blob")), Ok(true));
+        assert_eq!(nvs.remove(&Key::from_str("ns1"), &Key::from_str("my_blob")), Ok(false));
+    }
+}
+
+mod erase_all {
+    use esp_nvs::Key;
+    use esp_nvs::error::Error;
+    use pretty_assertions::assert_eq;
+
+    use crate::common;
+
+    #[test]
+    fn removes_everything() {
+        let mut flash = common::flash(3);
+
+        {
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            nvs.set(&Key::from_str("ns1"), &Key::from_str("primitive"), 123u32)
+                .unwrap();
+            nvs.set(&Key::from_str("ns1"), &Key::from_str("string"), "hello")
+                .unwrap();
+            nvs.set(&Key::from_str("ns2"), &Key::from_str("blob"), [0xAAu8; 200].as_slice())
+                .unwrap();
+
+            nvs.erase_all().unwrap();
+
+            assert_eq!(
+                nvs.get::<u32>(&Key::from_str("ns1"), &Key::from_str("primitive")),
+                Err(Error::NamespaceNotFound)
+            );
+        }
+
+        assert_eq!(common::erases(&flash), 3);
+
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        assert_eq!(nvs.namespaces().count(), 0);
+
+        nvs.set(&Key::from_str("ns1"), &Key::from_str("other"), 1u8).unwrap();
+        assert_eq!(
+            nvs.get::<u32>(&Key::from_str("ns1"), &Key::from_str("primitive")),
+            Err(Error::KeyNotFound)
+        );
+        assert_eq!(
+            nvs.get::<String>(&Key::from_str("ns1"), &Key::from_str("string")),
+            Err(Error::KeyNotFound)
+        );
+        assert_eq!(
+            nvs.get::<Vec<u8>>(&Key::from_str("ns2"), &Key::from_str("blob")),
+            Err(Error::NamespaceNotFound)
+        );
+        assert_eq!(nvs.get::<u8>(&Key::from_str("ns1"), &Key::from_str("other")), Ok(1));
+    }
+
+    #[test]
+    fn interrupted_erase_can_be_loaded() {
+        let mut flash = common::flash(3);
+
+        {
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            for i in 0..200u32 {
+                nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), i).unwrap();
+            }
+        }
+
+        // fail right after loading all 3 sectors and erasing the first one
+        flash.set_fail_after_operation(flash.operation_count() + 3 + 1);
+        {
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            assert_eq!(nvs.erase_all(), Err(Error::FlashError));
+            assert_eq!(nvs.erase_all(), Err(Error::FlashError));
+        }
+        flash.disable_faults();
+
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), 1000u32)
+            .unwrap();
+        assert_eq!(nvs.get::<u32>(&Key::from_str("ns1"), &Key::from_str("value")), Ok(1000));
+    }
+
+    #[test]
+    fn counts_erases() {
+        let mut flash = common::flash(3);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        assert_eq!(nvs.erase_counts(), vec![(0, 0), (0x1000, 0), (0x2000, 0)]);
+
+        nvs.erase_all().unwrap();
+        nvs.erase_all().unwrap();
+        assert_eq!(nvs.erase_counts(), vec![(0, 2), (0x1000, 2), (0x2000, 2)]);
+    }
+}
+
+mod recover {
+    use esp_nvs::Key;
+    use esp_nvs::error::Error;
+    use pretty_assertions::assert_eq;
+
+    use crate::common;
+
+    #[test]
+    fn clears_transient_fault() {
+        let mut flash = common::flash(3);
+        {
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), 1u8).unwrap();
+        }
+
+        // updating the value (one read, one write) and the first attempt to recover fail
+        flash.set_fail_after_operation(flash.operation_count() + 3);
+        flash.set_transient_faults(3);
+
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        assert!(!nvs.is_faulted());
+        assert_eq!(
+            nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), 2u8),
+            Err(Error::FlashError)
+        );
+        assert!(nvs.is_faulted());
+        // latched even though the flash works again
+        assert_eq!(
+            nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), 2u8),
+            Err(Error::FlashError)
+        );
+
+        assert_eq!(nvs.recover(), Err(Error::FlashError));
+        assert!(nvs.is_faulted());
+        assert_eq!(
+            nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), 2u8),
+            Err(Error::FlashError)
+        );
+
+        assert_eq!(nvs.recover(), Ok(()));
+        assert!(!nvs.is_faulted());
+        assert_eq!(nvs.get::<u8>(&Key::from_str("ns1"), &Key::from_str("value")), Ok(1));
+        nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), 2u8).unwrap();
+        assert_eq!(nvs.get::<u8>(&Key::from_str("ns1"), &Key::from_str("value")), Ok(2));
+    }
+}
+
+mod namespace_handle {
+    use esp_nvs::Key;
+    use esp_nvs::error::Error;
+    use pretty_assertions::assert_eq;
+
+    use crate::common;
+
+    #[test]
+    fn forwards_to_namespace() {
+        let mut flash = common::flash(2);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+
+        {
+            let mut handle = nvs.open(&Key::from_str("ns1"));
+            assert_eq!(handle.name(), &Key::from_str("ns1"));
+            assert_eq!(handle.contains(&Key::from_str("value")), Ok(false));
+            assert_eq!(
+                handle.get::<u32>(&Key::from_str("value")),
+                Err(Error::NamespaceNotFound)
+            );
+
+            handle.set(&Key::from_str("value"), 42u32).unwrap();
+            handle.set(&Key::from_str("string"), "hello").unwrap();
+            assert_eq!(handle.contains(&Key::from_str("value")), Ok(true));
+            assert_eq!(handle.contains(&Key::from_str("string")), Ok(true));
+            assert_eq!(handle.get::<u32>(&Key::from_str("value")), Ok(42));
+            assert_eq!(handle.get_or::<u32>(&Key::from_str("missing"), 7), Ok(7));
+
+            handle.delete(&Key::from_str("value")).unwrap();
+            assert_eq!(handle.contains(&Key::from_str("value")), Ok(false));
+        }
+
+        assert_eq!(
+            nvs.get::<String>(&Key::from_str("ns1"), &Key::from_str("string")),
+            Ok(String::from("hello"))
+        );
+        assert_eq!(nvs.contains(&Key::from_str("ns2"), &Key::from_str("string")), Ok(false));
+    }
+}
+
+mod read_only {
+    use esp_nvs::Key;
+    use esp_nvs::error::Error;
+    use pretty_assertions::assert_eq;
+
+    use crate::common;
+    use crate::common::Operation;
+
+    #[test]
+    fn never_touches_the_flash() {
+        let mut flash = common::flash_with_fault(2, 10);
+
+        {
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            nvs.set(&Key::from_str("ns1"), &Key::from_str("item"), 1u8).unwrap();
+
+            // leaves a duplicate behind as the old value can't be deleted
+            assert_eq!(
+                nvs.set(&Key::from_str("ns1"), &Key::from_str("item"), 2u8),
+                Err(Error::FlashError)
+            );
+        }
+
+        flash.disable_faults();
+        let operations_before = flash.operation_count();
+
+        let mut nvs = esp_nvs::Nvs::new_read_only(0, flash.len(), &mut flash).unwrap();
+        assert_eq!(nvs.get::<u8>(&Key::from_str("ns1"), &Key::from_str("item")), Ok(2));
+        assert_eq!(
+            nvs.set(&Key::from_str("ns1"), &Key::from_str("item"), 3u8),
+            Err(Error::ReadOnly)
+        );
+        assert_eq!(
+            nvs.delete(&Key::from_str("ns1"), &Key::from_str("item")),
+            Err(Error::ReadOnly)
+        );
+        assert_eq!(nvs.erase_all(), Err(Error::ReadOnly));
+        drop(nvs);
+
+        assert!(
+            flash.operations()[operations_before..]
+                .iter()
+                .all(|op| matches!(op, Operation::Read { .. }))
+        );
+
+        // the regular mode still cleans up the duplicate
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        assert_eq!(nvs.get::<u8>(&Key::from_str("ns1"), &Key::from_str("item")), Ok(2));
+    }
+}
+
+mod free_space {
+    use std::collections::BTreeMap;
+
+    use esp_nvs::error::Error;
+    use esp_nvs::{
+        Key,
+        NamespaceStatistics,
+    };
+    use pretty_assertions::assert_eq;
+
+    use crate::common;
+
+    #[test]
+    fn free_entries_and_largest_free_run() {
+        let mut flash = common::flash(3);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+
+        // one page is always kept in reserve
+        assert_eq!(nvs.free_entries(), 2 * 126);
+        assert_eq!(nvs.largest_free_run(), 0);
+
+        // namespace + value
+        nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), 1u8).unwrap();
+        assert_eq!(nvs.free_entries(), 124 + 126);
+        assert_eq!(nvs.largest_free_run(), 124);
+
+        // header + 2 data entries
+        nvs.set(&Key::from_str("ns1"), &Key::from_str("string"), "a".repeat(40).as_str())
+            .unwrap();
+        assert_eq!(nvs.free_entries(), 121 + 126);
+        assert_eq!(nvs.largest_free_run(), 121);
+
+        // overwriting doesn't free up the erased entry
+        nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), 2u8).unwrap();
+        assert_eq!(nvs.free_entries(), 120 + 126);
+    }
+
+    #[test]
+    fn used_and_capacity_entries() {
+        let mut flash = common::flash(3);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        assert_eq!(nvs.used_entries(), 0);
+        assert_eq!(nvs.capacity_entries(), 2 * 126);
+        assert_eq!(nvs.usage_ratio(), 0.0);
+
+        // namespace, value and a string with 2 data entries
+        nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), 1u8).unwrap();
+        nvs.set(&Key::from_str("ns1"), &Key::from_str("string"), "a".repeat(40).as_str())
+            .unwrap();
+        assert_eq!(nvs.used_entries(), 5);
+        assert_eq!(nvs.usage_ratio(), 5.0 / 252.0);
+
+        // erased entries don't count
+        nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), 2u8).unwrap();
+        assert_eq!(nvs.used_entries(), 5);
+        assert_eq!(nvs.used_entries(), nvs.statistics().unwrap().entries_overall.written);
+
+        nvs.set_reserved_pages(2).unwrap();
+        assert_eq!(nvs.capacity_entries(), 126);
+    }
+
+    #[test]
+    fn zeroed_flash() {
+        let mut flash = common::flash(3);
+        flash.as_mut_slice().fill(0x00);
+        {
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            let statistics = nvs.statistics().unwrap();
+            assert_eq!(statistics.pages.empty, 3);
+            assert_eq!(statistics.pages.corrupted, 0);
+            assert_eq!(nvs.free_entries(), 2 * 126);
+
+            // pages are erased once they are used
+            nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), 1u8).unwrap();
+            assert_eq!(nvs.erase_counts(), [(0x0000, 1), (0x1000, 0), (0x2000, 0)]);
+        }
+
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        assert_eq!(nvs.get::<u8>(&Key::from_str("ns1"), &Key::from_str("value")), Ok(1));
+        assert_eq!(nvs.erase_free_pages(), Ok(2));
+        assert_eq!(nvs.erase_free_pages(), Ok(0));
+        assert_eq!(nvs.erase_counts(), [(0x0000, 0), (0x1000, 1), (0x2000, 1)]);
+        drop(nvs);
+        assert!(flash.as_slice()[0x1000..].iter().all(|&b| b == 0xFF));
+    }
+
+    #[test]
+    fn corrupt_free_page() {
+        let mut flash = common::flash(3);
+        // an active page whose header CRC doesn't match
+        flash.as_mut_slice()[0x1000..0x1004].copy_from_slice(&0xFFFF_FFFEu32.to_le_bytes());
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        assert_eq!(nvs.statistics().unwrap().pages.corrupted, 1);
+
+        // the corrupt page is erased once it is used
+        assert_eq!(nvs.free_entries(), 2 * 126);
+        let namespace = Key::from_str("ns");
+        for i in 0..2 * 126 - 1 {
+            nvs.set(&namespace, &Key::from_str(&format!("{i}")), 1u8).unwrap();
+        }
+        assert_eq!(nvs.free_entries(), 0);
+        assert_eq!(nvs.set(&namespace, &Key::from_str("full"), 1u8), Err(Error::FlashFull));
+    }
+
+    #[test]
+    fn reserved_pages() {
+        let mut flash = common::flash(4);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+
+        assert_eq!(nvs.reserved_pages(), 1);
+        assert_eq!(nvs.set_reserved_pages(0), Err(Error::InvalidReservedPages));
+        assert_eq!(nvs.set_reserved_pages(4), Err(Error::InvalidReservedPages));
+        assert_eq!(nvs.free_entries(), 3 * 126);
+
+        nvs.set_reserved_pages(2).unwrap();
+        assert_eq!(nvs.free_entries(), 2 * 126);
+
+        // namespace + one value per entry until both unreserved pages are used up
+        let namespace = Key::from_str("ns");
+        for i in 0..2 * 126 - 1 {
+            nvs.set(&namespace, &Key::from_str(&format!("{i}")), 1u8).unwrap();
+        }
+        assert_eq!(nvs.free_entries(), 0);
+        assert_eq!(nvs.set(&namespace, &Key::from_str("full"), 1u8), Err(Error::FlashFull));
 
+        nvs.set_reserved_pages(1).unwrap();
+        nvs.set(&namespace, &Key::from_str("full"), 1u8).unwrap();
+    }
+
+    #[test]
+    fn namespace_statistics() {
+        let mut flash = common::flash(3);
         let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
-        let result = nvs.delete(&Key::from_str("ns1"), &Key::from_str("my_key"));
 
-        assert!(result.is_ok());
+        nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), 1u8).unwrap();
+        nvs.set(&Key::from_str("ns1"), &Key::from_str("string"), "a".repeat(40).as_str())
+            .unwrap();
+        nvs.set(&Key::from_str("ns2"), &Key::from_str("blob"), &[0u8; 10][..])
+            .unwrap();
+        nvs.set(&Key::from_str("ns3"), &Key::from_str("value"), 1u8).unwrap();
+        nvs.delete(&Key::from_str("ns3"), &Key::from_str("value")).unwrap();
+
+        assert_eq!(
+            nvs.namespace_statistics().unwrap(),
+            BTreeMap::from([
+                (
+                    Key::from_str("ns1"),
+                    NamespaceStatistics {
+                        entries: 4,
+                        data_size: 41,
+                    }
+                ),
+                (
+                    Key::from_str("ns2"),
+                    NamespaceStatistics {
+                        entries: 3,
+                        data_size: 10,
+                    }
+                ),
+                (Key::from_str("ns3"), NamespaceStatistics::default()),
+            ])
+        );
     }
 }
 
 mod overwrite {
     use esp_nvs::error::Error::{
         FlashError,
+        ItemTypeMismatch,
         KeyNotFound,
     };
     use esp_nvs::{
         EntryStatistics,
+        ItemType,
         Key,
         NvsStatistics,
         PageStatistics,
@@ -673,9 +1444,74 @@ mod overwrite {
 
     use crate::common;
 
+    #[derive(Debug, PartialEq, Clone)]
+    enum Value {
+        U32(u32),
+        Str(String),
+        Blob(Vec<u8>),
+    }
+
+    #[test]
+    fn type_change_is_atomic() {
+        let namespace = Key::from_str("ns1");
+        let key = Key::from_str("value");
+        let set = |nvs: &mut esp_nvs::Nvs<&mut common::MemFlash>, value: &Value| match value {
+            Value::U32(v) => nvs.set(&namespace, &key, *v),
+            Value::Str(v) => nvs.set(&namespace, &key, v.as_str()),
+            Value::Blob(v) => nvs.set(&namespace, &key, v.as_slice()),
+        };
+        let get = |nvs: &mut esp_nvs::Nvs<&mut common::MemFlash>| match nvs.get::<u32>(&namespace, &key) {
+            Ok(v) => Value::U32(v),
+            Err(ItemTypeMismatch(ItemType::Sized)) => Value::Str(nvs.get(&namespace, &key).unwrap()),
+            Err(ItemTypeMismatch(ItemType::BlobIndex)) => Value::Blob(nvs.get(&namespace, &key).unwrap()),
+            Err(e) => panic!("unexpected error {e:?}"),
+        };
+
+        let blob = Value::Blob((u8::MIN..u8::MAX).cycle().take(5000).collect());
+        let string = Value::Str("a string spanning multiple entries".to_string());
+        let changes = [
+            (blob.clone(), Value::U32(42)),
+            (Value::U32(42), string.clone()),
+            (string.clone(), blob.clone()),
+        ];
+
+        for (old, new) in changes {
+            // fail the change after every single flash operation until it succeeds
+            for operations in 0.. {
+                let mut flash = common::flash(3);
+                set(&mut esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap(), &old).unwrap();
+
+                flash.set_fail_after_operation(flash.operation_count() + operations);
+                let result = match esp_nvs::Nvs::new(0, flash.len(), &mut flash) {
+                    Ok(mut nvs) => set(&mut nvs, &new),
+                    Err(e) => Err(e),
+                };
+                flash.disable_faults();
+
+                let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+                let current = get(&mut nvs);
+                let items = nvs
+                    .typed_entries()
+                    .filter(|entry| entry.as_ref().unwrap().1 == key)
+                    .count();
+                assert_eq!(items, 1, "leftover items after {operations} operations");
+
+                if result.is_ok() {
+                    assert_eq!(current, new);
+                    break;
+                }
+                assert_eq!(result, Err(FlashError));
+                assert!(
+                    current == old || current == new,
+                    "lost the value after {operations} operations"
+                );
+            }
+        }
+    }
+
     #[test]
     fn primitive_overwrites_primitive() {
-        let mut flash = common::Flash::new(2);
+        let mut flash = common::flash(2);
 
         let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
         nvs.set(&Key::from_str("ns1"), &Key::from_str("my_primitive"), 42u8)
@@ -726,7 +1562,7 @@ mod overwrite {
 
     #[test]
     fn primitive_ensure_write_before_delete() {
-        let mut flash = common::Flash::new_with_fault(2, 10);
+        let mut flash = common::flash_with_fault(2, 10);
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -746,9 +1582,47 @@ mod overwrite {
         assert_eq!(nvs.get::<u8>(&Key::from_str("ns1"), &Key::from_str("item")).unwrap(), 2);
     }
 
+    #[test]
+    fn primitive_overwrites_blob_and_back() {
+        let mut flash = common::flash(3);
+        let namespace = Key::from_str("ns1");
+        let key = Key::from_str("item");
+        // spans two pages so the blob has two data chunks
+        let blob = vec![0xAB; 5000];
+        let blob_entries;
+
+        {
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            nvs.set(&namespace, &key, blob.as_slice()).unwrap();
+            blob_entries = nvs.statistics().unwrap().entries_overall.written;
+            nvs.set(&namespace, &key, 42u32).unwrap();
+
+            assert_eq!(nvs.get::<u32>(&namespace, &key).unwrap(), 42);
+            assert_eq!(
+                nvs.get::<Vec<u8>>(&namespace, &key),
+                Err(ItemTypeMismatch(ItemType::U32))
+            );
+            // only the namespace and the primitive are left
+            assert_eq!(nvs.statistics().unwrap().entries_overall.written, 2);
+        }
+
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        assert_eq!(nvs.get::<u32>(&namespace, &key).unwrap(), 42);
+
+        nvs.set(&namespace, &key, "text").unwrap();
+        nvs.set(&namespace, &key, blob.as_slice()).unwrap();
+        assert_eq!(nvs.get::<Vec<u8>>(&namespace, &key).unwrap(), blob);
+        assert_eq!(
+            nvs.get::<u32>(&namespace, &key),
+            Err(ItemTypeMismatch(ItemType::BlobIndex))
+        );
+        // the primitive and the string are gone
+        assert_eq!(nvs.statistics().unwrap().entries_overall.written, blob_entries);
+    }
+
     #[test]
     fn blob_overwrites_blob() {
-        let mut flash = common::Flash::new(6);
+        let mut flash = common::flash(6);
 
         let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
 
@@ -912,7 +1786,7 @@ mod overwrite {
         // fail_after_operations is the highest value that makes writing the blob fail.
         // That means that already parts of blob have been written to flash but the old but the
         // chunk index is missing -> there are orphaned chunks on the flash.
-        let mut flash = common::Flash::new_with_fault(3, 14);
+        let mut flash = common::flash_with_fault(3, 14);
 
         let blob = (u8::MIN..u8::MAX).cycle().take(4096).collect::<Vec<_>>();
         {
@@ -975,7 +1849,7 @@ mod overwrite {
         // fail_after_operations is the highest value that makes writing the changed block fail.
         // That means that already parts of blob_changed have been written to flash but the old
         // chunk_index has not been marked as erased yet.
-        let mut flash = common::Flash::new_with_fault(4, 23);
+        let mut flash = common::flash_with_fault(4, 23);
 
         let blob_initial = (u8::MIN..u8::MAX).cycle().take(4096).collect::<Vec<_>>();
         {
@@ -1010,7 +1884,7 @@ mod overwrite {
     fn blob_overwrites_blob_atomicity_fail_to_delete_old() {
         // fail_after_operations is the highest value that makes deleting the old, overwritten block
         // fail.
-        let mut flash = common::Flash::new_with_fault(5, 39);
+        let mut flash = common::flash_with_fault(5, 39);
 
         // a page has 126 entries
         // the first page contains the namespace, the header for the blob_data and the first 124*32
@@ -1054,7 +1928,7 @@ mod overwrite {
     fn blob_overwrites_blob_atomicity_fail_to_delete_old_twice() {
         // fail_after_operations is the highest value that makes deleting the old, overwritten block
         // fail.
-        let mut flash = common::Flash::new_with_fault(8, 60);
+        let mut flash = common::flash_with_fault(8, 60);
 
         // a page has 126 entries
         // the first page contains the namespace, the header for the blob_data and the first 124*32
@@ -1096,6 +1970,31 @@ mod overwrite {
             blob_initial
         );
     }
+
+    #[test]
+    fn blob_with_corrupted_data_falls_back_to_old_version() {
+        let mut flash = common::flash(3);
+        let namespace = Key::from_str("ns1");
+        let key = Key::from_str("blob");
+        {
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            nvs.set(&namespace, &key, [1u8, 2, 3].as_slice()).unwrap();
+            nvs.set(&namespace, &key, [4u8, 5, 6].as_slice()).unwrap();
+        }
+
+        // entry 0 is the namespace, entries 1-3 the old version (chunk, data, index) and 4-6 the
+        // new one. Undo erasing the old version as if deleting it failed.
+        for entry in 1..=3 {
+            let bits = &mut flash.as_mut_slice()[32 + entry / 4];
+            *bits = (*bits & !(0b11 << ((entry % 4) * 2))) | (0b10 << ((entry % 4) * 2));
+        }
+        // corrupt the data of the new version, the chunk itself stays valid
+        flash.as_mut_slice()[64 + 5 * 32] ^= 0xFF;
+
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        assert_eq!(nvs.get::<Vec<u8>>(&namespace, &key), Ok(vec![1, 2, 3]));
+        assert_eq!(nvs.statistics().unwrap().entries_overall.written, 4);
+    }
 }
 
 // TODO overwrite small blob with fail to erase
@@ -1113,9 +2012,35 @@ mod defrag {
     use crate::common;
     use crate::common::Operation;
 
+    #[test]
+    fn page_sequences() {
+        let mut flash = common::flash(3);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        assert_eq!(nvs.page_sequences(), vec![(0, None), (0x1000, None), (0x2000, None)]);
+        assert_eq!(nvs.next_sequence(), 0);
+
+        for i in 0..(125 + 126) {
+            nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), i).unwrap();
+        }
+        assert_eq!(
+            nvs.page_sequences(),
+            vec![(0, Some(0)), (0x1000, Some(1)), (0x2000, None)]
+        );
+        assert_eq!(nvs.next_sequence(), 2);
+
+        // the first page is reclaimed into the last one
+        nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), i32::MAX)
+            .unwrap();
+        assert_eq!(
+            nvs.page_sequences(),
+            vec![(0, None), (0x1000, Some(1)), (0x2000, Some(2))]
+        );
+        assert_eq!(nvs.next_sequence(), 3);
+    }
+
     #[test]
     fn defragmentation() {
-        let mut flash = common::Flash::new(3);
+        let mut flash = common::flash(3);
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -1126,7 +2051,7 @@ mod defrag {
             }
         }
 
-        assert_eq!(flash.erases(), 0);
+        assert_eq!(common::erases(&flash), 0);
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -1174,12 +2099,50 @@ mod defrag {
             );
         }
 
-        assert_eq!(flash.erases(), 1);
+        assert_eq!(common::erases(&flash), 1);
+    }
+
+    #[test]
+    fn lookups_stay_correct_while_items_move() {
+        let mut flash = common::flash(6);
+        let namespaces = ["ns0", "ns1", "ns2", "ns3"].map(Key::from_str);
+        let keys: Vec<Key> = (0..10).map(|i| Key::from_str(&format!("key{i}"))).collect();
+
+        {
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            // enough rounds to reclaim every page multiple times
+            for round in 0..20u32 {
+                for ns in &namespaces {
+                    for key in &keys {
+                        nvs.set(ns, key, round).unwrap();
+                    }
+                }
+                nvs.delete(&namespaces[0], &keys[round as usize % keys.len()]).unwrap();
+            }
+            nvs.compact().unwrap();
+
+            assert!(!nvs.contains(&namespaces[0], &keys[9]).unwrap());
+            for ns in &namespaces[1..] {
+                for key in &keys {
+                    assert_eq!(nvs.get::<u32>(ns, key), Ok(19));
+                }
+            }
+        }
+
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(nvs.contains(&namespaces[0], key).unwrap(), i != 9);
+        }
+        for ns in &namespaces[1..] {
+            for key in &keys {
+                assert_eq!(nvs.get::<u32>(ns, key), Ok(19));
+            }
+        }
     }
 
     #[test]
     fn page_freeing_no_fault() {
-        let mut flash = common::Flash::new(2);
+        let mut flash = common::flash(2);
 
         {
             // we fill hald the page with persistent data, the other half with erased entries
@@ -1291,7 +2254,7 @@ mod defrag {
     #[test]
     fn page_freeing_fault_before_copy() {
         // Set up initial state with pages full and ready for defragmentation
-        let mut flash = common::Flash::new_with_fault(2, 380);
+        let mut flash = common::flash_with_fault(2, 380);
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -1352,7 +2315,7 @@ mod defrag {
     #[test]
     fn page_freeing_fault_before_marking_as_freeing() {
         // Set up initial state with pages full and ready for defragmentation
-        let mut flash = common::Flash::new_with_fault(2, 381);
+        let mut flash = common::flash_with_fault(2, 381);
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -1373,7 +2336,7 @@ mod defrag {
 
         // the last successful operation was to set the state to freeing
         assert_eq!(
-            flash.operations.last().unwrap(),
+            flash.operations().last().unwrap(),
             &Operation::Write { offset: 0, len: 4 }
         );
 
@@ -1435,7 +2398,7 @@ mod defrag {
         use esp_nvs::error::Error::FlashError;
 
         // Set up initial state with pages full and ready for defragmentation
-        let mut flash = common::Flash::new(2);
+        let mut flash = common::flash(2);
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -1452,7 +2415,7 @@ mod defrag {
         // The defragmentation starts around operation 380 relative to this point.
         // Copying happens from operations 384-575 (192 operations).
         // We inject fault halfway through copying at operation 480.
-        flash.fail_after_operation = flash.operations.len() + 99;
+        flash.set_fail_after_operation(flash.operation_count() + 99);
 
         {
             // set() will trigger defragmentation and fail during the copy phase
@@ -1463,20 +2426,20 @@ mod defrag {
 
         // the last successful operation was write an item to the new page
         assert_eq!(
-            flash.operations.last().unwrap(),
+            flash.operations().last().unwrap(),
             &Operation::Write { offset: 5152, len: 32 }
         );
 
         // Disable faults and verify system recovers
         flash.disable_faults();
-        flash.operations.clear();
+        flash.clear_operations();
 
         {
             let _ = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
         }
 
         // If there are only two operations, the defragmentation was not recovered
-        assert!(flash.operations.len() > 2);
+        assert!(flash.operation_count() > 2);
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -1534,7 +2497,7 @@ mod defrag {
         use esp_nvs::error::Error::FlashError;
 
         // Set up initial state
-        let mut flash = common::Flash::new(2);
+        let mut flash = common::flash(2);
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -1551,7 +2514,7 @@ mod defrag {
         // From test output: erase happens at operation #576 (196 operations after initial setup at
         // #380) Inject fault at operation 195 to fail at operation 575 (just before erase
         // at 576)
-        flash.fail_after_operation = flash.operations.len() + 195;
+        flash.set_fail_after_operation(flash.operation_count() + 195);
 
         {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -1595,6 +2558,49 @@ mod defrag {
         }
     }
 
+    #[test]
+    fn multiple_freeing_pages() {
+        // two interrupted reclamations leave two pages in the freeing state, only one of them is
+        // continued when loading, the other one has to be reclaimed by the next defragmentation
+        let mut flash = common::flash(4);
+
+        {
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            for i in 0u8..62 {
+                nvs.set(&Key::from_str("ns1"), &Key::from_str(&format!("unique_{i}")), i)
+                    .unwrap();
+            }
+            for i in 0u32..200 {
+                nvs.set(&Key::from_str("ns1"), &Key::from_str("duplicate"), i).unwrap();
+            }
+        }
+
+        let freeing = (esp_nvs::PageState::Freeing as u32).to_le_bytes();
+        for page in 0..2 {
+            let offset = page * esp_nvs::FLASH_SECTOR_SIZE;
+            flash.as_mut_slice()[offset..offset + 4].copy_from_slice(&freeing);
+        }
+
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        for i in 200u32..600 {
+            nvs.set(&Key::from_str("ns1"), &Key::from_str("duplicate"), i).unwrap();
+        }
+
+        assert_eq!(
+            nvs.get::<u32>(&Key::from_str("ns1"), &Key::from_str("duplicate"))
+                .unwrap(),
+            599
+        );
+        for i in 0u8..62 {
+            assert_eq!(
+                nvs.get::<u8>(&Key::from_str("ns1"), &Key::from_str(&format!("unique_{i}")))
+                    .unwrap(),
+                i
+            );
+        }
+        assert_eq!(nvs.statistics().unwrap().pages.erasing, 0);
+    }
+
     #[test]
     fn ensure_active_page_is_in_correct_spot_after_init() {
         // Our code depends on the invariant that the internal `Nvs::pages` vector always stores
@@ -1610,7 +2616,7 @@ mod defrag {
         // `Nvs::pages` is not marked as active and the defragmentation process is started again.
         // Now, when the first page is evaluated if it is eligible for defragmentation, the
         // code trips on an `unreachable!()` as the `Active` state is not expected.
-        let mut flash = common::Flash::new(3);
+        let mut flash = common::flash(3);
 
         for i in 0..5u32 {
             let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
@@ -1625,7 +2631,370 @@ mod defrag {
         }
     }
 
-    // TODO: in case we we want to write a sized item to a page and it doesn't fit, before
-    //  allocating an new empty page and defragmenting into it we can try to fill the still empty
-    // entries first
+    #[test]
+    fn compact_active_page() {
+        let mut flash = common::flash(3);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+
+        for i in 0..10 {
+            nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), i).unwrap();
+        }
+
+        assert_eq!(nvs.compact(), Ok(9));
+        assert_eq!(nvs.get::<i32>(&Key::from_str("ns1"), &Key::from_str("value")), Ok(9));
+        assert_eq!(
+            nvs.statistics().unwrap().entries_overall,
+            EntryStatistics {
+                empty: 376,
+                written: 2,
+                erased: 0,
+                illegal: 0,
+            }
+        );
+
+        // nothing left to reclaim
+        assert_eq!(nvs.compact(), Ok(0));
+    }
+
+    #[test]
+    fn compact_keeps_legacy_blob() {
+        let mut flash = common::flash(3);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+
+        nvs.set(
+            &Key::from_str("ns1"),
+            &Key::from_str("legacy"),
+            esp_nvs::LegacyBlob(&[1, 2, 3]),
+        )
+        .unwrap();
+        for i in 0..10 {
+            nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), i).unwrap();
+        }
+
+        assert_eq!(nvs.compact(), Ok(9));
+        assert_eq!(
+            nvs.get::<Vec<u8>>(&Key::from_str("ns1"), &Key::from_str("legacy")),
+            Ok(vec![1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn compact_full_page() {
+        let mut flash = common::flash(3);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+
+        // namespace + 125 values fill up the first page
+        for i in 0..125 {
+            nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), i).unwrap();
+        }
+        for i in 0..5 {
+            nvs.set(&Key::from_str("ns1"), &Key::from_str("other"), i).unwrap();
+        }
+
+        // 124 erased entries are reclaimed, the 121 empty entries of the active page are given up
+        assert_eq!(nvs.compact(), Ok(3));
+        assert_eq!(nvs.get::<i32>(&Key::from_str("ns1"), &Key::from_str("value")), Ok(124));
+        assert_eq!(nvs.get::<i32>(&Key::from_str("ns1"), &Key::from_str("other")), Ok(4));
+        assert_eq!(
+            nvs.statistics().unwrap().pages,
+            PageStatistics {
+                empty: 1,
+                active: 1,
+                full: 1,
+                erasing: 0,
+                corrupted: 0,
+            }
+        );
+
+        // the active page is still used for new values
+        nvs.set(&Key::from_str("ns1"), &Key::from_str("new"), 1u8).unwrap();
+        assert_eq!(nvs.statistics().unwrap().pages.active, 1);
+
+        // giving up the free entries of the active page isn't worth it anymore
+        assert_eq!(nvs.compact(), Ok(0));
+    }
+
+    #[test]
+    fn reclaims_mostly_erased_page_before_last_free_page() {
+        let mut flash = common::flash(5);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        let erases = |nvs: &esp_nvs::Nvs<_>| nvs.erase_counts().iter().map(|(_, count)| count).sum::<u32>();
+
+        // namespace + 125 values fill up the first page, 2 * 126 values the next two
+        for i in 0..(125 + 2 * 126) {
+            nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), i).unwrap();
+        }
+        assert_eq!(erases(&nvs), 0);
+        assert_eq!(nvs.statistics().unwrap().pages.empty, 2);
+
+        // instead of using the last free page besides the reserved one, the first page is
+        // reclaimed, only the namespace entry has to be copied
+        nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), -1).unwrap();
+        assert_eq!(erases(&nvs), 1);
+        assert_eq!(nvs.get::<i32>(&Key::from_str("ns1"), &Key::from_str("value")), Ok(-1));
+        assert_eq!(
+            nvs.statistics().unwrap().pages,
+            PageStatistics {
+                empty: 2,
+                active: 1,
+                full: 2,
+                erasing: 0,
+                corrupted: 0,
+            }
+        );
+        assert_eq!(nvs.used_entries(), 2);
+    }
+
+    #[test]
+    fn compact_fault_at_any_point() {
+        let mut completed = false;
+        for fault_after in 0..50 {
+            let mut flash = common::flash(3);
+
+            {
+                let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+                for i in 0..125 {
+                    nvs.set(&Key::from_str("ns1"), &Key::from_str("value"), i).unwrap();
+                }
+                // header + 2 data entries on the active page
+                nvs.set(
+                    &Key::from_str("ns1"),
+                    &Key::from_str("string"),
+                    "hello".repeat(8).as_str(),
+                )
+                .unwrap();
+            }
+
+            flash.set_fail_after_operation(flash.operation_count() + 3 + fault_after);
+
+            {
+                let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+                match nvs.compact() {
+                    Ok(freed) => {
+                        assert_eq!(freed, 1);
+                        completed = true;
+                    }
+                    Err(e) => assert_eq!(e, FlashError),
+                }
+            }
+
+            flash.disable_faults();
+
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            assert_eq!(nvs.get::<i32>(&Key::from_str("ns1"), &Key::from_str("value")), Ok(124));
+            assert_eq!(
+                nvs.get::<String>(&Key::from_str("ns1"), &Key::from_str("string")),
+                Ok("hello".repeat(8))
+            );
+        }
+
+        assert!(completed);
+    }
+}
+
+mod batch {
+    use esp_nvs::Key;
+    use esp_nvs::error::Error;
+    use pretty_assertions::assert_eq;
+
+    use crate::common;
+
+    #[test]
+    fn commit_applies_last_operation_per_key() {
+        let mut flash = common::flash(3);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        let namespace = Key::from_str("ns");
+        nvs.set(&namespace, &Key::from_str("deleted"), 1u8).unwrap();
+
+        let mut batch = nvs.batch();
+        batch.set(&namespace, &Key::from_str("value"), 1u32).unwrap();
+        batch.set(&namespace, &Key::from_str("value"), 2u32).unwrap();
+        batch.set(&namespace, &Key::from_str("string"), "text").unwrap();
+        batch.set(&namespace, &Key::from_str("blob"), &[1u8, 2][..]).unwrap();
+        batch.delete(&namespace, &Key::from_str("deleted")).unwrap();
+        assert_eq!(batch.len(), 4);
+        batch.commit().unwrap();
+
+        assert_eq!(nvs.get::<u32>(&namespace, &Key::from_str("value")), Ok(2));
+        assert_eq!(nvs.get::<String>(&namespace, &Key::from_str("string")).unwrap(), "text");
+        assert_eq!(
+            nvs.get::<Vec<u8>>(&namespace, &Key::from_str("blob")).unwrap(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            nvs.get::<u8>(&namespace, &Key::from_str("deleted")),
+            Err(Error::KeyNotFound)
+        );
+        // namespace, the value, the string with its data and the blob index with its data chunk
+        assert_eq!(nvs.statistics().unwrap().entries_overall.written, 7);
+    }
+
+    #[test]
+    fn primitives_are_packed() {
+        let mut flash = common::flash(3);
+        let namespace = Key::from_str("ns");
+        let keys: Vec<Key> = (0..10).map(|i| Key::from_str(&format!("value{i}"))).collect();
+        {
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            nvs.set(&namespace, &keys[0], 0u32).unwrap();
+            nvs.set(&namespace, &keys[1], 1u32).unwrap();
+        }
+
+        let writes = |flash: &common::MemFlash| {
+            flash
+                .operations()
+                .iter()
+                .filter(|operation| matches!(operation, common::Operation::Write { .. }))
+                .count()
+        };
+        flash.clear_operations();
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        let mut batch = nvs.batch();
+        for (i, key) in keys.iter().enumerate() {
+            batch.set(&namespace, key, i as u32 * 10).unwrap();
+        }
+        batch.commit().unwrap();
+        drop(nvs);
+
+        // the nine changed values with a single write, one bitmap update and the erased old value
+        assert_eq!(writes(&flash), 3);
+
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(nvs.get::<u32>(&namespace, key), Ok(i as u32 * 10));
+        }
+        // namespace and one entry per value
+        assert_eq!(nvs.statistics().unwrap().entries_overall.written, 11);
+    }
+
+    #[test]
+    fn packed_primitives_spanning_pages() {
+        let mut flash = common::flash(4);
+        let namespace = Key::from_str("ns");
+        {
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            // namespace, values and the string leave 3 free entries on the first page
+            for i in 0..120u32 {
+                nvs.set(&namespace, &Key::from_str(&format!("old{i}")), i).unwrap();
+            }
+            nvs.set(&namespace, &Key::from_str("type"), "a string").unwrap();
+
+            let mut batch = nvs.batch();
+            for i in 0..20u32 {
+                batch
+                    .set(&namespace, &Key::from_str(&format!("new{i}")), i as u8)
+                    .unwrap();
+            }
+            batch.set(&namespace, &Key::from_str("old0"), -1i64).unwrap();
+            batch.set(&namespace, &Key::from_str("type"), true).unwrap();
+            batch.commit().unwrap();
+        }
+
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        for i in 0..20u32 {
+            assert_eq!(
+                nvs.get::<u8>(&namespace, &Key::from_str(&format!("new{i}"))),
+                Ok(i as u8)
+            );
+        }
+        for i in 1..120u32 {
+            assert_eq!(nvs.get::<u32>(&namespace, &Key::from_str(&format!("old{i}"))), Ok(i));
+        }
+        assert_eq!(nvs.get::<i64>(&namespace, &Key::from_str("old0")), Ok(-1));
+        assert_eq!(nvs.get::<bool>(&namespace, &Key::from_str("type")), Ok(true));
+        // namespace and one entry per value
+        assert_eq!(nvs.statistics().unwrap().entries_overall.written, 1 + 120 + 20 + 1);
+    }
+
+    #[test]
+    fn packed_primitives_survive_power_loss() {
+        let namespace = Key::from_str("ns");
+        let keys: Vec<Key> = (0..6).map(|i| Key::from_str(&format!("value{i}"))).collect();
+
+        // fail the commit after every single flash operation until it succeeds
+        for operations in 0.. {
+            let mut flash = common::flash(3);
+            {
+                let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+                for key in &keys[..3] {
+                    nvs.set(&namespace, key, 1u32).unwrap();
+                }
+            }
+
+            flash.set_fail_after_operation(flash.operation_count() + operations);
+            let result = esp_nvs::Nvs::new(0, flash.len(), &mut flash).and_then(|mut nvs| {
+                let mut batch = nvs.batch();
+                for key in &keys {
+                    batch.set(&namespace, key, 2u32)?;
+                }
+                batch.commit()
+            });
+            flash.disable_faults();
+
+            let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+            for (i, key) in keys.iter().enumerate() {
+                let value = nvs.get::<u32>(&namespace, key);
+                match result {
+                    Ok(()) => assert_eq!(value, Ok(2)),
+                    Err(_) if i < 3 => assert!(value == Ok(1) || value == Ok(2), "lost {i} after {operations}"),
+                    Err(_) => assert!(value == Err(Error::KeyNotFound) || value == Ok(2)),
+                }
+            }
+            let entries = nvs.typed_entries().count();
+            assert!(entries <= keys.len(), "duplicates after {operations} operations");
+
+            if result.is_ok() {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_operations_are_rejected_when_added() {
+        let mut flash = common::flash(3);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+        let namespace = Key::from_str("ns");
+        let max_blob_size = nvs.max_blob_size();
+
+        let mut batch = nvs.batch();
+        batch.set(&namespace, &Key::from_str("value"), 1u8).unwrap();
+        assert_eq!(
+            batch.set(&namespace, &Key::from_array(b"a\0b"), 1u8),
+            Err(Error::KeyMalformed)
+        );
+        assert_eq!(
+            batch.delete(&Key::from_array(b"a\0b"), &Key::from_str("value")),
+            Err(Error::NamespaceMalformed)
+        );
+        assert_eq!(
+            batch.set(&namespace, &Key::from_str("string"), "x".repeat(4000).as_str()),
+            Err(Error::ValueTooLong)
+        );
+        assert_eq!(
+            batch.set(&namespace, &Key::from_str("blob"), vec![0u8; max_blob_size].as_slice()),
+            Err(Error::ValueTooLong)
+        );
+        assert_eq!(batch.len(), 1);
+        batch.commit().unwrap();
+
+        assert_eq!(nvs.get::<u8>(&namespace, &Key::from_str("value")), Ok(1));
+    }
+
+    #[test]
+    fn drop_without_commit_writes_nothing() {
+        let mut flash = common::flash(3);
+        let mut nvs = esp_nvs::Nvs::new(0, flash.len(), &mut flash).unwrap();
+
+        let mut batch = nvs.batch();
+        batch.set(&Key::from_str("ns"), &Key::from_str("value"), 1u8).unwrap();
+        drop(batch);
+        drop(nvs);
+
+        assert!(
+            flash
+                .operations()
+                .iter()
+                .all(|operation| matches!(operation, common::Operation::Read { .. }))
+        );
+    }
 }